@@ -0,0 +1,77 @@
+//! Built-in extension catalog mapping several hundred formats to a MIME type
+//! and a category
+//!
+//! The catalog is `resources/extensions.tsv`, embedded at build time. Its
+//! categories are the `FileExtensions` list names plus `installers` and
+//! `fonts`, which are recognised but have no destination folder.
+
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+/// MIME type of extensions neither the configuration nor the catalog describes
+pub const DEFAULT_MIME: &str = "application/octet-stream";
+
+const SOURCE: &str = include_str!("../resources/extensions.tsv");
+
+/// A catalog line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CatalogEntry {
+    pub extension: &'static str,
+    pub category: &'static str,
+    pub mime: &'static str,
+}
+
+static CATALOG: Lazy<HashMap<&'static str, CatalogEntry>> = Lazy::new(|| {
+    SOURCE
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let entry = CatalogEntry {
+                extension: fields.next()?,
+                category: fields.next()?,
+                mime: fields.next()?,
+            };
+            Some((entry.extension, entry))
+        })
+        .collect()
+});
+
+/// Catalog entry of a lowercase `extension` without the dot.
+pub fn lookup(extension: &str) -> Option<&'static CatalogEntry> {
+    CATALOG.get(extension)
+}
+
+/// Every catalog entry, in no particular order.
+pub fn entries() -> impl Iterator<Item = &'static CatalogEntry> {
+    CATALOG.values()
+}
+
+/// Whether some catalog entry has `category`.
+pub fn has_category(category: &str) -> bool {
+    entries().any(|entry| entry.category == category)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_covers_common_formats() {
+        assert!(entries().count() > 400);
+        assert_eq!(lookup("flac").unwrap().category, "music");
+        assert_eq!(lookup("webm").unwrap().mime, "video/webm");
+        assert_eq!(lookup("heic").unwrap().category, "image");
+        assert_eq!(
+            lookup("docx").unwrap().mime,
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+        );
+        assert_eq!(lookup("deb").unwrap().category, "installers");
+        assert!(lookup("DOCX").is_none());
+        assert!(lookup("xyz").is_none());
+        for entry in entries() {
+            assert!(entry.mime.contains('/'), "{}", entry.extension);
+        }
+    }
+}
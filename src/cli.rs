@@ -0,0 +1,413 @@
+//! Headless command line: organize without the TUI and export the results
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::history::{largest_runs, weekly_totals, HistoryEntry, HistoryStore};
+use crate::logging::open_audit_log;
+use crate::organizer::engine::Organizer;
+use crate::organizer::suggestions::{suggestions, Suggestion};
+use crate::organizer::types::CollisionPolicy;
+use crate::platform::user::{effective_uid, system_user_provider};
+use crate::report::{ExportedReport, ReportFormat};
+use crate::schedule::{self, Backend, Invocation, Schedule, Trigger};
+use crate::ui::summary::{runs_table, schedules_table, summary_table, weekly_table};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(name = "rustganizer", version, about = "Console file organizer")]
+pub struct Cli {
+    /// Without a command the interactive UI starts.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Configuration file instead of the one under the config directory
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// History file instead of the one under the data directory
+    #[arg(long, global = true, value_name = "PATH")]
+    pub history_file: Option<PathBuf>,
+}
+
+impl Cli {
+    /// Loads the configuration from `--config` or the default location.
+    pub fn load_config(&self) -> Result<Config> {
+        match &self.config {
+            Some(path) => Config::load_from(path),
+            None => Config::load(),
+        }
+    }
+
+    /// Where settings are saved: `--config` or the default location.
+    pub fn config_path(&self) -> Option<PathBuf> {
+        self.config.clone().or_else(Config::default_path)
+    }
+
+    /// Where runs are recorded, if anywhere.
+    pub fn history(&self) -> Option<HistoryStore> {
+        match &self.history_file {
+            Some(path) => Some(HistoryStore::new(path)),
+            None => HistoryStore::open_default(),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Organize the given users' homes and print a summary
+    Organize(OrganizeArgs),
+    /// List past runs, most recent first
+    History {
+        /// Number of runs to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Only show runs of this user
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Show files organized per category per week and the largest runs
+    Stats {
+        /// Number of largest runs to show
+        #[arg(long, default_value_t = 10)]
+        largest: usize,
+    },
+    /// Install, list, remove or run scheduled organize runs
+    #[command(subcommand)]
+    Schedule(ScheduleCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ScheduleCommand {
+    /// Save a schedule in the config file and install it
+    Add(ScheduleArgs),
+    /// List the configured schedules and whether they are installed
+    List,
+    /// Uninstall a schedule and delete it from the config file
+    Remove {
+        /// Name of the schedule
+        name: String,
+    },
+    /// Run the builtin schedules in the foreground, for systems without systemd or cron
+    Run,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ScheduleArgs {
+    /// Name of the schedule, used for its units and crontab line
+    pub name: String,
+    /// hourly, "daily HH:MM", "weekly <day> HH:MM" or login, in local time
+    #[arg(long, value_parser = parse_trigger)]
+    pub when: Trigger,
+    /// Users to organize
+    #[arg(required_unless_present = "all")]
+    pub users: Vec<String>,
+    /// Organize every user on the system, as root only
+    #[arg(long, conflicts_with = "users")]
+    pub all: bool,
+    /// Plan the scheduled runs without touching any file
+    #[arg(long)]
+    pub dry_run: bool,
+    /// What runs the schedule: systemd, cron or builtin
+    #[arg(long, default_value = "systemd", value_parser = parse_backend)]
+    pub backend: Backend,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct OrganizeArgs {
+    /// Users to organize
+    #[arg(required_unless_present = "all")]
+    pub users: Vec<String>,
+    /// Organize every user on the system
+    #[arg(long, conflicts_with = "users")]
+    pub all: bool,
+    /// Plan without touching any file
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Language of the destination folder names; the configured one by default
+    #[arg(long)]
+    pub lang: Option<String>,
+    /// What to do when a destination exists: overwrite, rename or skip
+    #[arg(long, default_value = "rename", value_parser = parse_collision_policy)]
+    pub collision_policy: CollisionPolicy,
+    /// Write the run report to this file; may be repeated
+    #[arg(long = "report", value_name = "PATH")]
+    pub reports: Vec<PathBuf>,
+    /// Report format: json, csv or html; inferred from the file extension otherwise
+    #[arg(long, value_parser = parse_report_format)]
+    pub format: Option<ReportFormat>,
+    /// Add the suggested mappings for unrecognised extensions to the config file
+    #[arg(long)]
+    pub accept_suggestions: bool,
+}
+
+fn parse_collision_policy(value: &str) -> std::result::Result<CollisionPolicy, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_lowercase()))
+        .map_err(|_| format!("unknown collision policy `{value}`"))
+}
+
+fn parse_trigger(value: &str) -> std::result::Result<Trigger, String> {
+    value.parse().map_err(|e: Error| e.to_string())
+}
+
+fn parse_backend(value: &str) -> std::result::Result<Backend, String> {
+    value.parse().map_err(|e: Error| e.to_string())
+}
+
+fn parse_report_format(value: &str) -> std::result::Result<ReportFormat, String> {
+    value
+        .parse()
+        .map_err(|e: crate::error::Error| e.to_string())
+}
+
+/// Runs the parsed command line with `config` and returns the process exit code.
+pub fn run(cli: Cli, config: Config) -> i32 {
+    let history = cli.history();
+    let outcome = match &cli.command {
+        None => return 0,
+        Some(Command::Organize(args)) => {
+            organize(args, config, history.as_ref(), cli.config_path())
+        }
+        Some(Command::History { limit, user }) => {
+            show_history(history.as_ref(), *limit, user.as_deref()).map(|_| 0)
+        }
+        Some(Command::Stats { largest }) => show_stats(history.as_ref(), *largest).map(|_| 0),
+        Some(Command::Schedule(command)) => run_schedule_command(&cli, command, config),
+    };
+    outcome.unwrap_or_else(|e| {
+        eprintln!("rustganizer: {e}");
+        2
+    })
+}
+
+/// Organizes and returns 1 when any user had errors.
+fn organize(
+    args: &OrganizeArgs,
+    config: Config,
+    history: Option<&HistoryStore>,
+    config_path: Option<PathBuf>,
+) -> Result<i32> {
+    let user_provider = system_user_provider();
+    let users = if args.all {
+        user_provider.list_users()
+    } else {
+        args.users.clone()
+    };
+    let mut builder = Organizer::builder();
+    if let Some(lang) = &args.lang {
+        builder = builder.language(lang.clone());
+    }
+    if let Some(audit) = open_audit_log(&config.logging) {
+        builder = builder.audit(audit);
+    }
+    // As root, organize each home in a worker running as its owner
+    let report = builder
+        .config(config.clone())
+        .user_provider(user_provider)
+        .collision_policy(args.collision_policy)
+        .dry_run(args.dry_run)
+        .privilege_separation(effective_uid() == Some(0))
+        .build()
+        .run_users(&users);
+    println!("{}", summary_table(&report));
+    if let Some(history) = history {
+        // Losing the history entry is no reason to fail the run
+        if let Err(e) = history.record(&report) {
+            eprintln!("rustganizer: {e}");
+        }
+    }
+
+    let suggested = suggestions(&report.unrecognized(), &config);
+    if args.accept_suggestions && !suggested.is_empty() {
+        accept_suggestions(config, &suggested, config_path)?;
+    } else if !suggested.is_empty() {
+        println!();
+        println!("Suggested mappings, added with --accept-suggestions:");
+        for suggestion in &suggested {
+            println!("  {:<8} -> {}", suggestion.extension, suggestion.category);
+        }
+    }
+
+    let export = ExportedReport::new(report);
+    for path in &args.reports {
+        export.save(path, args.format)?;
+    }
+    Ok(i32::from(!export.run.errors().is_empty()))
+}
+
+/// Adds the suggested mappings to `config` and saves it to `path`.
+fn accept_suggestions(
+    mut config: Config,
+    suggested: &[Suggestion],
+    path: Option<PathBuf>,
+) -> Result<()> {
+    let path = path.ok_or_else(|| Error::InvalidConfig {
+        message: "there is no config file to add the mappings to".to_string(),
+    })?;
+    for suggestion in suggested {
+        if config.add_extension(suggestion.category, &suggestion.extension) {
+            println!(
+                "Mapped .{} to {}",
+                suggestion.extension, suggestion.category
+            );
+        }
+    }
+    config.save_to(&path)?;
+    println!("Saved {}", path.display());
+    Ok(())
+}
+
+fn run_schedule_command(cli: &Cli, command: &ScheduleCommand, config: Config) -> Result<i32> {
+    match command {
+        ScheduleCommand::Add(args) => add_schedule(cli, args, config).map(|_| 0),
+        ScheduleCommand::List => {
+            list_schedules(&config);
+            Ok(0)
+        }
+        ScheduleCommand::Remove { name } => remove_schedule(cli, name, config).map(|_| 0),
+        ScheduleCommand::Run => run_builtin_schedules(cli, config),
+    }
+}
+
+/// The config file schedules are saved in, which their runs also read.
+fn schedule_config_path(cli: &Cli) -> Result<PathBuf> {
+    cli.config_path().ok_or_else(|| Error::InvalidConfig {
+        message: "there is no config file to save the schedule in".to_string(),
+    })
+}
+
+fn add_schedule(cli: &Cli, args: &ScheduleArgs, mut config: Config) -> Result<()> {
+    let path = schedule_config_path(cli)?;
+    if config.schedules.iter().any(|s| s.name == args.name) {
+        return Err(Error::InvalidConfig {
+            message: format!("there already is a schedule named `{}`", args.name),
+        });
+    }
+    // Run as the invoking user, a schedule could not reach any other home
+    if args.all && effective_uid().is_some_and(|uid| uid != 0) {
+        return Err(Error::Schedule {
+            message: "only root can schedule every user; name the users instead".to_string(),
+        });
+    }
+    let schedule = Schedule {
+        name: args.name.clone(),
+        when: args.when,
+        users: if args.all {
+            Vec::new()
+        } else {
+            args.users.clone()
+        },
+        dry_run: args.dry_run,
+        backend: args.backend,
+    };
+    config.schedules.push(schedule.clone());
+    config.validate()?;
+    let invocation = Invocation {
+        executable: std::env::current_exe()?,
+        config: path.clone(),
+        history_file: cli.history_file.clone(),
+    };
+    schedule::install(&schedule, &invocation)?;
+    if let Err(e) = config.save_to(&path) {
+        // Do not leave a schedule behind that the config does not know about
+        let _ = schedule::uninstall(&schedule);
+        return Err(e);
+    }
+    println!(
+        "Scheduled {} ({}, {})",
+        schedule.name, schedule.when, schedule.backend
+    );
+    if schedule.backend == Backend::Builtin {
+        println!("It runs while `rustganizer schedule run` does.");
+    }
+    Ok(())
+}
+
+fn list_schedules(config: &Config) {
+    if config.schedules.is_empty() {
+        println!("No schedules.");
+        return;
+    }
+    let uses_cron = config.schedules.iter().any(|s| s.backend == Backend::Cron);
+    let crontab = if uses_cron {
+        schedule::read_crontab().unwrap_or_default()
+    } else {
+        String::new()
+    };
+    println!("{}", schedules_table(&config.schedules, &crontab));
+}
+
+fn remove_schedule(cli: &Cli, name: &str, mut config: Config) -> Result<()> {
+    let path = schedule_config_path(cli)?;
+    let Some(index) = config.schedules.iter().position(|s| s.name == name) else {
+        return Err(Error::InvalidConfig {
+            message: format!("there is no schedule named `{name}`"),
+        });
+    };
+    let schedule = config.schedules.remove(index);
+    schedule::uninstall(&schedule)?;
+    config.save_to(&path)?;
+    println!("Removed {name}");
+    Ok(())
+}
+
+/// Runs the builtin schedules until interrupted; each run rereads the config.
+fn run_builtin_schedules(cli: &Cli, config: Config) -> Result<i32> {
+    let schedules: Vec<Schedule> = config
+        .schedules
+        .iter()
+        .filter(|s| s.backend == Backend::Builtin)
+        .cloned()
+        .collect();
+    if schedules.is_empty() {
+        println!("There are no builtin schedules to run.");
+        return Ok(0);
+    }
+    schedule::run_loop(&schedules, |schedule| {
+        println!("Running schedule {}", schedule.name);
+        let config = cli.load_config().unwrap_or_else(|e| {
+            eprintln!("rustganizer: {e}; using the configuration loaded at start");
+            config.clone()
+        });
+        let args = OrganizeArgs {
+            users: schedule.users.clone(),
+            all: schedule.users.is_empty(),
+            dry_run: schedule.dry_run,
+            lang: None,
+            collision_policy: CollisionPolicy::Rename,
+            reports: Vec::new(),
+            format: None,
+            accept_suggestions: false,
+        };
+        if let Err(e) = organize(&args, config, cli.history().as_ref(), cli.config_path()) {
+            eprintln!("rustganizer: {e}");
+        }
+    });
+    Ok(0)
+}
+
+fn load_history(history: Option<&HistoryStore>) -> Result<Vec<HistoryEntry>> {
+    match history {
+        Some(history) => history.load(),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn show_history(history: Option<&HistoryStore>, limit: usize, user: Option<&str>) -> Result<()> {
+    let entries = load_history(history)?;
+    let recent: Vec<&HistoryEntry> = entries
+        .iter()
+        .rev()
+        .filter(|e| user.is_none_or(|u| e.username == u))
+        .take(limit)
+        .collect();
+    println!("{}", runs_table(&recent));
+    Ok(())
+}
+
+fn show_stats(history: Option<&HistoryStore>, largest: usize) -> Result<()> {
+    let entries = load_history(history)?;
+    println!("{}", weekly_table(&weekly_totals(&entries)));
+    println!();
+    println!("Largest runs:");
+    println!("{}", runs_table(&largest_runs(&entries, largest)));
+    Ok(())
+}
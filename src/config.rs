@@ -1,16 +1,32 @@
 //! Enhanced configuration management with backward compatibility
 
+use crate::catalog::{self, DEFAULT_MIME};
+use crate::error::{Error, Result};
+use crate::organizer::types::{Category, FolderStrategy, RetentionAction, SymlinkPolicy};
+use crate::schedule::Schedule;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Directory for Rustganizer's own files such as the history and logs.
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("rustganizer"))
+}
 
 /// Configuration struct to hold file organization rules and localized directory names
-#[derive(Debug, Clone)]
+///
+/// Persisted as TOML, see [`Config::load`]; keys missing from the file keep
+/// their default, and localized names and messages are not persisted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub file_extensions: FileExtensions,
+    #[serde(skip)]
     pub localized_dirs: HashMap<String, HashMap<String, String>>,
+    #[serde(skip)]
     pub error_messages: HashMap<String, ErrorMessages>,
     #[allow(dead_code)]
+    #[serde(skip)]
     pub version: String,
     #[allow(dead_code)]
     pub logging: LoggingConfig,
@@ -18,19 +34,53 @@ pub struct Config {
     pub performance: PerformanceConfig,
     #[allow(dead_code)]
     pub ui: UiConfig,
+    pub classifier: ClassifierConfig,
+    /// Directories organized in every home, in processing order
+    pub sources: Vec<SourceDirConfig>,
+    /// Gitignore-style patterns excluded in every source directory
+    pub ignore_patterns: Vec<String>,
+    pub traversal: TraversalConfig,
+    pub archives: ArchiveConfig,
+    /// Unattended runs, installed with `rustganizer schedule add`
+    pub schedules: Vec<Schedule>,
+    /// Cleanup of stale files in source directories, first match wins
+    pub retention: Vec<RetentionRule>,
 }
 
 /// File extension mappings for different categories
-#[derive(Debug, Clone)]
+///
+/// Extensions are lowercase and without the leading dot. The lists come
+/// before `overrides` and the built-in [`catalog`] fills in the rest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FileExtensions {
-    pub music: Vec<&'static str>,
-    pub videos: Vec<&'static str>,
-    pub images: Vec<&'static str>,
-    pub docs: Vec<&'static str>,
-    #[allow(dead_code)]
-    pub archives: Vec<&'static str>,
-    #[allow(dead_code)]
-    pub code: Vec<&'static str>,
+    pub music: Vec<String>,
+    pub videos: Vec<String>,
+    pub images: Vec<String>,
+    pub docs: Vec<String>,
+    pub archives: Vec<String>,
+    pub code: Vec<String>,
+    /// Look up extensions none of the lists has in the built-in catalog.
+    pub catalog: bool,
+    /// Per-extension corrections, taking precedence over lists and catalog
+    pub overrides: BTreeMap<String, ExtensionOverride>,
+}
+
+/// Replaces the category or MIME type of one extension
+///
+/// A category of `none` leaves files of the extension unrecognised.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtensionOverride {
+    pub category: Option<String>,
+    pub mime: Option<String>,
+}
+
+/// Category and MIME type of a file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileType<'a> {
+    pub category: &'a str,
+    pub mime: &'a str,
 }
 
 /// Localized error messages
@@ -42,16 +92,26 @@ pub struct ErrorMessages {
 
 /// Logging configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LoggingConfig {
     pub level: String,
     pub file: Option<PathBuf>,
     pub console: bool,
+    /// Size in bytes at which the log file is rotated.
     pub max_file_size: usize,
+    /// Rotated log files kept besides the active one.
     pub max_files: usize,
+    /// Gzip rotated log files.
+    pub compress: bool,
+    /// Record every filesystem mutation in a JSON lines audit log.
+    pub audit: bool,
+    /// Audit log location, `audit.jsonl` in [`data_dir`] by default.
+    pub audit_file: Option<PathBuf>,
 }
 
 /// Performance configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PerformanceConfig {
     pub max_concurrent_users: usize,
     pub thread_pool_size: usize,
@@ -62,6 +122,7 @@ pub struct PerformanceConfig {
 
 /// UI configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct UiConfig {
     pub theme: String,
     pub language: String,
@@ -69,6 +130,192 @@ pub struct UiConfig {
     pub show_progress: bool,
 }
 
+/// A directory in the user's home that gets organized
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceDirConfig {
+    /// Logical directory name, localized through `localized_dirs`
+    pub name: String,
+    pub folder_strategy: FolderStrategy,
+}
+
+/// Rules deciding whether a Downloads subfolder is moved as a whole
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClassifierConfig {
+    /// Weighted share of the recognised content one category needs, from 0.0 to 1.0
+    pub dominance_threshold: f64,
+    /// How much byte size counts against file count, from 0.0 (counts only) to 1.0 (bytes only)
+    pub byte_weight: f64,
+    /// Fraction of all files in the folder that must be recognised at all
+    pub min_recognized_ratio: f64,
+    /// Entry names marking a project checkout. A folder holding one directly
+    /// moves as a unit to the projects folder; one holding it deeper stays.
+    pub project_markers: Vec<String>,
+}
+
+/// Cleans up files at the top of source directories that were not modified
+/// for a while
+///
+/// A file falls under the rule when it is old enough and every filter that
+/// is not empty matches it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionRule {
+    /// Days since the file was last modified
+    pub older_than_days: u64,
+    /// Lowercase extensions without the dot
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// Category names as `Config::get_file_category` reports them, e.g. `installers`
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Logical names of the source directories, e.g. `Downloads`
+    #[serde(default)]
+    pub sources: Vec<String>,
+    pub action: RetentionAction,
+    /// Folder relative to the home that `move` sends files to
+    #[serde(default)]
+    pub move_to: Option<PathBuf>,
+}
+
+/// How folder trees are walked when analyzing and organizing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TraversalConfig {
+    pub symlinks: SymlinkPolicy,
+    /// Deepest level looked into below a folder; `None` walks the whole tree.
+    ///
+    /// Written as `max_depth = 0` in the file, since TOML has no null.
+    #[serde(with = "depth_limit")]
+    pub max_depth: Option<usize>,
+    /// Do not descend into directories mounted from another filesystem.
+    pub same_filesystem: bool,
+}
+
+/// `TraversalConfig::max_depth` as stored, 0 meaning no limit
+mod depth_limit {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        depth: &Option<usize>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(depth.unwrap_or(0) as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<usize>, D::Error> {
+        Ok(Some(usize::deserialize(deserializer)?).filter(|depth| *depth > 0))
+    }
+}
+
+impl Default for TraversalConfig {
+    fn default() -> Self {
+        Self {
+            symlinks: SymlinkPolicy::Skip,
+            max_depth: Some(32),
+            same_filesystem: true,
+        }
+    }
+}
+
+/// What is done with archives found in source directories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    /// List zip and tar archives to move them to the category of their contents.
+    pub inspect: bool,
+    /// Largest archive listed, in bytes; bigger ones stay in Archives.
+    pub max_inspected_bytes: u64,
+    /// Unpack inspected archives into a folder at their destination, then
+    /// move them to the trash.
+    pub extract: bool,
+    /// Largest total size unpacked from one archive, in bytes.
+    pub max_extracted_bytes: u64,
+    /// Most entries read from one archive.
+    pub max_entries: usize,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            inspect: true,
+            max_inspected_bytes: 100 * 1024 * 1024, // 100MiB
+            extract: false,
+            max_extracted_bytes: 1024 * 1024 * 1024, // 1GiB
+            max_entries: 5_000,
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            file: None,
+            console: true,
+            max_file_size: 10 * 1024 * 1024, // 10MB
+            max_files: 5,
+            compress: false,
+            audit: true,
+            audit_file: None,
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// Where the audit log goes when auditing is enabled.
+    pub fn audit_path(&self) -> Option<PathBuf> {
+        if !self.audit {
+            return None;
+        }
+        self.audit_file
+            .clone()
+            .or_else(|| data_dir().map(|dir| dir.join("audit.jsonl")))
+    }
+}
+
+impl Default for PerformanceConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_users: 4,
+            thread_pool_size: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4),
+            batch_size: 100,
+            buffer_size: 8192,
+            use_parallel_processing: true,
+        }
+    }
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            theme: "default".to_string(),
+            language: "en".to_string(),
+            auto_refresh: true,
+            show_progress: true,
+        }
+    }
+}
+
+impl Default for ClassifierConfig {
+    fn default() -> Self {
+        Self {
+            dominance_threshold: 0.7,
+            byte_weight: 0.5,
+            min_recognized_ratio: 0.5,
+            project_markers: vec![
+                ".git".to_string(),
+                "Cargo.toml".to_string(),
+                "package.json".to_string(),
+                "pyproject.toml".to_string(),
+            ],
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         let mut localized_dirs = HashMap::new();
@@ -81,6 +328,8 @@ impl Default for Config {
             ("Videos".to_string(), "Videos".to_string()),
             ("Pictures".to_string(), "Pictures".to_string()),
             ("Documents".to_string(), "Documents".to_string()),
+            ("Archives".to_string(), "Archives".to_string()),
+            ("Projects".to_string(), "Projects".to_string()),
         ]);
         localized_dirs.insert("en".to_string(), english_dirs);
 
@@ -92,6 +341,8 @@ impl Default for Config {
             ("Videos".to_string(), "Vídeos".to_string()),
             ("Pictures".to_string(), "Imágenes".to_string()),
             ("Documents".to_string(), "Documentos".to_string()),
+            ("Archives".to_string(), "Archivos comprimidos".to_string()),
+            ("Projects".to_string(), "Proyectos".to_string()),
         ]);
         localized_dirs.insert("es".to_string(), spanish_dirs);
 
@@ -114,41 +365,41 @@ impl Default for Config {
             file_extensions: FileExtensions::default(),
             localized_dirs,
             error_messages,
-            logging: LoggingConfig {
-                level: "info".to_string(),
-                file: None,
-                console: true,
-                max_file_size: 10 * 1024 * 1024, // 10MB
-                max_files: 5,
-            },
-            performance: PerformanceConfig {
-                max_concurrent_users: 4,
-                thread_pool_size: std::thread::available_parallelism()
-                    .map(|n| n.get())
-                    .unwrap_or(4),
-                batch_size: 100,
-                buffer_size: 8192,
-                use_parallel_processing: true,
-            },
-            ui: UiConfig {
-                theme: "default".to_string(),
-                language: "en".to_string(),
-                auto_refresh: true,
-                show_progress: true,
-            },
+            logging: LoggingConfig::default(),
+            performance: PerformanceConfig::default(),
+            ui: UiConfig::default(),
+            classifier: ClassifierConfig::default(),
+            sources: vec![
+                SourceDirConfig {
+                    name: "Downloads".to_string(),
+                    folder_strategy: FolderStrategy::MoveWhole,
+                },
+                SourceDirConfig {
+                    name: "Desktop".to_string(),
+                    folder_strategy: FolderStrategy::Skip,
+                },
+            ],
+            ignore_patterns: Vec::new(),
+            traversal: TraversalConfig::default(),
+            archives: ArchiveConfig::default(),
+            schedules: Vec::new(),
+            retention: Vec::new(),
         }
     }
 }
 
 impl Default for FileExtensions {
     fn default() -> Self {
+        let list = |extensions: &[&str]| extensions.iter().map(|e| e.to_string()).collect();
         Self {
-            music: vec!["mp3", "ogg", "wav", "flac"],
-            videos: vec!["mp4", "avi", "mkv", "mov"],
-            images: vec!["png", "jpg", "jpeg", "gif"],
-            docs: vec!["pdf", "txt", "epub"],
-            archives: vec!["zip", "rar", "7z"],
-            code: vec!["rs", "py", "js"],
+            music: list(&["mp3", "ogg", "wav", "flac"]),
+            videos: list(&["mp4", "avi", "mkv", "mov"]),
+            images: list(&["png", "jpg", "jpeg", "gif"]),
+            docs: list(&["pdf", "txt", "epub"]),
+            archives: list(&["zip", "rar", "7z"]),
+            code: list(&["rs", "py", "js"]),
+            catalog: true,
+            overrides: BTreeMap::new(),
         }
     }
 }
@@ -190,25 +441,280 @@ impl Config {
         fallback.replace("{username}", username)
     }
 
-    /// Check if a file extension belongs to a specific category
-    #[allow(dead_code)]
-    pub fn get_file_category(&self, extension: &str) -> Option<&'static str> {
+    /// Returns the organizer category for a file extension, if it has a destination
+    pub fn get_category(&self, extension: &str) -> Option<Category> {
+        self.get_file_category(extension)
+            .and_then(|file_type| Category::from_name(file_type.category))
+    }
+
+    /// Category and MIME type of a file extension: an override, else the
+    /// list containing it, else the built-in catalog when enabled.
+    pub fn get_file_category(&self, extension: &str) -> Option<FileType<'_>> {
         let ext = extension.to_lowercase();
+        let known = self.known_file_type(&ext);
+        let Some(correction) = self.file_extensions.overrides.get(&ext) else {
+            return known;
+        };
+        let category = match correction.category.as_deref() {
+            Some("none") => return None,
+            Some(category) => category,
+            None => known?.category,
+        };
+        let mime = correction
+            .mime
+            .as_deref()
+            .or(known.map(|file_type| file_type.mime))
+            .unwrap_or(DEFAULT_MIME);
+        Some(FileType { category, mime })
+    }
+
+    fn known_file_type(&self, ext: &str) -> Option<FileType<'static>> {
+        let entry = catalog::lookup(ext);
+        let listed = self
+            .file_extensions
+            .lists()
+            .into_iter()
+            .find(|(_, extensions)| extensions.iter().any(|e| e == ext));
+        match (listed, entry) {
+            (Some((category, _)), _) => Some(FileType {
+                category,
+                mime: entry.map_or(DEFAULT_MIME, |entry| entry.mime),
+            }),
+            (None, Some(entry)) if self.file_extensions.catalog => Some(FileType {
+                category: entry.category,
+                mime: entry.mime,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Maps `extension` to `category`; returns false when it already has a
+    /// destination or an override decides it.
+    pub fn add_extension(&mut self, category: Category, extension: &str) -> bool {
+        let extension = extension.trim().trim_start_matches('.').to_lowercase();
+        if extension.is_empty()
+            || self.get_category(&extension).is_some()
+            || self.file_extensions.overrides.contains_key(&extension)
+        {
+            return false;
+        }
+        match self.file_extensions.list_mut(category.as_str()) {
+            Some(list) => {
+                list.push(extension);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Default location of the persistent configuration,
+    /// `rustganizer/config.toml` in the user's config directory.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rustganizer").join("config.toml"))
+    }
+
+    /// Loads the configuration from [`Config::default_path`], or the defaults
+    /// when there is no such file.
+    pub fn load() -> Result<Config> {
+        match Config::default_path() {
+            Some(path) => Config::load_from(&path),
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// Loads and validates the configuration at `path`; a missing file gives the defaults.
+    pub fn load_from(path: &Path) -> Result<Config> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(source) => {
+                return Err(Error::FileOperation {
+                    operation: "read config".to_string(),
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        let config: Config = toml::from_str(&text).map_err(|e| Error::InvalidConfig {
+            message: format!("{}: {}", path.display(), e.message()),
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Validates the configuration and writes it to `path` as TOML.
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        self.validate()?;
+        let text = toml::to_string_pretty(self).map_err(|e| Error::InvalidConfig {
+            message: e.to_string(),
+        })?;
+        let file_error = |source| Error::FileOperation {
+            operation: "save config".to_string(),
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(file_error)?;
+        }
+        std::fs::write(path, text).map_err(file_error)
+    }
+
+    /// Checks the settings a user can edit for values the organizer cannot work with.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Err(Error::InvalidConfig { message });
+        let mut seen: HashMap<&str, &str> = HashMap::new();
+        for (category, extensions) in self.file_extensions.lists() {
+            for extension in extensions {
+                if extension.is_empty()
+                    || !extension.chars().all(|c| {
+                        c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-'
+                    })
+                {
+                    return invalid(format!(
+                        "invalid {category} extension `{extension}`: use lowercase letters and digits without the dot"
+                    ));
+                }
+                if let Some(other) = seen.insert(extension, category) {
+                    return invalid(format!(
+                        "extension `{extension}` is listed under both {other} and {category}"
+                    ));
+                }
+            }
+        }
+        for (extension, correction) in &self.file_extensions.overrides {
+            if extension.is_empty() || *extension != extension.to_lowercase() {
+                return invalid(format!(
+                    "invalid override `{extension}`: use the lowercase extension without the dot"
+                ));
+            }
+            if let Some(category) = correction.category.as_deref() {
+                if category != "none" && !self.is_known_category(category) {
+                    return invalid(format!(
+                        "unknown category `{category}` in the override of `{extension}`"
+                    ));
+                }
+            }
+        }
+        for rule in &self.retention {
+            if rule.older_than_days == 0 {
+                return invalid("retention.older_than_days must be at least 1".to_string());
+            }
+            if let Some(extension) = rule
+                .extensions
+                .iter()
+                .find(|e| e.is_empty() || **e != e.to_lowercase() || e.starts_with('.'))
+            {
+                return invalid(format!(
+                    "invalid retention extension `{extension}`: use the lowercase extension without the dot"
+                ));
+            }
+            if let Some(category) = rule.categories.iter().find(|c| !self.is_known_category(c)) {
+                return invalid(format!("unknown retention category `{category}`"));
+            }
+            let move_to = rule.move_to.as_deref();
+            let inside_home = move_to.is_some_and(|dir| {
+                dir.components()
+                    .all(|c| matches!(c, std::path::Component::Normal(_)))
+                    && dir.components().next().is_some()
+            });
+            match rule.action {
+                RetentionAction::Move if !inside_home => {
+                    return invalid(
+                        "retention rules that move files need a move_to folder relative to the home"
+                            .to_string(),
+                    );
+                }
+                RetentionAction::Trash if move_to.is_some() => {
+                    return invalid("retention rules that trash files take no move_to".to_string());
+                }
+                _ => {}
+            }
+        }
+        let performance = [
+            (
+                "max_concurrent_users",
+                self.performance.max_concurrent_users,
+            ),
+            ("thread_pool_size", self.performance.thread_pool_size),
+            ("batch_size", self.performance.batch_size),
+            ("buffer_size", self.performance.buffer_size),
+        ];
+        if let Some((name, _)) = performance.iter().find(|(_, value)| *value == 0) {
+            return invalid(format!("performance.{name} must be at least 1"));
+        }
+        if self.archives.max_inspected_bytes == 0
+            || self.archives.max_extracted_bytes == 0
+            || self.archives.max_entries == 0
+        {
+            return invalid("archives limits must be at least 1".to_string());
+        }
+        let mut names = std::collections::HashSet::new();
+        for schedule in &self.schedules {
+            schedule.validate()?;
+            if !names.insert(&schedule.name) {
+                return invalid(format!("there are two schedules named `{}`", schedule.name));
+            }
+        }
+        if !self.localized_dirs.contains_key(&self.ui.language) {
+            return invalid(format!("unsupported language `{}`", self.ui.language));
+        }
+        if self.ui.theme.trim().is_empty() {
+            return invalid("ui.theme must not be empty".to_string());
+        }
+        if !["trace", "debug", "info", "warn", "error"].contains(&self.logging.level.as_str()) {
+            return invalid(format!("unknown log level `{}`", self.logging.level));
+        }
+        let classifier = [
+            ("dominance_threshold", self.classifier.dominance_threshold),
+            ("byte_weight", self.classifier.byte_weight),
+            ("min_recognized_ratio", self.classifier.min_recognized_ratio),
+        ];
+        if let Some((name, _)) = classifier.iter().find(|(_, v)| !(0.0..=1.0).contains(v)) {
+            return invalid(format!("classifier.{name} must be between 0 and 1"));
+        }
+        Ok(())
+    }
 
-        if self.file_extensions.music.contains(&ext.as_str()) {
-            Some("music")
-        } else if self.file_extensions.videos.contains(&ext.as_str()) {
-            Some("video")
-        } else if self.file_extensions.images.contains(&ext.as_str()) {
-            Some("image")
-        } else if self.file_extensions.docs.contains(&ext.as_str()) {
-            Some("docs")
-        } else if self.file_extensions.archives.contains(&ext.as_str()) {
-            Some("archives")
-        } else if self.file_extensions.code.contains(&ext.as_str()) {
-            Some("code")
-        } else {
-            None
+    /// Whether `category` is a list name or a catalog category.
+    fn is_known_category(&self, category: &str) -> bool {
+        self.file_extensions
+            .lists()
+            .iter()
+            .any(|(name, _)| *name == category)
+            || catalog::has_category(category)
+    }
+
+    /// Languages with localized folder names, sorted.
+    pub fn languages(&self) -> Vec<String> {
+        let mut languages: Vec<String> = self.localized_dirs.keys().cloned().collect();
+        languages.sort();
+        languages
+    }
+}
+
+impl FileExtensions {
+    /// Every list with the category name `Config::get_file_category` reports for it.
+    pub fn lists(&self) -> [(&'static str, &Vec<String>); 6] {
+        [
+            ("music", &self.music),
+            ("video", &self.videos),
+            ("image", &self.images),
+            ("docs", &self.docs),
+            ("archives", &self.archives),
+            ("code", &self.code),
+        ]
+    }
+
+    /// Mutable access to the list `Config::get_file_category` calls `name`.
+    pub fn list_mut(&mut self, name: &str) -> Option<&mut Vec<String>> {
+        match name {
+            "music" => Some(&mut self.music),
+            "video" => Some(&mut self.videos),
+            "image" => Some(&mut self.images),
+            "docs" => Some(&mut self.docs),
+            "archives" => Some(&mut self.archives),
+            "code" => Some(&mut self.code),
+            _ => None,
         }
     }
 }
@@ -234,21 +740,22 @@ mod tests {
         let config = Config::default();
         let extensions = config.get_file_extensions();
 
-        assert!(extensions.music.contains(&"mp3"));
-        assert!(extensions.videos.contains(&"mp4"));
-        assert!(extensions.images.contains(&"png"));
-        assert!(extensions.docs.contains(&"pdf"));
+        assert!(extensions.music.contains(&"mp3".to_string()));
+        assert!(extensions.videos.contains(&"mp4".to_string()));
+        assert!(extensions.images.contains(&"png".to_string()));
+        assert!(extensions.docs.contains(&"pdf".to_string()));
     }
 
     #[test]
     fn test_file_category_detection() {
         let config = Config::default();
 
-        assert_eq!(config.get_file_category("mp3"), Some("music"));
-        assert_eq!(config.get_file_category("mp4"), Some("video"));
-        assert_eq!(config.get_file_category("png"), Some("image"));
-        assert_eq!(config.get_file_category("pdf"), Some("docs"));
-        assert_eq!(config.get_file_category("unknown"), None);
+        let category = |ext| config.get_file_category(ext).map(|t| t.category);
+        assert_eq!(category("mp3"), Some("music"));
+        assert_eq!(category("mp4"), Some("video"));
+        assert_eq!(category("png"), Some("image"));
+        assert_eq!(category("pdf"), Some("docs"));
+        assert_eq!(category("unknown"), None);
     }
 
     #[test]
@@ -261,4 +768,157 @@ mod tests {
         let not_found_msg = config.get_error_message("es", "user_not_found", "testuser");
         assert!(not_found_msg.contains("testuser"));
     }
+
+    #[test]
+    fn test_config_round_trips_through_toml() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rustganizer").join("config.toml");
+        let mut config = Config::default();
+        config.file_extensions.music.push("opus".to_string());
+        config.ui.language = "es".to_string();
+        config.performance.thread_pool_size = 2;
+        config.traversal.max_depth = None;
+        config.ignore_patterns = vec!["*.part".to_string()];
+        config.schedules = vec![Schedule {
+            name: "weekly".to_string(),
+            when: "weekly sun 22:00".parse().unwrap(),
+            users: vec!["alice".to_string()],
+            dry_run: false,
+            backend: crate::schedule::Backend::Cron,
+        }];
+        config.retention = vec![RetentionRule {
+            older_than_days: 30,
+            extensions: vec!["deb".to_string()],
+            categories: vec!["installers".to_string()],
+            sources: Vec::new(),
+            action: RetentionAction::Trash,
+            move_to: None,
+        }];
+        config.save_to(&path).unwrap();
+
+        let loaded = Config::load_from(&path).unwrap();
+        assert_eq!(loaded.get_category("OPUS"), Some(Category::Music));
+        assert_eq!(loaded.ui.language, "es");
+        assert_eq!(loaded.performance.thread_pool_size, 2);
+        assert_eq!(loaded.traversal.max_depth, None);
+        assert_eq!(loaded.ignore_patterns, ["*.part"]);
+        assert_eq!(loaded.schedules, config.schedules);
+        assert_eq!(loaded.retention, config.retention);
+        assert_eq!(loaded.get_localized_dir("es", "Music"), "Música");
+    }
+
+    #[test]
+    fn test_partial_config_keeps_defaults_and_missing_file_is_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        assert_eq!(
+            Config::load_from(&path).unwrap().file_extensions,
+            FileExtensions::default()
+        );
+
+        std::fs::write(
+            &path,
+            "[file_extensions]\ndocs = [\"md\"]\n[ui]\ntheme = \"dark\"\n\
+             [traversal]\nsymlinks = \"skip\"\n",
+        )
+        .unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.file_extensions.docs, ["md"]);
+        assert_eq!(config.get_category("md"), Some(Category::Docs));
+        assert_eq!(config.get_category("mp3"), Some(Category::Music));
+        assert_eq!(config.ui.theme, "dark");
+        assert_eq!(config.ui.language, "en");
+        assert_eq!(config.traversal.max_depth, Some(32));
+    }
+
+    #[test]
+    fn test_validation_rejects_unusable_settings() {
+        let mut config = Config::default();
+        config.file_extensions.docs.push("mp3".to_string());
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("both music and docs"), "{error}");
+
+        let mut config = Config::default();
+        config.file_extensions.images.push(".PNG".to_string());
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.performance.max_concurrent_users = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        let schedule = Schedule {
+            name: "nightly".to_string(),
+            when: crate::schedule::Trigger::Login,
+            users: Vec::new(),
+            dry_run: false,
+            backend: Default::default(),
+        };
+        config.schedules = vec![schedule.clone(), schedule];
+        assert!(config.validate().is_err());
+
+        let config = Config {
+            retention: vec![RetentionRule {
+                older_than_days: 90,
+                extensions: Vec::new(),
+                categories: Vec::new(),
+                sources: vec!["Downloads".to_string()],
+                action: RetentionAction::Move,
+                move_to: Some(PathBuf::from("../elsewhere")),
+            }],
+            ..Config::default()
+        };
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.ui.language = "xx".to_string();
+        assert!(config.validate().is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[performance]\nbatch_size = 0\n").unwrap();
+        assert!(Config::load_from(&path).is_err());
+        assert!(config.save_to(&path).is_err());
+    }
+
+    #[test]
+    fn test_catalog_fills_in_and_overrides_take_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[file_extensions.overrides.heic]\ncategory = \"docs\"\n\
+             [file_extensions.overrides.webp]\ncategory = \"none\"\n\
+             [file_extensions.overrides.mp3]\nmime = \"audio/x-mp3\"\n",
+        )
+        .unwrap();
+        let config = Config::load_from(&path).unwrap();
+
+        let flac = config.get_file_category("FLAC").unwrap();
+        assert_eq!((flac.category, flac.mime), ("music", "audio/flac"));
+        assert_eq!(
+            config.get_file_category("deb").unwrap().category,
+            "installers"
+        );
+        assert_eq!(config.get_category("deb"), None);
+        let heic = config.get_file_category("heic").unwrap();
+        assert_eq!((heic.category, heic.mime), ("docs", "image/heic"));
+        assert_eq!(config.get_file_category("webp"), None);
+        let mp3 = config.get_file_category("mp3").unwrap();
+        assert_eq!((mp3.category, mp3.mime), ("music", "audio/x-mp3"));
+
+        let mut config = config;
+        config.file_extensions.catalog = false;
+        assert_eq!(config.get_file_category("opus"), None);
+        assert_eq!(config.get_file_category("mp3").unwrap().category, "music");
+
+        config.file_extensions.overrides.insert(
+            "mp3".to_string(),
+            ExtensionOverride {
+                category: Some("podcasts".to_string()),
+                mime: None,
+            },
+        );
+        assert!(config.validate().is_err());
+    }
 }
@@ -53,6 +53,12 @@ pub enum Error {
     #[error("Invalid configuration: {message}")]
     InvalidConfig { message: String },
 
+    #[error("Worker failed: {message}")]
+    Worker { message: String },
+
+    #[error("Scheduling failed: {message}")]
+    Schedule { message: String },
+
     #[error("Other error: {0}")]
     Other(#[from] anyhow::Error),
 }
@@ -0,0 +1,272 @@
+//! Run history: one JSON line per organized user, kept under the data directory
+
+use crate::config::data_dir;
+use crate::error::{Error, Result};
+use crate::organizer::types::{FileStats, MultiUserReport};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// One user's part of a run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Start of the run in seconds since the Unix epoch.
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub username: String,
+    pub source_dirs: Vec<PathBuf>,
+    pub stats: FileStats,
+    pub errors: usize,
+    pub dry_run: bool,
+}
+
+impl HistoryEntry {
+    /// Entries for every user of a run; a user that failed outright counts one error.
+    pub fn from_run(report: &MultiUserReport) -> Vec<Self> {
+        report
+            .users
+            .iter()
+            .map(|user| {
+                let run = user.report.as_ref();
+                HistoryEntry {
+                    started_at: user.started_at,
+                    duration_ms: user.duration_ms,
+                    username: user.username.clone(),
+                    source_dirs: user.source_dirs.clone(),
+                    stats: run.map(|r| r.stats.clone()).unwrap_or_default(),
+                    errors: run.map_or(0, |r| r.errors.len()) + usize::from(user.error.is_some()),
+                    dry_run: run.is_some_and(|r| r.dry_run),
+                }
+            })
+            .collect()
+    }
+
+    /// Start of the run as an RFC 3339 timestamp.
+    pub fn started(&self) -> String {
+        humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(self.started_at))
+            .to_string()
+    }
+}
+
+/// Files organized in one week, dry runs excluded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeeklyStats {
+    /// Date of the Monday the week starts on, `YYYY-MM-DD`.
+    pub week: String,
+    pub runs: usize,
+    pub stats: FileStats,
+}
+
+/// Append-only JSON lines file of [`HistoryEntry`]
+#[derive(Debug, Clone)]
+pub struct HistoryStore {
+    path: PathBuf,
+}
+
+impl HistoryStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Store at `history.jsonl` in [`crate::config::data_dir`], if there is one.
+    pub fn open_default() -> Option<Self> {
+        data_dir().map(|dir| Self::new(dir.join("history.jsonl")))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends one entry per user of `report`.
+    pub fn record(&self, report: &MultiUserReport) -> Result<()> {
+        let file_error = |source| Error::FileOperation {
+            operation: "record history".to_string(),
+            path: self.path.clone(),
+            source,
+        };
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(file_error)?;
+        }
+        let mut lines = String::new();
+        for entry in HistoryEntry::from_run(report) {
+            lines.push_str(&serde_json::to_string(&entry)?);
+            lines.push('\n');
+        }
+        // A single write keeps concurrent runs from interleaving their lines
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(lines.as_bytes()))
+            .map_err(file_error)
+    }
+
+    /// All entries, oldest first. A missing file is an empty history and
+    /// lines that do not parse are ignored.
+    pub fn load(&self) -> Result<Vec<HistoryEntry>> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(source) => {
+                return Err(Error::FileOperation {
+                    operation: "read history".to_string(),
+                    path: self.path.clone(),
+                    source,
+                })
+            }
+        };
+        Ok(BufReader::new(file)
+            .lines()
+            .map_while(|line| line.ok())
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect())
+    }
+}
+
+/// Files organized per category per week, oldest week first.
+pub fn weekly_totals(entries: &[HistoryEntry]) -> Vec<WeeklyStats> {
+    let mut weeks: BTreeMap<u64, (usize, FileStats)> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| !e.dry_run) {
+        let (runs, stats) = weeks.entry(week_start(entry.started_at)).or_default();
+        *runs += 1;
+        stats.add(&entry.stats);
+    }
+    weeks
+        .into_iter()
+        .map(|(start, (runs, stats))| WeeklyStats {
+            week: date(start),
+            runs,
+            stats,
+        })
+        .collect()
+}
+
+/// The `limit` runs that organized the most files, largest first.
+pub fn largest_runs(entries: &[HistoryEntry], limit: usize) -> Vec<&HistoryEntry> {
+    let mut runs: Vec<&HistoryEntry> = entries.iter().filter(|e| !e.dry_run).collect();
+    // Stable sort: among equal runs the earlier one comes first
+    runs.sort_by_key(|e| std::cmp::Reverse(e.stats.total()));
+    runs.truncate(limit);
+    runs
+}
+
+/// Midnight of the Monday on or before `timestamp`.
+fn week_start(timestamp: u64) -> u64 {
+    let day = timestamp / SECONDS_PER_DAY;
+    // 1970-01-01 was a Thursday, three days after a Monday
+    let monday = day - (day + 3) % 7;
+    monday * SECONDS_PER_DAY
+}
+
+fn date(timestamp: u64) -> String {
+    let mut text =
+        humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(timestamp)).to_string();
+    text.truncate("YYYY-MM-DD".len());
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::organizer::types::{RunReport, UserRunResult};
+
+    // 2025-01-06 was a Monday
+    const MONDAY: u64 = 1_736_121_600;
+
+    fn entry(started_at: u64, music: usize, dry_run: bool) -> HistoryEntry {
+        HistoryEntry {
+            started_at,
+            duration_ms: 10,
+            username: "alice".to_string(),
+            source_dirs: vec![PathBuf::from("/home/alice/Downloads")],
+            stats: FileStats {
+                music,
+                ..FileStats::default()
+            },
+            errors: 0,
+            dry_run,
+        }
+    }
+
+    #[test]
+    fn test_record_appends_and_load_skips_bad_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = HistoryStore::new(dir.path().join("nested").join("history.jsonl"));
+        assert!(store.load().unwrap().is_empty());
+
+        let report = MultiUserReport {
+            users: vec![
+                UserRunResult {
+                    username: "alice".to_string(),
+                    report: Some(RunReport {
+                        errors: vec!["oops".to_string()],
+                        ..RunReport::default()
+                    }),
+                    started_at: MONDAY,
+                    ..UserRunResult::default()
+                },
+                UserRunResult {
+                    username: "bob".to_string(),
+                    error: Some("User not found: bob".to_string()),
+                    ..UserRunResult::default()
+                },
+            ],
+            totals: FileStats::default(),
+        };
+        store.record(&report).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(store.path())
+            .unwrap()
+            .write_all(b"not json\n")
+            .unwrap();
+        store.record(&report).unwrap();
+
+        let entries = store.load().unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].started(), "2025-01-06T00:00:00Z");
+        assert_eq!(
+            entries.iter().map(|e| e.errors).collect::<Vec<_>>(),
+            [1, 1, 1, 1]
+        );
+    }
+
+    #[test]
+    fn test_weekly_totals_group_by_monday_and_skip_dry_runs() {
+        let sunday_night = MONDAY + 7 * SECONDS_PER_DAY - 1;
+        let entries = [
+            entry(MONDAY - 1, 1, false),
+            entry(MONDAY, 2, false),
+            entry(sunday_night, 3, false),
+            entry(MONDAY + 3600, 50, true),
+        ];
+        let weeks = weekly_totals(&entries);
+        assert_eq!(
+            weeks
+                .iter()
+                .map(|w| (w.week.as_str(), w.runs, w.stats.music))
+                .collect::<Vec<_>>(),
+            [("2024-12-30", 1, 1), ("2025-01-06", 2, 5)]
+        );
+    }
+
+    #[test]
+    fn test_largest_runs_are_sorted_by_total() {
+        let entries = [
+            entry(MONDAY, 2, false),
+            entry(MONDAY + 1, 9, false),
+            entry(MONDAY + 2, 100, true),
+            entry(MONDAY + 3, 5, false),
+        ];
+        let largest: Vec<usize> = largest_runs(&entries, 2)
+            .iter()
+            .map(|e| e.stats.total())
+            .collect();
+        assert_eq!(largest, [9, 5]);
+    }
+}
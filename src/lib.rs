@@ -1,5 +1,11 @@
+pub mod catalog;
+pub mod cli;
 pub mod config;
 pub mod error;
+pub mod history;
+pub mod logging;
 pub mod organizer;
 pub mod platform;
+pub mod report;
+pub mod schedule;
 pub mod ui;
@@ -1,122 +1,290 @@
-//! Logging and monitoring setup for Rustganizer
-
-use tracing::{info, warn, error, debug};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use std::path::PathBuf;
-use crate::config::Config;
-
-/// Initialize logging based on configuration
-pub fn initialize_logging(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
-    let log_level = match config.logging.level.as_str() {
-        "trace" => tracing::Level::TRACE,
-        "debug" => tracing::Level::DEBUG,
-        "info" => tracing::Level::INFO,
-        "warn" => tracing::Level::WARN,
-        "error" => tracing::Level::ERROR,
-        _ => tracing::Level::INFO,
-    };
-
-    let stdout_layer = tracing_subscriber::fmt::layer()
-        .with_target(false)
-        .with_thread_ids(true)
-        .with_level(true);
-
-    let mut layers = vec![stdout_layer.boxed()];
-
-    // Add file logging if configured
-    if let Some(log_file) = &config.logging.file {
-        let file_appender = tracing_appender::rolling::daily(
-            log_file.parent().unwrap_or_else(|| PathBuf::from(".")),
-            log_file.file_name().unwrap_or_default().to_string_lossy(),
-        );
-        
-        let file_layer = tracing_subscriber::fmt::layer()
-            .with_writer(file_appender)
-            .with_ansi(false)
-            .with_target(false)
-            .with_thread_ids(true)
-            .with_level(true);
-            
-        layers.push(file_layer.boxed());
-    }
-
-    tracing_subscriber::registry()
-        .with(layers)
-        .with(tracing_subscriber::filter::LevelFilter::from_level(log_level))
-        .init();
-
-    info!("Logging initialized at level: {}", config.logging.level);
-    Ok(())
-}
-
-/// Log file organization operation start
-pub fn log_organization_start(username: &str, lang: &str) {
-    info!("Starting file organization for user: {} (language: {})", username, lang);
-}
-
-/// Log file organization completion
-pub fn log_organization_complete(stats: &crate::organizer::types::FileStats, errors: &[String]) {
-    info!("File organization completed: music={}, videos={}, images={}, docs={}", 
-          stats.music, stats.videos, stats.images, stats.docs);
-    
-    if !errors.is_empty() {
-        warn!("Organization completed with {} errors: {:?}", errors.len(), errors);
-    }
-}
-
-/// Log file move operation
-pub fn log_file_move(from: &std::path::Path, to: &std::path::Path, success: bool) {
-    if success {
-        debug!("Moved file from {:?} to {:?}", from, to);
-    } else {
-        error!("Failed to move file from {:?} to {:?}", from, to);
-    }
-}
-
-/// Log directory analysis
-pub fn log_directory_analysis(path: &std::path::Path, stats: &crate::organizer::types::FileStats) {
-    debug!("Analyzed directory {:?}: music={}, videos={}, images={}, docs={}", 
-           path, stats.music, stats.videos, stats.images, stats.docs);
-}
-
-/// Log configuration loading
-pub fn log_config_load(config_path: &std::path::Path, success: bool) {
-    if success {
-        info!("Configuration loaded from: {:?}", config_path);
-    } else {
-        warn!("Failed to load configuration from: {:?}, using defaults", config_path);
-    }
-}
-
-/// Log error with context
-pub fn log_error_with_context(error: &crate::error::Error, context: &str) {
-    error!("Error in {}: {}", context, error);
-}
-
-/// Performance logging
-pub fn log_performance_metric(operation: &str, duration: std::time::Duration, item_count: usize) {
-    let items_per_second = if duration.as_secs() > 0 {
-        item_count as f64 / duration.as_secs_f64()
-    } else {
-        0.0
-    };
-    
-    debug!("Performance - {}: {} items in {:.2}s ({:.2} items/s)", 
-           operation, item_count, duration.as_secs_f64(), items_per_second);
-}
-
-/// Log user interaction
-pub fn log_user_action(action: &str, details: &str) {
-    info!("User action: {} - {}", action, details);
-}
-
-/// Log system information
-pub fn log_system_info() {
-    let cpu_count = num_cpus::get();
-    let memory = match sysinfo::System::new_all().total_memory() {
-        mem if mem > 0 => format!("{:.1} GB", mem as f64 / 1024.0 / 1024.0 / 1024.0),
-        _ => "Unknown".to_string(),
-    };
-    
-    info!("System info: {} CPUs, {} RAM", cpu_count, memory);
-}
\ No newline at end of file
+//! Logging and monitoring setup for Rustganizer
+
+use crate::config::{data_dir, LoggingConfig};
+use crate::error::{Error, Result};
+use crate::platform::audit::{AuditLog, AuditSink};
+use rolling::RollingFileWriter;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::field::{Field, Visit};
+use tracing::{debug, error, info, trace, warn, Event, Level, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::{util::SubscriberInitExt, Layer};
+
+pub mod rolling;
+
+/// Flushes the log file when dropped
+#[must_use = "dropping the guard stops file logging"]
+pub struct LoggingGuard(Option<WorkerGuard>);
+
+/// Log file used while the UI owns the terminal and no file is configured
+pub fn default_log_file() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("rustganizer.log"))
+}
+
+/// Initialize logging based on configuration
+///
+/// Console output goes to stderr. While `ui_active` the terminal belongs to the
+/// UI, so nothing is written there and logs go to `LoggingConfig::file`, or
+/// [`default_log_file`] when none is set. The file rotates by size, see
+/// [`rolling::RollingFileWriter`]; keep the returned guard alive until exit so
+/// buffered lines are written.
+pub fn initialize_logging(config: &LoggingConfig, ui_active: bool) -> Result<LoggingGuard> {
+    let log_level = parse_level(&config.level);
+
+    let mut guard = LoggingGuard(None);
+    let mut layers = Vec::new();
+    if config.console && !ui_active {
+        let stderr_layer = tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_target(false)
+            .with_thread_ids(true)
+            .with_level(true);
+        layers.push(stderr_layer.boxed());
+    }
+
+    // Add file logging if configured
+    let log_file = match &config.file {
+        Some(file) => Some(file.clone()),
+        None if ui_active => default_log_file(),
+        None => None,
+    };
+    if let Some(log_file) = &log_file {
+        let rolling = RollingFileWriter::open(
+            log_file,
+            config.max_file_size as u64,
+            config.max_files,
+            config.compress,
+        )
+        .map_err(|source| Error::FileOperation {
+            operation: "open log file".to_string(),
+            path: log_file.clone(),
+            source,
+        })?;
+        // Lines are written on a background thread so logging never stalls a move
+        let (file_appender, worker_guard) = tracing_appender::non_blocking(rolling);
+        guard.0 = Some(worker_guard);
+
+        let file_layer = tracing_subscriber::fmt::layer()
+            .with_writer(file_appender)
+            .with_ansi(false)
+            .with_target(false)
+            .with_thread_ids(true)
+            .with_level(true);
+
+        layers.push(file_layer.boxed());
+    }
+
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(tracing_subscriber::filter::LevelFilter::from_level(
+            log_level,
+        ))
+        .try_init()
+        .map_err(|e| Error::Other(anyhow::anyhow!("failed to initialize logging: {e}")))?;
+
+    info!("Logging initialized at level: {}", config.level);
+    Ok(guard)
+}
+
+/// Level named by `LoggingConfig::level`, info when unknown
+fn parse_level(level: &str) -> Level {
+    match level {
+        "trace" => Level::TRACE,
+        "debug" => Level::DEBUG,
+        "info" => Level::INFO,
+        "warn" => Level::WARN,
+        "error" => Level::ERROR,
+        _ => Level::INFO,
+    }
+}
+
+/// Hands every event at `level` or above to `forward` instead of writing it
+///
+/// Used by worker processes, whose events are logged by the parent.
+pub fn initialize_forwarded_logging<F>(level: &str, forward: F)
+where
+    F: Fn(Level, String) + Send + Sync + 'static,
+{
+    let _ = tracing_subscriber::registry()
+        .with(ForwardLayer(forward))
+        .with(tracing_subscriber::filter::LevelFilter::from_level(
+            parse_level(level),
+        ))
+        .try_init();
+}
+
+/// Logs an event forwarded by the worker organizing `username`
+pub fn log_forwarded(username: &str, level: Level, message: &str) {
+    match level {
+        Level::ERROR => error!("[worker {}] {}", username, message),
+        Level::WARN => warn!("[worker {}] {}", username, message),
+        Level::INFO => info!("[worker {}] {}", username, message),
+        Level::DEBUG => debug!("[worker {}] {}", username, message),
+        Level::TRACE => trace!("[worker {}] {}", username, message),
+    }
+}
+
+struct ForwardLayer<F>(F);
+
+impl<S, F> Layer<S> for ForwardLayer<F>
+where
+    S: Subscriber,
+    F: Fn(Level, String) + Send + Sync + 'static,
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut message = MessageVisitor(String::new());
+        event.record(&mut message);
+        (self.0)(*event.metadata().level(), message.0);
+    }
+}
+
+/// Collects the formatted `message` field of an event
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{value:?}");
+        }
+    }
+}
+
+/// Opens the audit log at `LoggingConfig::audit_path`.
+///
+/// A log that cannot be opened is reported and leaves auditing off.
+pub fn open_audit_log(config: &LoggingConfig) -> Option<Arc<dyn AuditSink>> {
+    let path = config.audit_path()?;
+    match AuditLog::open(&path) {
+        Ok(log) => Some(Arc::new(log)),
+        Err(e) => {
+            error!("Failed to open audit log {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Log file organization operation start
+pub fn log_organization_start(username: &str, lang: &str) {
+    info!(
+        "Starting file organization for user: {} (language: {})",
+        username, lang
+    );
+}
+
+/// Log file organization completion
+pub fn log_organization_complete(stats: &crate::organizer::types::FileStats, errors: &[String]) {
+    info!(
+        "File organization completed: music={}, videos={}, images={}, docs={}, archives={}, code={}, cleaned={}",
+        stats.music, stats.videos, stats.images, stats.docs, stats.archives, stats.code, stats.cleaned
+    );
+
+    if !errors.is_empty() {
+        warn!(
+            "Organization completed with {} errors: {:?}",
+            errors.len(),
+            errors
+        );
+    }
+}
+
+/// Log file move operation
+pub fn log_file_move(from: &std::path::Path, to: &std::path::Path, success: bool) {
+    if success {
+        debug!("Moved file from {:?} to {:?}", from, to);
+    } else {
+        error!("Failed to move file from {:?} to {:?}", from, to);
+    }
+}
+
+/// Log directory analysis
+pub fn log_directory_analysis(path: &std::path::Path, stats: &crate::organizer::types::FileStats) {
+    debug!(
+        "Analyzed directory {:?}: music={}, videos={}, images={}, docs={}, archives={}, code={}",
+        path, stats.music, stats.videos, stats.images, stats.docs, stats.archives, stats.code
+    );
+}
+
+/// Log configuration loading
+pub fn log_config_load(config_path: &std::path::Path, success: bool) {
+    if success {
+        info!("Configuration loaded from: {:?}", config_path);
+    } else {
+        warn!(
+            "Failed to load configuration from: {:?}, using defaults",
+            config_path
+        );
+    }
+}
+
+/// Log error with context
+pub fn log_error_with_context(error: &crate::error::Error, context: &str) {
+    error!("Error in {}: {}", context, error);
+}
+
+/// Performance logging
+pub fn log_performance_metric(operation: &str, duration: std::time::Duration, item_count: usize) {
+    let items_per_second = if duration.as_secs() > 0 {
+        item_count as f64 / duration.as_secs_f64()
+    } else {
+        0.0
+    };
+
+    debug!(
+        "Performance - {}: {} items in {:.2}s ({:.2} items/s)",
+        operation,
+        item_count,
+        duration.as_secs_f64(),
+        items_per_second
+    );
+}
+
+/// Log user interaction
+pub fn log_user_action(action: &str, details: &str) {
+    info!("User action: {} - {}", action, details);
+}
+
+/// Log system information
+pub fn log_system_info() {
+    let cpu_count = num_cpus::get();
+    let memory = match sysinfo::System::new_all().total_memory() {
+        mem if mem > 0 => format!("{:.1} GB", mem as f64 / 1024.0 / 1024.0 / 1024.0),
+        _ => "Unknown".to_string(),
+    };
+
+    info!("System info: {} CPUs, {} RAM", cpu_count, memory);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_forwarded_events_keep_level_and_message() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(ForwardLayer(move |level, message| {
+                sink.lock().unwrap().push((level, message))
+            }))
+            .with(tracing_subscriber::filter::LevelFilter::from_level(
+                parse_level("info"),
+            ));
+
+        tracing::subscriber::with_default(subscriber, || {
+            log_file_move("/a".as_ref(), "/b".as_ref(), true);
+            log_organization_start("alice", "en");
+        });
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [(
+                Level::INFO,
+                "Starting file organization for user: alice (language: en)".to_string()
+            )]
+        );
+    }
+}
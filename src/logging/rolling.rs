@@ -0,0 +1,168 @@
+//! Size-based rolling log file
+//!
+//! The active file is `<name>`; when it would grow past the size limit it is
+//! renamed to `<name>.1`, older files shift to `<name>.2` and so on, and the
+//! oldest beyond the limit is deleted. Rotated files may be gzip-compressed
+//! to `<name>.N.gz`.
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Log writer that rotates by size and keeps a bounded number of old files
+#[derive(Debug)]
+pub struct RollingFileWriter {
+    path: PathBuf,
+    max_file_size: u64,
+    max_files: usize,
+    compress: bool,
+    file: File,
+    size: u64,
+}
+
+impl RollingFileWriter {
+    /// Opens `path` for appending, creating it and its parents if needed.
+    ///
+    /// The file rotates before exceeding `max_file_size` bytes and at most
+    /// `max_files` rotated files are kept.
+    pub fn open(
+        path: impl Into<PathBuf>,
+        max_file_size: u64,
+        max_files: usize,
+        compress: bool,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = open_append(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_file_size,
+            max_files,
+            compress,
+            file,
+            size,
+        })
+    }
+
+    /// Path of the `index`th rotated file, 1 being the most recent.
+    pub fn rotated_path(&self, index: usize, compressed: bool) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{index}"));
+        if compressed {
+            name.push(".gz");
+        }
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        for compressed in [false, true] {
+            remove_if_exists(&self.rotated_path(self.max_files.max(1), compressed))?;
+        }
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                for compressed in [false, true] {
+                    let from = self.rotated_path(index, compressed);
+                    if from.exists() {
+                        fs::rename(&from, self.rotated_path(index + 1, compressed))?;
+                    }
+                }
+            }
+            let rotated = self.rotated_path(1, false);
+            fs::rename(&self.path, &rotated)?;
+            if self.compress {
+                compress(&rotated, &self.rotated_path(1, true))?;
+            }
+        }
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RollingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A single line larger than the limit still goes into a file of its own
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_file_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Replaces `from` with its gzip-compressed copy at `to`.
+fn compress(from: &Path, to: &Path) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(File::create(to)?, Compression::default());
+    io::copy(&mut File::open(from)?, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::remove_file(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_rotates_at_size_and_keeps_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let mut writer = RollingFileWriter::open(&path, 10, 2, false).unwrap();
+        for line in ["one 1234\n", "two 1234\n", "three 12\n", "four 123\n"] {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let read = |p: PathBuf| fs::read_to_string(p).unwrap();
+        assert_eq!(read(path.clone()), "four 123\n");
+        assert_eq!(read(writer.rotated_path(1, false)), "three 12\n");
+        assert_eq!(read(writer.rotated_path(2, false)), "two 1234\n");
+        assert!(!writer.rotated_path(3, false).exists());
+    }
+
+    #[test]
+    fn test_rotated_files_can_be_compressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs").join("app.log");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "earlier run\n").unwrap();
+        let mut writer = RollingFileWriter::open(&path, 16, 3, true).unwrap();
+        writer.write_all(b"this line is too long\n").unwrap();
+
+        let mut decoded = String::new();
+        GzDecoder::new(File::open(writer.rotated_path(1, true)).unwrap())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "earlier run\n");
+        assert!(!writer.rotated_path(1, false).exists());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "this line is too long\n"
+        );
+    }
+}
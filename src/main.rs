@@ -1,10 +1,37 @@
-use crate::ui::views::run_ui;
-
-mod config;
-mod organizer;
-mod platform;
-mod ui;
+use clap::Parser;
+use rustganizer::cli::{self, Cli};
+use rustganizer::logging::initialize_logging;
+use rustganizer::organizer::worker::{run_worker, WORKER_ARG};
+use rustganizer::ui::views::run_ui;
 
 fn main() {
-    run_ui();
+    if std::env::args().nth(1).as_deref() == Some(WORKER_ARG) {
+        std::process::exit(run_worker());
+    }
+    let cli = Cli::parse();
+    let config = match cli.load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("rustganizer: {e}");
+            std::process::exit(2);
+        }
+    };
+    // While the UI runs it owns the terminal, so logs only go to a file
+    let ui_active = cli.command.is_none();
+    let guard = match initialize_logging(&config.logging, ui_active) {
+        Ok(guard) => Some(guard),
+        Err(e) => {
+            eprintln!("rustganizer: {e}");
+            None
+        }
+    };
+    if ui_active {
+        let config_path = cli.config_path();
+        run_ui(config, config_path, cli.history());
+    } else {
+        let code = cli::run(cli, config);
+        // `exit` skips destructors, so flush the log first
+        drop(guard);
+        std::process::exit(code);
+    }
 }
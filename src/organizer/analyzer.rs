@@ -1,33 +1,131 @@
 use crate::config::Config;
-use crate::organizer::types::FileStats;
-use std::path::Path;
-use walkdir::WalkDir;
+use crate::organizer::ignore::IgnoreRules;
+use crate::organizer::types::{Category, ExtensionTally, FileStats};
+use crate::organizer::walk::{walk_entries, Walked};
+use crate::platform::filesystem::{FileSystem, RealFileSystem};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Number of files kept in `FolderAnalysis::largest`
+pub const LARGEST_FILES: usize = 10;
+
+/// Detailed breakdown of a directory tree, as shown by the analysis browser
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FolderAnalysis {
+    /// Recognised files per category.
+    pub stats: FileStats,
+    /// Bytes of the recognised files per category.
+    pub bytes: BTreeMap<Category, u64>,
+    /// Files without a destination folder, by lowercase extension; `""` for none.
+    pub unrecognized: BTreeMap<String, ExtensionTally>,
+    /// The largest files, largest first.
+    pub largest: Vec<(PathBuf, u64)>,
+    pub files: usize,
+    pub total_bytes: u64,
+}
+
+impl FolderAnalysis {
+    /// Counts one file of `size` bytes.
+    pub fn add_file(&mut self, path: &Path, size: u64, config: &Config) {
+        self.files += 1;
+        self.total_bytes += size;
+        let extension = file_extension(path);
+        if let Some(category) = config.get_category(&extension) {
+            self.stats.increment(category);
+            *self.bytes.entry(category).or_default() += size;
+        } else {
+            self.unrecognized
+                .entry(extension)
+                .or_default()
+                .add_file(size);
+        }
+        self.keep_largest([(path.to_path_buf(), size)]);
+    }
+
+    /// Adds the counts of another tree.
+    pub fn merge(&mut self, other: &FolderAnalysis) {
+        self.stats.add(&other.stats);
+        for (category, bytes) in &other.bytes {
+            *self.bytes.entry(*category).or_default() += bytes;
+        }
+        for (extension, tally) in &other.unrecognized {
+            self.unrecognized
+                .entry(extension.clone())
+                .or_default()
+                .add(tally);
+        }
+        self.files += other.files;
+        self.total_bytes += other.total_bytes;
+        self.keep_largest(other.largest.iter().cloned());
+    }
+
+    pub fn category_bytes(&self, category: Category) -> u64 {
+        self.bytes.get(&category).copied().unwrap_or_default()
+    }
+
+    fn keep_largest(&mut self, files: impl IntoIterator<Item = (PathBuf, u64)>) {
+        self.largest.extend(files);
+        self.largest
+            .sort_by(|(a, a_size), (b, b_size)| b_size.cmp(a_size).then_with(|| a.cmp(b)));
+        self.largest.truncate(LARGEST_FILES);
+    }
+}
+
+/// Lowercase extension of `path`, empty when it has none.
+pub fn file_extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
 
 pub fn analyze_folder(path: &Path, config: &Config) -> FileStats {
-    let mut stats = FileStats {
-        music: 0,
-        videos: 0,
-        images: 0,
-        docs: 0,
-    };
-    let file_extensions = config.get_file_extensions();
-    for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
-        if entry.file_type().is_file() {
-            if let Some(extension) = entry.path().extension() {
-                let ext = extension.to_str().unwrap().to_lowercase();
-                if file_extensions.music.contains(&ext.as_str()) {
-                    stats.music += 1;
-                } else if file_extensions.videos.contains(&ext.as_str()) {
-                    stats.videos += 1;
-                } else if file_extensions.images.contains(&ext.as_str()) {
-                    stats.images += 1;
-                } else if file_extensions.docs.contains(&ext.as_str()) {
-                    stats.docs += 1;
+    analyze_folder_with(&RealFileSystem, path, config)
+}
+
+/// Counts recognised files below `path` using the given filesystem.
+///
+/// Entries matched by the global ignore patterns or by a `.rustganizerignore`
+/// file inside the tree are not counted. Symlinks, special files, depth and
+/// filesystem boundaries are handled as configured in `Config::traversal`.
+pub fn analyze_folder_with(fs: &dyn FileSystem, path: &Path, config: &Config) -> FileStats {
+    let mut stats = FileStats::default();
+    let rules = IgnoreRules::global(config, path);
+    walk_entries(fs, path, &rules, &config.traversal, |walked| {
+        let Walked::Entry { path, metadata } = walked else {
+            return;
+        };
+        if metadata.is_dir() {
+            return;
+        }
+        if let Some(category) = path
+            .extension()
+            .and_then(|e| config.get_category(&e.to_string_lossy()))
+        {
+            stats.increment(category);
+        }
+    });
+    stats
+}
+
+/// Like [`analyze_folder_with`], with sizes, the largest files and the
+/// extensions no category claims. A file counts as a tree of its own.
+pub fn analyze_tree(fs: &dyn FileSystem, path: &Path, config: &Config) -> FolderAnalysis {
+    let mut analysis = FolderAnalysis::default();
+    match fs.symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => {
+            let rules = IgnoreRules::global(config, path);
+            walk_entries(fs, path, &rules, &config.traversal, |walked| {
+                if let Walked::Entry { path, metadata } = walked {
+                    if !metadata.is_dir() {
+                        analysis.add_file(path, metadata.len, config);
+                    }
                 }
-            }
+            });
         }
+        Ok(metadata) => analysis.add_file(path, metadata.len, config),
+        Err(_) => {}
     }
-    stats
+    analysis
 }
 
 pub fn get_majority_type(stats: &FileStats) -> Option<&'static str> {
@@ -36,11 +134,54 @@ pub fn get_majority_type(stats: &FileStats) -> Option<&'static str> {
         (stats.videos, "video"),
         (stats.images, "image"),
         (stats.docs, "docs"),
+        (stats.archives, "archives"),
+        (stats.code, "code"),
     ];
-    type_counts.sort_by(|a, b| b.0.cmp(&a.0));
+    type_counts.sort_by_key(|t| std::cmp::Reverse(t.0));
     if type_counts[0].0 > 0 {
         Some(type_counts[0].1)
     } else {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::filesystem::MemoryFileSystem;
+
+    #[test]
+    fn test_analyze_tree_counts_bytes_and_unrecognized_extensions() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/d/a.mp3", "12345");
+        fs.add_file("/d/sub/b.MP3", "123");
+        fs.add_file("/d/sub/c.png", "1");
+        fs.add_file("/d/sub/setup.xyz", "1234567");
+        fs.add_file("/d/README", "12");
+        fs.add_file("/d/backup.zip", "1234");
+        let config = Config::default();
+
+        let analysis = analyze_tree(&fs, Path::new("/d"), &config);
+        assert_eq!(
+            analysis.stats,
+            analyze_folder_with(&fs, Path::new("/d"), &config)
+        );
+        assert_eq!(analysis.category_bytes(Category::Music), 8);
+        assert_eq!(analysis.category_bytes(Category::Images), 1);
+        assert_eq!((analysis.files, analysis.total_bytes), (6, 22));
+        // Archives are known to the config, so they are not unrecognised
+        let unrecognized: Vec<(&str, usize, u64)> = analysis
+            .unrecognized
+            .iter()
+            .map(|(e, t)| (e.as_str(), t.files, t.bytes))
+            .collect();
+        assert_eq!(unrecognized, [("", 1, 2), ("xyz", 1, 7)]);
+        assert_eq!(analysis.largest[0], (PathBuf::from("/d/sub/setup.xyz"), 7));
+
+        let mut merged = analyze_tree(&fs, Path::new("/d/a.mp3"), &config);
+        assert_eq!(merged.stats.music, 1);
+        merged.merge(&analyze_tree(&fs, Path::new("/d/sub"), &config));
+        assert_eq!(merged.category_bytes(Category::Music), 8);
+        assert_eq!(merged.largest.len(), 4);
+    }
+}
@@ -0,0 +1,495 @@
+//! Archives found in source directories: listing their contents to classify
+//! them, and unpacking them safely
+//!
+//! Zip and tar archives, plain or compressed with gzip or xz, can be read;
+//! other archive formats are only recognised by their extension. Entries
+//! whose path leaves the extraction folder, links and archives over the
+//! `ArchiveConfig` limits are refused before anything is written.
+
+use crate::config::{ArchiveConfig, Config};
+use crate::organizer::classifier::FolderProfile;
+use crate::organizer::mover::create_dirs;
+use crate::platform::filesystem::{FileSystem, Owner};
+use flate2::read::GzDecoder;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+
+/// Decompressed xz chunks buffered between the decoder and the tar reader
+const XZ_CHUNKS: usize = 4;
+
+/// Archive formats that can be listed and unpacked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+}
+
+/// File name suffixes of each format, longest first
+const SUFFIXES: [(&str, ArchiveFormat); 6] = [
+    (".tar.gz", ArchiveFormat::TarGz),
+    (".tar.xz", ArchiveFormat::TarXz),
+    (".tgz", ArchiveFormat::TarGz),
+    (".txz", ArchiveFormat::TarXz),
+    (".tar", ArchiveFormat::Tar),
+    (".zip", ArchiveFormat::Zip),
+];
+
+impl ArchiveFormat {
+    /// Format of the archive at `path`, judged by its name.
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy();
+        SUFFIXES
+            .iter()
+            .find(|(suffix, _)| strip_suffix(&name, suffix).is_some())
+            .map(|(_, format)| *format)
+    }
+
+    /// File name of `path` without the archive suffix, e.g. `album` for
+    /// `album.tar.gz`; the folder an archive is unpacked into.
+    pub fn stem(path: &Path) -> String {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        SUFFIXES
+            .iter()
+            .find_map(|(suffix, _)| strip_suffix(&name, suffix))
+            .filter(|stem| !stem.is_empty())
+            .unwrap_or(&name)
+            .to_string()
+    }
+}
+
+fn strip_suffix<'a>(name: &'a str, suffix: &str) -> Option<&'a str> {
+    let start = name.len().checked_sub(suffix.len())?;
+    name.get(start..)
+        .filter(|end| end.eq_ignore_ascii_case(suffix))
+        .map(|_| &name[..start])
+}
+
+/// A file or directory stored in an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    /// Relative path, guaranteed to stay below the extraction folder.
+    pub path: PathBuf,
+    /// Unpacked size in bytes, 0 for directories.
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+/// Lists the files and directories of an archive.
+///
+/// Fails when an entry would land outside the extraction folder or the
+/// archive exceeds `limits`.
+pub fn list_entries(
+    fs: &dyn FileSystem,
+    path: &Path,
+    format: ArchiveFormat,
+    limits: &ArchiveConfig,
+) -> io::Result<Vec<ArchiveEntry>> {
+    let mut entries = Vec::new();
+    read_entries(fs, path, format, limits, |entry, _| {
+        entries.push(entry);
+        Ok(())
+    })?;
+    Ok(entries)
+}
+
+/// Content summary of archive entries, for `classify_profile`.
+pub fn content_profile(entries: &[ArchiveEntry], config: &Config) -> FolderProfile {
+    let markers = &config.classifier.project_markers;
+    let mut profile = FolderProfile::default();
+    for entry in entries {
+        if profile.project_marker.is_none() {
+            profile.project_marker = entry
+                .path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .find(|name| markers.iter().any(|m| m == name))
+                .map(|name| name.into_owned());
+        }
+        if entry.is_dir {
+            continue;
+        }
+        profile.total_files += 1;
+        if let Some(category) = entry
+            .path
+            .extension()
+            .and_then(|e| config.get_category(&e.to_string_lossy()))
+        {
+            let tally = profile.categories.entry(category).or_default();
+            tally.files += 1;
+            tally.bytes += entry.size;
+        }
+    }
+    profile
+}
+
+/// Unpacks an archive into `destination`, returning the number of files written.
+///
+/// The whole archive is checked before the first file is written. Created
+/// folders and files are given to `owner` when set.
+pub fn extract(
+    fs: &dyn FileSystem,
+    path: &Path,
+    format: ArchiveFormat,
+    destination: &Path,
+    limits: &ArchiveConfig,
+    owner: Option<Owner>,
+) -> io::Result<usize> {
+    list_entries(fs, path, format, limits)?;
+    create_dirs(fs, destination, owner)?;
+    let mut files = 0;
+    read_entries(fs, path, format, limits, |entry, contents| {
+        refuse_symlinks(fs, destination, &entry.path)?;
+        let target = destination.join(&entry.path);
+        if entry.is_dir {
+            return create_dirs(fs, &target, owner);
+        }
+        if let Some(parent) = target.parent() {
+            create_dirs(fs, parent, owner)?;
+        }
+        // Never trust the declared size further than the limits checked it
+        let written = fs.write_from(&target, &mut contents.take(entry.size + 1))?;
+        if written > entry.size {
+            let _ = fs.remove_file(&target);
+            return Err(invalid(format!(
+                "{} is larger than its header says",
+                entry.path.display()
+            )));
+        }
+        if let Some(owner) = owner {
+            fs.set_owner(&target, owner)?;
+        }
+        files += 1;
+        Ok(())
+    })?;
+    Ok(files)
+}
+
+/// Fails when a component of `relative` below `destination` is a symlink,
+/// which writing through would escape the folder.
+fn refuse_symlinks(fs: &dyn FileSystem, destination: &Path, relative: &Path) -> io::Result<()> {
+    let mut current = destination.to_path_buf();
+    for component in relative.components() {
+        current.push(component);
+        if fs.symlink_metadata(&current).is_ok_and(|m| m.is_symlink()) {
+            return Err(invalid(format!(
+                "{} is a symlink, not unpacking through it",
+                current.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Hands each file and directory of the archive to `visit` with a reader
+/// over its contents, enforcing the entry and size limits.
+fn read_entries(
+    fs: &dyn FileSystem,
+    path: &Path,
+    format: ArchiveFormat,
+    limits: &ArchiveConfig,
+    visit: impl FnMut(ArchiveEntry, &mut dyn Read) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut budget = Budget {
+        limits,
+        entries: 0,
+        bytes: 0,
+        visit,
+    };
+    let file = fs.open(path)?;
+    match format {
+        ArchiveFormat::Zip => {
+            let mut archive = zip::ZipArchive::new(file)?;
+            for index in 0..archive.len() {
+                let mut entry = archive.by_index(index)?;
+                if entry.is_symlink() {
+                    continue;
+                }
+                let (name, size, is_dir) = (entry.name().to_string(), entry.size(), entry.is_dir());
+                budget.entry(&name, size, is_dir, &mut entry)?;
+            }
+            Ok(())
+        }
+        ArchiveFormat::Tar => read_tar(file, &mut budget),
+        ArchiveFormat::TarGz => read_tar(GzDecoder::new(file), &mut budget),
+        ArchiveFormat::TarXz => {
+            // The xz decoder only writes, so it runs on its own thread and
+            // feeds the tar reader through a bounded channel; headers and
+            // padding add at most 1 KiB per entry
+            let overhead = (limits.max_entries as u64 + 2).saturating_mul(1024);
+            let limit = limits.max_extracted_bytes.saturating_add(overhead);
+            let (sender, receiver) = mpsc::sync_channel(XZ_CHUNKS);
+            std::thread::scope(|scope| {
+                let decoder = scope.spawn(move || {
+                    let mut output = ChannelWriter {
+                        sender,
+                        written: 0,
+                        limit,
+                    };
+                    lzma_rs::xz_decompress(&mut BufReader::new(file), &mut output)
+                });
+                let reader = ChannelReader {
+                    receiver,
+                    chunk: io::Cursor::new(Vec::new()),
+                };
+                // Dropping the reader stops a decoder still writing
+                let listed = read_tar(reader, &mut budget);
+                let decoded = decoder
+                    .join()
+                    .unwrap_or_else(|_| Err(invalid("xz decoder panicked").into()));
+                match decoded {
+                    Err(lzma_rs::error::Error::IoError(e))
+                        if e.kind() == io::ErrorKind::BrokenPipe && listed.is_ok() =>
+                    {
+                        Ok(())
+                    }
+                    Err(lzma_rs::error::Error::IoError(e)) => listed.and(Err(e)),
+                    Err(e) => listed.and(Err(invalid(e.to_string()))),
+                    Ok(()) => listed,
+                }
+            })
+        }
+    }
+}
+
+fn read_tar<R, F>(reader: R, budget: &mut Budget<'_, F>) -> io::Result<()>
+where
+    R: Read,
+    F: FnMut(ArchiveEntry, &mut dyn Read) -> io::Result<()>,
+{
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let kind = entry.header().entry_type();
+        if !kind.is_file() && !kind.is_dir() {
+            continue;
+        }
+        let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        let size = if kind.is_dir() { 0 } else { entry.size() };
+        budget.entry(&name, size, kind.is_dir(), &mut entry)?;
+    }
+    Ok(())
+}
+
+/// Entries and bytes seen so far, checked against the limits
+struct Budget<'a, F> {
+    limits: &'a ArchiveConfig,
+    entries: usize,
+    bytes: u64,
+    visit: F,
+}
+
+impl<F> Budget<'_, F>
+where
+    F: FnMut(ArchiveEntry, &mut dyn Read) -> io::Result<()>,
+{
+    fn entry(
+        &mut self,
+        name: &str,
+        size: u64,
+        is_dir: bool,
+        contents: &mut dyn Read,
+    ) -> io::Result<()> {
+        let Some(path) = enclosed_path(name) else {
+            return Err(invalid(format!(
+                "entry `{name}` leaves the extraction folder"
+            )));
+        };
+        self.entries += 1;
+        self.bytes = self.bytes.saturating_add(size);
+        if self.entries > self.limits.max_entries {
+            return Err(invalid(format!(
+                "more than {} entries",
+                self.limits.max_entries
+            )));
+        }
+        if self.bytes > self.limits.max_extracted_bytes {
+            return Err(invalid(format!(
+                "more than {} bytes unpacked",
+                self.limits.max_extracted_bytes
+            )));
+        }
+        (self.visit)(ArchiveEntry { path, size, is_dir }, contents)
+    }
+}
+
+/// `name` as a relative path that stays inside the extraction folder, or
+/// `None` when it is absolute or climbs out with `..`.
+fn enclosed_path(name: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+/// Sends decompressed data to a `ChannelReader`, failing once more than
+/// `limit` bytes are written or the reader is gone
+struct ChannelWriter {
+    sender: SyncSender<Vec<u8>>,
+    written: u64,
+    limit: u64,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written += buf.len() as u64;
+        if self.written > self.limit {
+            return Err(invalid("archive unpacks to more than the size limit"));
+        }
+        self.sender
+            .send(buf.to_vec())
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads what a `ChannelWriter` sends; the end of the data once it is dropped
+struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    chunk: io::Cursor<Vec<u8>>,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.chunk.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            match self.receiver.recv() {
+                Ok(chunk) => self.chunk = io::Cursor::new(chunk),
+                Err(_) => return Ok(0),
+            }
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::filesystem::MemoryFileSystem;
+
+    fn zip_bytes(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn tar_xz_bytes(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, name, *contents).unwrap();
+        }
+        let tar = builder.into_inner().unwrap();
+        let mut xz = Vec::new();
+        lzma_rs::xz_compress(&mut io::Cursor::new(tar), &mut xz).unwrap();
+        xz
+    }
+
+    #[test]
+    fn test_formats_are_detected_by_name() {
+        let detect = |name: &str| ArchiveFormat::detect(Path::new(name));
+        assert_eq!(detect("album.ZIP"), Some(ArchiveFormat::Zip));
+        assert_eq!(detect("src.tar.gz"), Some(ArchiveFormat::TarGz));
+        assert_eq!(detect("src.txz"), Some(ArchiveFormat::TarXz));
+        assert_eq!(detect("backup.rar"), None);
+        assert_eq!(ArchiveFormat::stem(Path::new("/d/src.Tar.Gz")), "src");
+        assert_eq!(ArchiveFormat::stem(Path::new("/d/.zip")), ".zip");
+        assert_eq!(enclosed_path("./a/b.mp3"), Some(PathBuf::from("a/b.mp3")));
+        assert_eq!(enclosed_path("a/../../etc/passwd"), None);
+        assert_eq!(enclosed_path("/etc/passwd"), None);
+    }
+
+    #[test]
+    fn test_unsafe_and_oversized_archives_are_refused() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/d/evil.zip", zip_bytes(&[("../outside.txt", b"x")]));
+        fs.add_file(
+            "/d/big.zip",
+            zip_bytes(&[("a.txt", b"12345"), ("b.txt", b"6")]),
+        );
+        let limits = ArchiveConfig::default();
+        let zip = ArchiveFormat::Zip;
+
+        let error = extract(
+            &fs,
+            Path::new("/d/evil.zip"),
+            zip,
+            Path::new("/x"),
+            &limits,
+            None,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("leaves the extraction folder"));
+        assert!(!fs.exists(Path::new("/x")));
+
+        let small = ArchiveConfig {
+            max_extracted_bytes: 5,
+            ..ArchiveConfig::default()
+        };
+        assert!(list_entries(&fs, Path::new("/d/big.zip"), zip, &small).is_err());
+        let few = ArchiveConfig {
+            max_entries: 1,
+            ..ArchiveConfig::default()
+        };
+        assert!(list_entries(&fs, Path::new("/d/big.zip"), zip, &few).is_err());
+        let entries = list_entries(&fs, Path::new("/d/big.zip"), zip, &limits).unwrap();
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn test_tar_xz_archives_are_streamed() {
+        let fs = MemoryFileSystem::new();
+        let big = vec![b'x'; 256 * 1024];
+        let bytes = tar_xz_bytes(&[("a/one.txt", b"1"), ("a/big.bin", &big)]);
+        fs.add_file("/d/src.tar.xz", bytes.clone());
+        fs.add_file("/d/cut.tar.xz", bytes[..bytes.len() / 2].to_vec());
+        let limits = ArchiveConfig::default();
+        let xz = ArchiveFormat::TarXz;
+
+        let entries = list_entries(&fs, Path::new("/d/src.tar.xz"), xz, &limits).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].size, big.len() as u64);
+        assert!(list_entries(&fs, Path::new("/d/cut.tar.xz"), xz, &limits).is_err());
+        let small = ArchiveConfig {
+            max_extracted_bytes: 1024,
+            ..ArchiveConfig::default()
+        };
+        assert!(list_entries(&fs, Path::new("/d/src.tar.xz"), xz, &small).is_err());
+
+        extract(
+            &fs,
+            Path::new("/d/src.tar.xz"),
+            xz,
+            Path::new("/x"),
+            &limits,
+            None,
+        )
+        .unwrap();
+        assert_eq!(fs.contents("/x/a/one.txt").unwrap(), b"1");
+        assert_eq!(fs.contents("/x/a/big.bin").unwrap(), big);
+    }
+}
@@ -1,12 +1,35 @@
 //! Asynchronous file analyzer with improved performance and scalability
 
 use crate::config::Config;
-use crate::organizer::types::FileStats;
+use crate::organizer::analyzer::{analyze_folder_with, analyze_tree, FolderAnalysis};
+use crate::organizer::types::{CancellationToken, FileStats};
+use crate::platform::filesystem::{FileSystem, RealFileSystem};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use tokio::fs;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 use tracing::{error, info};
 
+type AnalyzerError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Statistics for one analyzed directory tree
+#[derive(Debug, Clone)]
+pub struct DirectoryStats {
+    pub path: PathBuf,
+    pub stats: FileStats,
+}
+
+/// Result of analyzing several directory trees
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisSummary {
+    /// Stats for each directory that could be read, in input order.
+    pub per_directory: Vec<DirectoryStats>,
+    /// Directories whose root could not be read, with the error message.
+    pub failed: Vec<(PathBuf, String)>,
+    /// Sum of all per-directory stats.
+    pub totals: FileStats,
+}
+
 /// Asynchronous folder analyzer with parallel processing
 #[allow(dead_code)]
 pub struct AsyncAnalyzer {
@@ -15,13 +38,12 @@ pub struct AsyncAnalyzer {
 }
 
 impl AsyncAnalyzer {
-    /// Create a new async analyzer with configuration
+    /// Create a new async analyzer with configuration.
+    ///
+    /// Concurrency defaults to `PerformanceConfig::thread_pool_size`.
     #[allow(dead_code)]
     pub fn new(config: Arc<Config>) -> Self {
-        let max_concurrent = std::cmp::min(
-            4, // Default to 4 for simplicity
-            num_cpus::get() * 2,
-        );
+        let max_concurrent = config.performance.thread_pool_size.max(1);
 
         Self {
             config,
@@ -29,70 +51,101 @@ impl AsyncAnalyzer {
         }
     }
 
-    /// Analyze multiple directories asynchronously
+    /// Override the number of directories read concurrently
+    pub fn with_max_concurrent(mut self, max_concurrent: usize) -> Self {
+        self.max_concurrent = max_concurrent.max(1);
+        self
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    /// Analyze multiple directory trees concurrently.
+    ///
+    /// Every tree is walked with [`analyze_folder_with`], so ignore rules and
+    /// `Config::traversal` apply exactly as in `analyze_folder`. At most
+    /// `max_concurrent` trees are walked at a time, each on a blocking task.
     #[allow(dead_code)]
     pub async fn analyze_directories(
         &self,
         paths: Vec<PathBuf>,
-    ) -> Result<FileStats, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<AnalysisSummary, AnalyzerError> {
         info!("Starting async analysis of {} directories", paths.len());
 
-        let stats = Arc::new(Mutex::new(FileStats::default()));
-
-        // Process each directory sequentially for now
-        for path in paths {
-            match self.analyze_single_directory(&path).await {
-                Ok(dir_stats) => {
-                    let mut total_stats = stats.lock().unwrap();
-                    total_stats.music += dir_stats.music;
-                    total_stats.videos += dir_stats.videos;
-                    total_stats.images += dir_stats.images;
-                    total_stats.docs += dir_stats.docs;
-                    info!("Analyzed directory {:?}: {:?}", path, dir_stats);
+        let permits = Arc::new(Semaphore::new(self.max_concurrent));
+        let results = futures::future::join_all(paths.iter().map(|path| {
+            let (path, config) = (path.clone(), self.config.clone());
+            let permits = permits.clone();
+            async move {
+                let _permit = permits.acquire_owned().await?;
+                let walk = move || -> Result<FileStats, AnalyzerError> {
+                    // The root is read first so that an unreadable root is reported as an error
+                    RealFileSystem.read_dir(&path)?;
+                    Ok(analyze_folder_with(&RealFileSystem, &path, &config))
+                };
+                tokio::task::spawn_blocking(walk).await?
+            }
+        }))
+        .await;
+
+        let mut summary = AnalysisSummary::default();
+        for (path, result) in paths.into_iter().zip(results) {
+            match result {
+                Ok(stats) => {
+                    info!("Analyzed directory {:?}: {:?}", path, stats);
+                    summary.totals.add(&stats);
+                    summary.per_directory.push(DirectoryStats { path, stats });
                 }
                 Err(e) => {
                     error!("Failed to analyze directory {:?}: {}", path, e);
+                    summary.failed.push((path, e.to_string()));
                 }
             }
         }
 
-        let final_stats = stats.lock().unwrap().clone();
-        Ok(final_stats)
+        Ok(summary)
     }
 
-    /// Analyze a single directory asynchronously
-    #[allow(dead_code)]
-    async fn analyze_single_directory(
+    /// Analyze trees through `fs` with [`analyze_tree`], streaming the results.
+    ///
+    /// At most `max_concurrent` trees are walked at a time, each on a blocking
+    /// task. Every analysis is handed to `on_result` with the index of its
+    /// path as soon as it is ready; trees not started yet are skipped once
+    /// `token` is cancelled or `on_result` returns false.
+    pub async fn analyze_trees<F>(
         &self,
-        path: &Path,
-    ) -> Result<FileStats, Box<dyn std::error::Error + Send + Sync>> {
-        let mut stats = FileStats::default();
-        let file_extensions = self.config.get_file_extensions();
-
-        let mut entries = fs::read_dir(path).await?;
-
-        while let Some(entry) = entries.next_entry().await? {
-            let entry_path = entry.path();
-
-            if entry_path.is_file() {
-                if let Some(extension) = entry_path.extension() {
-                    let ext = extension.to_string_lossy().to_lowercase();
-                    let extension_str = ext.as_str();
-
-                    if file_extensions.music.contains(&extension_str) {
-                        stats.music += 1;
-                    } else if file_extensions.videos.contains(&extension_str) {
-                        stats.videos += 1;
-                    } else if file_extensions.images.contains(&extension_str) {
-                        stats.images += 1;
-                    } else if file_extensions.docs.contains(&extension_str) {
-                        stats.docs += 1;
-                    }
+        fs: Arc<dyn FileSystem>,
+        paths: Vec<PathBuf>,
+        token: &CancellationToken,
+        mut on_result: F,
+    ) where
+        F: FnMut(usize, FolderAnalysis) -> bool,
+    {
+        let permits = Arc::new(Semaphore::new(self.max_concurrent));
+        let mut tasks = JoinSet::new();
+        for (index, path) in paths.into_iter().enumerate() {
+            let (fs, config) = (fs.clone(), self.config.clone());
+            let (permits, token) = (permits.clone(), token.clone());
+            tasks.spawn(async move {
+                let _permit = permits.acquire_owned().await.ok()?;
+                if token.is_cancelled() {
+                    return None;
                 }
+                let walk = move || analyze_tree(fs.as_ref(), &path, &config);
+                let analysis = tokio::task::spawn_blocking(walk).await.ok()?;
+                Some((index, analysis))
+            });
+        }
+        while let Some(joined) = tasks.join_next().await {
+            let Ok(Some((index, analysis))) = joined else {
+                continue;
+            };
+            // Dropping the set aborts the trees still waiting for a permit
+            if !on_result(index, analysis) {
+                return;
             }
         }
-
-        Ok(stats)
     }
 
     /// Get file statistics for a single file asynchronously
@@ -104,20 +157,11 @@ impl AsyncAnalyzer {
         let mut stats = FileStats::default();
 
         if path.is_file() {
-            if let Some(extension) = path.extension() {
-                let ext = extension.to_string_lossy().to_lowercase();
-                let file_extensions = self.config.get_file_extensions();
-                let extension_str = ext.as_str();
-
-                if file_extensions.music.contains(&extension_str) {
-                    stats.music = 1;
-                } else if file_extensions.videos.contains(&extension_str) {
-                    stats.videos = 1;
-                } else if file_extensions.images.contains(&extension_str) {
-                    stats.images = 1;
-                } else if file_extensions.docs.contains(&extension_str) {
-                    stats.docs = 1;
-                }
+            if let Some(category) = path
+                .extension()
+                .and_then(|e| self.config.get_category(&e.to_string_lossy()))
+            {
+                stats.increment(category);
             }
         }
 
@@ -132,8 +176,10 @@ impl AsyncAnalyzer {
             (stats.videos, "video"),
             (stats.images, "image"),
             (stats.docs, "docs"),
+            (stats.archives, "archives"),
+            (stats.code, "code"),
         ];
-        type_counts.sort_by(|a, b| b.0.cmp(&a.0));
+        type_counts.sort_by_key(|t| std::cmp::Reverse(t.0));
 
         if type_counts[0].0 > 0 {
             Some(type_counts[0].1)
@@ -194,6 +240,12 @@ mod tests {
         assert_eq!(stats.videos, 0);
         assert_eq!(stats.images, 0);
         assert_eq!(stats.docs, 0);
+
+        // Extensions known only to the catalog are counted too
+        let catalog_file = temp_dir.path().join("test.m4a");
+        File::create(&catalog_file).unwrap();
+        let stats = analyzer.get_file_stats(&catalog_file).await.unwrap();
+        assert_eq!(stats.music, 1);
     }
 
     #[tokio::test]
@@ -216,6 +268,122 @@ mod tests {
         assert_eq!(results.len(), 4);
     }
 
+    #[tokio::test]
+    async fn test_analyze_directories_matches_sync_analyzer() {
+        use crate::config::TraversalConfig;
+        use crate::organizer::analyzer::analyze_folder;
+
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("first");
+        let second = temp_dir.path().join("second");
+        let files = [
+            "first/a.mp3",
+            "first/b.MP3",
+            "first/nested/c.png",
+            "first/nested/deeper/d.pdf",
+            "first/nested/deeper/e.unknown",
+            "second/f.mkv",
+            "second/g/h.txt",
+            "second/g/i.jpeg",
+        ];
+        for file in files {
+            let path = temp_dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            File::create(&path).unwrap().write_all(b"test").unwrap();
+        }
+        std::fs::write(first.join(".rustganizerignore"), "/b.MP3\n").unwrap();
+        let missing = temp_dir.path().join("missing");
+
+        let config = Arc::new(Config {
+            ignore_patterns: vec!["*.mkv".to_string()],
+            traversal: TraversalConfig {
+                max_depth: Some(1),
+                ..TraversalConfig::default()
+            },
+            ..Config::default()
+        });
+        let analyzer = AsyncAnalyzer::new(config.clone()).with_max_concurrent(2);
+        let summary = analyzer
+            .analyze_directories(vec![first.clone(), second.clone(), missing.clone()])
+            .await
+            .unwrap();
+
+        assert_eq!(summary.per_directory.len(), 2);
+        assert_eq!(summary.per_directory[0].path, first);
+        assert_eq!(
+            summary.per_directory[0].stats,
+            analyze_folder(&first, &config)
+        );
+        assert_eq!(
+            summary.per_directory[1].stats,
+            analyze_folder(&second, &config)
+        );
+        let mut totals = analyze_folder(&first, &config);
+        totals.add(&analyze_folder(&second, &config));
+        assert_eq!(summary.totals, totals);
+        // b.MP3 is ignored by first/.rustganizerignore and f.mkv by the config
+        assert_eq!(summary.totals.music, 1);
+        assert_eq!(summary.totals.videos, 0);
+        // c.png and i.jpeg are below max_depth
+        assert_eq!(summary.totals.images, 0);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].0, missing);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_trees_streams_results_through_the_filesystem() {
+        use crate::platform::filesystem::MemoryFileSystem;
+
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/d/album/a.mp3", "12");
+        fs.add_file("/d/album/b.xyz", "3");
+        fs.add_file("/d/photo.png", "456");
+        let paths = vec![PathBuf::from("/d/album"), PathBuf::from("/d/photo.png")];
+        let config = Arc::new(Config::default());
+        let analyzer = AsyncAnalyzer::new(config.clone()).with_max_concurrent(1);
+        let fs: Arc<dyn FileSystem> = Arc::new(fs);
+
+        let mut results = Vec::new();
+        let token = CancellationToken::new();
+        analyzer
+            .analyze_trees(fs.clone(), paths.clone(), &token, |index, analysis| {
+                results.push((index, analysis));
+                true
+            })
+            .await;
+        results.sort_by_key(|(index, _)| *index);
+        assert_eq!(results.len(), 2);
+        for (index, analysis) in &results {
+            assert_eq!(
+                *analysis,
+                analyze_tree(fs.as_ref(), &paths[*index], &config)
+            );
+        }
+        assert_eq!(results[0].1.files, 2);
+
+        let mut calls = 0;
+        analyzer
+            .analyze_trees(fs.clone(), paths.clone(), &token, |_, _| {
+                calls += 1;
+                false
+            })
+            .await;
+        assert_eq!(calls, 1);
+        token.cancel();
+        analyzer
+            .analyze_trees(fs, paths, &token, |_, _| panic!("cancelled"))
+            .await;
+    }
+
+    #[test]
+    fn test_max_concurrent_from_performance_config() {
+        let mut config = Config::default();
+        config.performance.thread_pool_size = 3;
+        let analyzer = AsyncAnalyzer::new(Arc::new(config));
+        assert_eq!(analyzer.max_concurrent(), 3);
+        assert_eq!(analyzer.with_max_concurrent(0).max_concurrent(), 1);
+    }
+
     #[test]
     fn test_get_majority_type() {
         let analyzer = AsyncAnalyzer::default();
@@ -225,6 +393,9 @@ mod tests {
             videos: 2,
             images: 1,
             docs: 0,
+            archives: 0,
+            code: 0,
+            cleaned: 0,
         };
 
         assert_eq!(analyzer.get_majority_type(&stats), Some("music"));
@@ -0,0 +1,279 @@
+//! Classification of whole folders by the content they hold
+
+use crate::config::{ClassifierConfig, Config};
+use crate::organizer::ignore::IgnoreRules;
+use crate::organizer::types::Category;
+use crate::organizer::walk::{walk_entries, Walked};
+use crate::platform::filesystem::FileSystem;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Number of files and bytes seen for one category
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CategoryTally {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Content summary of a folder tree
+#[derive(Debug, Clone, Default)]
+pub struct FolderProfile {
+    pub categories: BTreeMap<Category, CategoryTally>,
+    pub total_files: usize,
+    /// First project marker found in the tree, if any.
+    pub project_marker: Option<String>,
+    /// Project marker directly inside the folder, making it a repository root.
+    pub repository_marker: Option<String>,
+}
+
+impl FolderProfile {
+    pub fn recognized_files(&self) -> usize {
+        self.categories.values().map(|t| t.files).sum()
+    }
+
+    pub fn recognized_bytes(&self) -> u64 {
+        self.categories.values().map(|t| t.bytes).sum()
+    }
+}
+
+/// Decision for a folder
+#[derive(Debug, Clone, PartialEq)]
+pub enum FolderClass {
+    /// One category dominates; the folder belongs in its destination.
+    Category(Category),
+    /// The folder is the root of a project checkout; it moves as a unit to
+    /// the projects folder.
+    Repository { marker: String },
+    /// A project checkout lies deeper inside; the folder stays where it is.
+    Project { marker: String },
+    /// Several categories, none dominant.
+    Mixed,
+    /// Too few recognised files to decide.
+    Unknown,
+}
+
+/// Classifies folders using the thresholds from `ClassifierConfig`
+pub struct FolderClassifier<'a> {
+    config: &'a Config,
+    fs: &'a dyn FileSystem,
+}
+
+impl<'a> FolderClassifier<'a> {
+    pub fn new(config: &'a Config, fs: &'a dyn FileSystem) -> Self {
+        Self { config, fs }
+    }
+
+    pub fn classify(&self, path: &Path) -> FolderClass {
+        self.classify_with(path, &IgnoreRules::global(self.config, path))
+    }
+
+    /// Classifies `path`, skipping entries matched by `rules`.
+    pub fn classify_with(&self, path: &Path, rules: &IgnoreRules) -> FolderClass {
+        classify_profile(&self.profile(path, rules), &self.config.classifier)
+    }
+
+    /// Walks `path` as configured in `Config::traversal` and tallies its content.
+    pub fn profile(&self, path: &Path, rules: &IgnoreRules) -> FolderProfile {
+        let markers = &self.config.classifier.project_markers;
+        let mut profile = FolderProfile::default();
+        walk_entries(self.fs, path, rules, &self.config.traversal, |walked| {
+            let Walked::Entry {
+                path: entry,
+                metadata,
+            } = walked
+            else {
+                return;
+            };
+            let name = entry
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if markers.contains(&name) {
+                if profile.repository_marker.is_none() && entry.parent() == Some(path) {
+                    profile.repository_marker = Some(name.clone());
+                }
+                if profile.project_marker.is_none() {
+                    profile.project_marker = Some(name);
+                }
+            }
+            if metadata.is_dir() {
+                return;
+            }
+            profile.total_files += 1;
+            if let Some(category) = entry
+                .extension()
+                .and_then(|e| self.config.get_category(&e.to_string_lossy()))
+            {
+                let tally = profile.categories.entry(category).or_default();
+                tally.files += 1;
+                tally.bytes += metadata.len;
+            }
+        });
+        profile
+    }
+}
+
+/// Applies the classification rules to an already computed profile.
+///
+/// Each category scores its share of the recognised files blended with its
+/// share of the recognised bytes according to `byte_weight`. The best
+/// category wins only if it reaches `dominance_threshold` on its own.
+pub fn classify_profile(profile: &FolderProfile, rules: &ClassifierConfig) -> FolderClass {
+    if let Some(marker) = &profile.repository_marker {
+        return FolderClass::Repository {
+            marker: marker.clone(),
+        };
+    }
+    if let Some(marker) = &profile.project_marker {
+        return FolderClass::Project {
+            marker: marker.clone(),
+        };
+    }
+    let recognized_files = profile.recognized_files();
+    if recognized_files == 0
+        || (recognized_files as f64) < rules.min_recognized_ratio * profile.total_files as f64
+    {
+        return FolderClass::Unknown;
+    }
+
+    let recognized_bytes = profile.recognized_bytes();
+    let byte_weight = if recognized_bytes == 0 {
+        0.0
+    } else {
+        rules.byte_weight.clamp(0.0, 1.0)
+    };
+    let mut scores: Vec<(Category, f64)> = profile
+        .categories
+        .iter()
+        .map(|(category, tally)| {
+            let count_share = tally.files as f64 / recognized_files as f64;
+            let byte_share = if recognized_bytes == 0 {
+                0.0
+            } else {
+                tally.bytes as f64 / recognized_bytes as f64
+            };
+            (
+                *category,
+                (1.0 - byte_weight) * count_share + byte_weight * byte_share,
+            )
+        })
+        .collect();
+    scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    match scores.as_slice() {
+        [(best, score), rest @ ..]
+            if *score >= rules.dominance_threshold
+                && rest.first().is_none_or(|(_, second)| second < score) =>
+        {
+            FolderClass::Category(*best)
+        }
+        _ => FolderClass::Mixed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::filesystem::MemoryFileSystem;
+
+    fn profile(entries: &[(Category, usize, u64)], total_files: usize) -> FolderProfile {
+        FolderProfile {
+            categories: entries
+                .iter()
+                .map(|(c, files, bytes)| {
+                    (
+                        *c,
+                        CategoryTally {
+                            files: *files,
+                            bytes: *bytes,
+                        },
+                    )
+                })
+                .collect(),
+            total_files,
+            project_marker: None,
+            repository_marker: None,
+        }
+    }
+
+    #[test]
+    fn test_single_file_among_unknowns_is_unknown() {
+        let rules = ClassifierConfig::default();
+        let p = profile(&[(Category::Music, 1, 5_000_000)], 501);
+        assert_eq!(classify_profile(&p, &rules), FolderClass::Unknown);
+    }
+
+    #[test]
+    fn test_dominant_category() {
+        let rules = ClassifierConfig::default();
+        let p = profile(
+            &[
+                (Category::Music, 9, 90_000_000),
+                (Category::Docs, 1, 10_000),
+            ],
+            10,
+        );
+        assert_eq!(
+            classify_profile(&p, &rules),
+            FolderClass::Category(Category::Music)
+        );
+    }
+
+    #[test]
+    fn test_bytes_shift_the_decision() {
+        let rules = ClassifierConfig::default();
+        // Many tiny subtitles next to a few large videos
+        let p = profile(
+            &[
+                (Category::Videos, 3, 3_000_000_000),
+                (Category::Docs, 5, 50_000),
+            ],
+            8,
+        );
+        assert_eq!(classify_profile(&p, &rules), FolderClass::Mixed);
+
+        let bytes_only = ClassifierConfig {
+            byte_weight: 1.0,
+            ..ClassifierConfig::default()
+        };
+        assert_eq!(
+            classify_profile(&p, &bytes_only),
+            FolderClass::Category(Category::Videos)
+        );
+    }
+
+    #[test]
+    fn test_tie_is_mixed() {
+        let rules = ClassifierConfig {
+            dominance_threshold: 0.5,
+            ..ClassifierConfig::default()
+        };
+        let p = profile(&[(Category::Music, 2, 100), (Category::Images, 2, 100)], 4);
+        assert_eq!(classify_profile(&p, &rules), FolderClass::Mixed);
+    }
+
+    #[test]
+    fn test_project_marker_wins() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/d/repo/src/a.mp3", "x");
+        fs.add_file("/d/repo/sub/Cargo.toml", "x");
+        let config = Config::default();
+
+        let class = FolderClassifier::new(&config, &fs).classify(Path::new("/d/repo"));
+        assert_eq!(
+            class,
+            FolderClass::Project {
+                marker: "Cargo.toml".to_string()
+            }
+        );
+
+        fs.add_file("/d/repo/pyproject.toml", "x");
+        let class = FolderClassifier::new(&config, &fs).classify(Path::new("/d/repo"));
+        assert_eq!(
+            class,
+            FolderClass::Repository {
+                marker: "pyproject.toml".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,1213 @@
+//! Organizer engine: plans and executes the moves for a user's home
+
+use crate::config::{Config, RetentionRule};
+use crate::error::{Error, ErrorHandler, Result};
+use crate::logging::{log_organization_complete, log_organization_start};
+use crate::organizer::analyzer::file_extension;
+use crate::organizer::archive::{content_profile, extract, list_entries, ArchiveFormat};
+use crate::organizer::classifier::{classify_profile, FolderClass, FolderClassifier};
+use crate::organizer::ignore::{pin_entry, IgnoreRules, IGNORE_FILE_NAME};
+use crate::organizer::mover::{
+    create_dirs, localized_error, move_dir_recursive, move_file, remove_empty_dirs,
+};
+use crate::organizer::resolver::{FolderResolver, LocalizedFolderResolver, SourceDir};
+use crate::organizer::retention::matching_rule;
+use crate::organizer::trash::move_to_trash;
+use crate::organizer::types::{
+    ActionKind, CancellationToken, Category, CollisionPolicy, ExtensionTally, FileStats,
+    FolderStrategy, ItemOutcome, ItemStatus, MultiUserReport, NoProgress, Plan, PlannedAction,
+    ProgressEvent, ProgressSink, RetentionAction, RunReport, SkipReason, SkippedEntry,
+    SymlinkPolicy, UserPlan, UserRunResult,
+};
+use crate::organizer::walk::{unorganized, walk_entries, Walked};
+#[cfg(unix)]
+use crate::organizer::worker::{spawn_worker, WorkerRequest};
+use crate::organizer::worker::{WorkerReply, WorkerTask};
+use crate::platform::audit::{AuditSink, AuditedFileSystem};
+use crate::platform::filesystem::{FileSystem, Metadata, Owner, RealFileSystem};
+use crate::platform::user::{effective_uid, system_user_provider, UserProvider};
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use std::collections::{BTreeMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Organizes the source folders of a user's home into category folders.
+///
+/// Built with [`Organizer::builder`]; every collaborator has a sensible default
+/// so only the parts a caller cares about need to be set.
+pub struct Organizer {
+    config: Arc<Config>,
+    user_provider: Arc<dyn UserProvider>,
+    resolver: Arc<dyn FolderResolver>,
+    fs: Arc<dyn FileSystem>,
+    collision_policy: CollisionPolicy,
+    dry_run: bool,
+    progress: Arc<dyn ProgressSink>,
+    cancellation: CancellationToken,
+    /// Pool for parallel execution, `None` when running serially.
+    pool: Option<Arc<ThreadPool>>,
+    /// Pool running users concurrently in `run_users`.
+    user_pool: Option<Arc<ThreadPool>>,
+    language: String,
+    /// Uid the filesystem operations run as, `None` where there are no numeric users.
+    acting_uid: Option<u32>,
+    /// Organize each user of `run_users` in a worker process running as that user.
+    privilege_separation: bool,
+    /// Executable started for workers, the current one by default.
+    worker_command: Option<PathBuf>,
+    /// Receives the audit records of workers; local mutations go through `fs`.
+    audit: Option<Arc<dyn AuditSink>>,
+}
+
+/// Builder for [`Organizer`]
+#[derive(Default)]
+pub struct OrganizerBuilder {
+    config: Option<Arc<Config>>,
+    language: Option<String>,
+    user_provider: Option<Arc<dyn UserProvider>>,
+    resolver: Option<Arc<dyn FolderResolver>>,
+    fs: Option<Arc<dyn FileSystem>>,
+    collision_policy: CollisionPolicy,
+    dry_run: bool,
+    progress: Option<Arc<dyn ProgressSink>>,
+    cancellation: Option<CancellationToken>,
+    acting_uid: Option<Option<u32>>,
+    privilege_separation: bool,
+    worker_command: Option<PathBuf>,
+    audit: Option<Arc<dyn AuditSink>>,
+}
+
+impl OrganizerBuilder {
+    pub fn config(mut self, config: impl Into<Arc<Config>>) -> Self {
+        self.config = Some(config.into());
+        self
+    }
+
+    /// Language used by the default folder resolver. Defaults to `UiConfig::language`.
+    pub fn language(mut self, lang: impl Into<String>) -> Self {
+        self.language = Some(lang.into());
+        self
+    }
+
+    pub fn user_provider(mut self, user_provider: Arc<dyn UserProvider>) -> Self {
+        self.user_provider = Some(user_provider);
+        self
+    }
+
+    pub fn folder_resolver(mut self, resolver: Arc<dyn FolderResolver>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    pub fn filesystem(mut self, fs: Arc<dyn FileSystem>) -> Self {
+        self.fs = Some(fs);
+        self
+    }
+
+    pub fn collision_policy(mut self, policy: CollisionPolicy) -> Self {
+        self.collision_policy = policy;
+        self
+    }
+
+    /// When set, `execute` reports what would happen without touching the filesystem.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn progress(mut self, progress: Arc<dyn ProgressSink>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Uid the organizer acts as. Defaults to the effective uid of the process;
+    /// as root, created folders are given to the owner of the home.
+    pub fn acting_uid(mut self, uid: Option<u32>) -> Self {
+        self.acting_uid = Some(uid);
+        self
+    }
+
+    /// Organizes each user of `run_users` in a separate process that runs as
+    /// the owner of the home, see [`crate::organizer::worker`].
+    pub fn privilege_separation(mut self, enabled: bool) -> Self {
+        self.privilege_separation = enabled;
+        self
+    }
+
+    /// Executable started for workers; it must call `worker::run_worker` when
+    /// given `worker::WORKER_ARG`. Defaults to the current executable.
+    pub fn worker_command(mut self, command: impl Into<PathBuf>) -> Self {
+        self.worker_command = Some(command.into());
+        self
+    }
+
+    /// Records every filesystem mutation, including those made by workers.
+    pub fn audit(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.audit = Some(sink);
+        self
+    }
+
+    pub fn build(self) -> Organizer {
+        let config = self.config.unwrap_or_default();
+        let language = self.language.unwrap_or_else(|| config.ui.language.clone());
+        let resolver = self.resolver.unwrap_or_else(|| {
+            Arc::new(LocalizedFolderResolver::new(
+                config.clone(),
+                language.clone(),
+            ))
+        });
+        let pool = if config.performance.use_parallel_processing {
+            build_pool(config.performance.thread_pool_size, "mover")
+        } else {
+            None
+        };
+        let user_pool = build_pool(config.performance.max_concurrent_users, "user");
+        let mut fs = self.fs.unwrap_or_else(|| Arc::new(RealFileSystem));
+        if let Some(sink) = &self.audit {
+            fs = Arc::new(AuditedFileSystem::new(fs, sink.clone()));
+        }
+        Organizer {
+            pool,
+            user_pool,
+            language,
+            user_provider: self.user_provider.unwrap_or_else(system_user_provider),
+            resolver,
+            fs,
+            collision_policy: self.collision_policy,
+            dry_run: self.dry_run,
+            progress: self.progress.unwrap_or_else(|| Arc::new(NoProgress)),
+            cancellation: self.cancellation.unwrap_or_default(),
+            acting_uid: self.acting_uid.unwrap_or_else(effective_uid),
+            privilege_separation: self.privilege_separation,
+            worker_command: self.worker_command,
+            audit: self.audit,
+            config,
+        }
+    }
+}
+
+impl Organizer {
+    pub fn builder() -> OrganizerBuilder {
+        OrganizerBuilder::default()
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// Filesystem the organizer reads and moves files through.
+    pub fn filesystem(&self) -> &Arc<dyn FileSystem> {
+        &self.fs
+    }
+
+    /// Plans and executes the organization of a user's home.
+    pub fn run(&self, username: &str) -> Result<RunReport> {
+        let plan = self.plan(username)?;
+        self.execute(&plan)
+    }
+
+    /// Organizes several users, at most `PerformanceConfig::max_concurrent_users` at a time.
+    ///
+    /// A failing or panicking user does not affect the others; the result
+    /// holds one entry per user in input order.
+    pub fn run_users(&self, usernames: &[String]) -> MultiUserReport {
+        self.for_each_user(usernames, |username| {
+            let outcome = if self.privilege_separation {
+                self.run_in_worker(username)
+            } else {
+                self.run(username)
+            };
+            outcome.map_err(|e| localized_error(&self.config, &self.language, &e))
+        })
+    }
+
+    /// Plans several users like `run_users` would organize them, without
+    /// modifying anything, so the plans can be reviewed first.
+    pub fn plan_users(&self, usernames: &[String]) -> Vec<UserPlan> {
+        let plan_one = |username: &String| {
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| self.plan_user(username)));
+            let (plan, error) = match outcome {
+                Ok(Ok(plan)) => (Some(plan), None),
+                Ok(Err(e)) => (
+                    None,
+                    Some(localized_error(&self.config, &self.language, &e)),
+                ),
+                Err(_) => (None, Some("organizer panicked".to_string())),
+            };
+            UserPlan {
+                username: username.clone(),
+                plan,
+                error,
+            }
+        };
+        match &self.user_pool {
+            Some(pool) => pool.install(|| usernames.par_iter().map(plan_one).collect()),
+            None => usernames.iter().map(plan_one).collect(),
+        }
+    }
+
+    /// Executes reviewed plans; users whose planning failed keep their error.
+    pub fn execute_plans(&self, plans: &[UserPlan]) -> MultiUserReport {
+        let usernames: Vec<String> = plans.iter().map(|p| p.username.clone()).collect();
+        self.for_each_user(&usernames, |username| {
+            let Some(user_plan) = plans.iter().find(|p| p.username == username) else {
+                return Err("no plan".to_string());
+            };
+            match (&user_plan.plan, &user_plan.error) {
+                (Some(plan), _) => self
+                    .execute_user(plan)
+                    .map_err(|e| localized_error(&self.config, &self.language, &e)),
+                (None, error) => Err(error.clone().unwrap_or_else(|| "no plan".to_string())),
+            }
+        })
+    }
+
+    /// Runs `organize` for every user on the user pool and collects the results
+    /// with their timing; a panic is reported as that user's error.
+    fn for_each_user<F>(&self, usernames: &[String], organize: F) -> MultiUserReport
+    where
+        F: Fn(&str) -> std::result::Result<RunReport, String> + Sync,
+    {
+        let run_one = |username: &String| {
+            let started_at = SystemTime::now();
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| organize(username)));
+            let (report, error) = match outcome {
+                Ok(Ok(report)) => (Some(report), None),
+                Ok(Err(error)) => (None, Some(error)),
+                Err(_) => (None, Some("organizer panicked".to_string())),
+            };
+            UserRunResult {
+                username: username.clone(),
+                report,
+                error,
+                started_at: started_at
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default(),
+                duration_ms: started_at
+                    .elapsed()
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or_default(),
+                source_dirs: self
+                    .source_dirs(username)
+                    .map(|dirs| dirs.into_iter().map(|d| d.path).collect())
+                    .unwrap_or_default(),
+            }
+        };
+        let users: Vec<UserRunResult> = match &self.user_pool {
+            Some(pool) => pool.install(|| usernames.par_iter().map(run_one).collect()),
+            None => usernames.iter().map(run_one).collect(),
+        };
+        let mut totals = FileStats::default();
+        for report in users.iter().filter_map(|u| u.report.as_ref()) {
+            totals.add(&report.stats);
+        }
+        MultiUserReport { users, totals }
+    }
+
+    /// Organizes a user in a worker process running as the owner of the home.
+    ///
+    /// Falls back to `run` where the owner is unknown.
+    pub fn run_in_worker(&self, username: &str) -> Result<RunReport> {
+        match self.in_worker(username, WorkerTask::Run)? {
+            Some(WorkerReply::Report(report)) => Ok(report),
+            Some(WorkerReply::Plan(_)) => Err(unexpected_reply(username)),
+            None => self.run(username),
+        }
+    }
+
+    /// Plans a user, in a worker when privilege separation is enabled.
+    pub fn plan_user(&self, username: &str) -> Result<Plan> {
+        if !self.privilege_separation {
+            return self.plan(username);
+        }
+        match self.in_worker(username, WorkerTask::Plan)? {
+            Some(WorkerReply::Plan(plan)) => Ok(plan),
+            Some(WorkerReply::Report(_)) => Err(unexpected_reply(username)),
+            None => self.plan(username),
+        }
+    }
+
+    /// Executes a plan, in a worker when privilege separation is enabled.
+    pub fn execute_user(&self, plan: &Plan) -> Result<RunReport> {
+        if !self.privilege_separation {
+            return self.execute(plan);
+        }
+        let task = WorkerTask::Execute { plan: plan.clone() };
+        match self.in_worker(&plan.username, task)? {
+            Some(WorkerReply::Report(report)) => Ok(report),
+            Some(WorkerReply::Plan(_)) => Err(unexpected_reply(&plan.username)),
+            None => self.execute(plan),
+        }
+    }
+
+    /// Runs `task` in a worker running as the owner of the user's home.
+    ///
+    /// Returns `None` where the owner is unknown, for the caller to act in-process.
+    #[cfg(unix)]
+    fn in_worker(&self, username: &str, task: WorkerTask) -> Result<Option<WorkerReply>> {
+        let username = username.trim();
+        let home = self.home(username)?;
+        let Some(owner) = self.fs.metadata(&home).ok().and_then(|m| m.owner) else {
+            return Ok(None);
+        };
+        let command = match &self.worker_command {
+            Some(command) => command.clone(),
+            None => std::env::current_exe()?,
+        };
+        let request = WorkerRequest {
+            username: username.to_string(),
+            home,
+            language: self.language.clone(),
+            collision_policy: self.collision_policy,
+            dry_run: self.dry_run,
+            file_extensions: self.config.file_extensions.clone(),
+            classifier: self.config.classifier.clone(),
+            sources: self.config.sources.clone(),
+            ignore_patterns: self.config.ignore_patterns.clone(),
+            traversal: self.config.traversal.clone(),
+            archives: self.config.archives.clone(),
+            retention: self.config.retention.clone(),
+            performance: self.config.performance.clone(),
+            audit: self.audit.is_some(),
+            log_level: self.config.logging.level.clone(),
+            task,
+        };
+        spawn_worker(
+            &command,
+            &request,
+            owner,
+            self.progress.as_ref(),
+            self.audit.as_deref(),
+            &self.cancellation,
+        )
+        .map(Some)
+    }
+
+    /// Without numeric owners there are no workers to run as.
+    #[cfg(not(unix))]
+    fn in_worker(&self, _username: &str, _task: WorkerTask) -> Result<Option<WorkerReply>> {
+        Ok(None)
+    }
+
+    /// Gives a planned move a different category, keeping its path below the
+    /// category folder; with `CollisionPolicy::Rename` the new destination
+    /// avoids the other destinations of the plan.
+    pub fn recategorize(&self, plan: &mut Plan, index: usize, category: Category) {
+        let home = plan.home.clone();
+        let reserved: HashSet<PathBuf> = plan
+            .actions
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != index)
+            .filter_map(|(_, a)| a.destination.clone())
+            .collect();
+        let Some(action) = plan.actions.get_mut(index) else {
+            return;
+        };
+        let (Some(old), Some(destination)) = (action.category, &action.destination) else {
+            return;
+        };
+        let old_dir = self.resolver.category_dir(&home, old);
+        let relative = match destination.strip_prefix(&old_dir) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => PathBuf::from(destination.file_name().unwrap_or_default()),
+        };
+        let wanted = self.resolver.category_dir(&home, category).join(relative);
+        action.destination = Some(match self.collision_policy {
+            CollisionPolicy::Rename => self.free_name(&wanted, &reserved),
+            CollisionPolicy::Overwrite | CollisionPolicy::Skip => wanted,
+        });
+        action.category = Some(category);
+    }
+
+    /// Directories organized for a user.
+    pub fn source_dirs(&self, username: &str) -> Result<Vec<SourceDir>> {
+        let home = self.home(username)?;
+        Ok(self.resolver.source_dirs(&home))
+    }
+
+    /// Entries at the top of a source directory, sorted by path.
+    pub fn source_entries(&self, source_dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut entries = self
+            .fs
+            .read_dir(source_dir)
+            .map_err(|source| Error::FileOperation {
+                operation: "list".to_string(),
+                path: source_dir.to_path_buf(),
+                source,
+            })?;
+        entries.sort();
+        Ok(entries)
+    }
+
+    /// Pins an entry of one of `username`'s source directories so later runs
+    /// leave it alone.
+    ///
+    /// Returns the pattern added to the directory's `.rustganizerignore`,
+    /// which goes to the home's owner when pinning as root.
+    pub fn pin(&self, username: &str, source_dir: &Path, entry: &Path) -> Result<String> {
+        let home = self.home(username)?;
+        let owner = self.home_owner(&home)?;
+        if !source_dir.starts_with(&home) {
+            return Err(Error::PermissionDenied {
+                path: source_dir.to_path_buf(),
+            });
+        }
+        let pin_error = |source| Error::FileOperation {
+            operation: "pin".to_string(),
+            path: entry.to_path_buf(),
+            source,
+        };
+        let pattern = pin_entry(self.fs.as_ref(), source_dir, entry).map_err(pin_error)?;
+        if let Some(owner) = owner {
+            self.fs
+                .set_owner(&source_dir.join(IGNORE_FILE_NAME), owner)
+                .map_err(pin_error)?;
+        }
+        Ok(pattern)
+    }
+
+    /// Computes the actions for a user without modifying anything.
+    pub fn plan(&self, username: &str) -> Result<Plan> {
+        let username = username.trim();
+        let home = self.home(username)?;
+        self.home_owner(&home)?;
+
+        let mut planner = Planner::new(self, &home);
+        for source in self.resolver.source_dirs(&home) {
+            if self.cancellation.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            planner.plan_source(&source);
+        }
+        let Planner {
+            actions,
+            skipped,
+            unrecognized,
+            ..
+        } = planner;
+
+        Ok(Plan {
+            username: username.to_string(),
+            home,
+            actions,
+            skipped,
+            unrecognized,
+        })
+    }
+
+    /// What a run would do with the entries of `dir`, planned as if it were
+    /// one of the user's source directories. Nothing is modified.
+    ///
+    /// A configured source directory keeps its folder strategy; any other
+    /// directory gets the default one.
+    pub fn explain(&self, username: &str, dir: &Path) -> Result<Plan> {
+        let home = self.home(username)?;
+        let folder_strategy = self
+            .resolver
+            .source_dirs(&home)
+            .into_iter()
+            .find(|source| source.path == dir)
+            .map(|source| source.folder_strategy)
+            .unwrap_or_default();
+        let mut planner = Planner::new(self, &home);
+        planner.plan_source(&SourceDir {
+            path: dir.to_path_buf(),
+            folder_strategy,
+        });
+        let Planner {
+            actions,
+            skipped,
+            unrecognized,
+            ..
+        } = planner;
+        Ok(Plan {
+            username: username.trim().to_string(),
+            home,
+            actions,
+            skipped,
+            unrecognized,
+        })
+    }
+
+    /// Owner to give the folders created below `home`, if they need one.
+    ///
+    /// Fails with `PermissionDenied` when a non-root process is asked to
+    /// organize a home it does not own.
+    fn home_owner(&self, home: &Path) -> Result<Option<Owner>> {
+        let Some(uid) = self.acting_uid else {
+            return Ok(None);
+        };
+        let Some(owner) = self.fs.metadata(home).ok().and_then(|m| m.owner) else {
+            return Ok(None);
+        };
+        if uid == 0 {
+            Ok(Some(owner).filter(|o| o.uid != 0))
+        } else if owner.uid == uid {
+            Ok(None)
+        } else {
+            Err(Error::PermissionDenied {
+                path: home.to_path_buf(),
+            })
+        }
+    }
+
+    /// Home directory of a user.
+    pub fn home(&self, username: &str) -> Result<PathBuf> {
+        let username = username.trim();
+        if username.is_empty() {
+            return Err(Error::EmptyUsername);
+        }
+        self.user_provider
+            .user_home(username)
+            .ok_or_else(|| Error::UserNotFound {
+                username: username.to_string(),
+            })
+    }
+
+    /// Executes a plan, returning a per-item report.
+    ///
+    /// Failures of individual items are recorded in the report rather than
+    /// aborting the run. Cancelling stops before the next item.
+    ///
+    /// Actions run in phases (deletions, then folders, then files) so the
+    /// per-directory order of the serial organizer is kept. Within a phase,
+    /// items run on the rayon pool when `use_parallel_processing` is enabled;
+    /// a folder is always moved by a single task. Items in the report keep
+    /// the order of the plan either way.
+    pub fn execute(&self, plan: &Plan) -> Result<RunReport> {
+        let owner = self.home_owner(&plan.home)?;
+        log_organization_start(&plan.username, &self.language);
+        let total = plan.actions.len();
+        self.progress.on_progress(&ProgressEvent::Started {
+            username: plan.username.clone(),
+            total,
+        });
+        let context = ExecutionContext {
+            home: plan.home.clone(),
+            owner,
+            total,
+            completed: AtomicUsize::new(0),
+            claimed: Mutex::new(
+                plan.actions
+                    .iter()
+                    .filter_map(|a| a.destination.clone())
+                    .collect(),
+            ),
+        };
+
+        let mut phases: Vec<u8> = plan.actions.iter().map(|a| a.kind.phase()).collect();
+        phases.sort_unstable();
+        phases.dedup();
+        let mut statuses: Vec<Option<ItemStatus>> = vec![None; total];
+        for phase in phases {
+            let batch: Vec<(usize, &PlannedAction)> = plan
+                .actions
+                .iter()
+                .enumerate()
+                .filter(|(_, a)| a.kind.phase() == phase)
+                .collect();
+            let results: Vec<(usize, ItemStatus)> = match &self.pool {
+                Some(pool) => pool.install(|| {
+                    batch
+                        .par_iter()
+                        .map(|(index, action)| (*index, self.run_item(action, &context)))
+                        .collect()
+                }),
+                None => batch
+                    .iter()
+                    .map(|(index, action)| (*index, self.run_item(action, &context)))
+                    .collect(),
+            };
+            for (index, status) in results {
+                statuses[index] = Some(status);
+            }
+        }
+
+        // Merge the per-item results into the report in plan order
+        let mut report = RunReport {
+            username: plan.username.clone(),
+            skipped: plan.skipped.clone(),
+            dry_run: self.dry_run,
+            unrecognized: plan.unrecognized.clone(),
+            ..RunReport::default()
+        };
+        for (action, status) in plan.actions.iter().zip(statuses) {
+            let outcome = ItemOutcome {
+                action: action.clone(),
+                status: status.expect("every action belongs to a phase"),
+            };
+            record_outcome(&mut report, &outcome);
+            report.items.push(outcome);
+        }
+        log_organization_complete(&report.stats, &report.errors);
+        self.progress.on_progress(&ProgressEvent::Finished {
+            username: plan.username.clone(),
+        });
+        Ok(report)
+    }
+
+    /// Executes one action and reports its progress.
+    fn run_item(&self, action: &PlannedAction, context: &ExecutionContext) -> ItemStatus {
+        let status = if self.cancellation.is_cancelled() {
+            ItemStatus::Skipped {
+                reason: CANCELLED.to_string(),
+            }
+        } else {
+            self.execute_action(action, context)
+        };
+        let completed = context.completed.fetch_add(1, Ordering::SeqCst) + 1;
+        self.progress.on_progress(&ProgressEvent::Item {
+            completed,
+            total: context.total,
+            outcome: ItemOutcome {
+                action: action.clone(),
+                status: status.clone(),
+            },
+        });
+        status
+    }
+
+    /// Returns `wanted` or the first `name (n).ext` variant that is not taken.
+    fn free_name(&self, wanted: &Path, reserved: &HashSet<PathBuf>) -> PathBuf {
+        let taken = |p: &Path| reserved.contains(p) || self.fs.symlink_metadata(p).is_ok();
+        if !taken(wanted) {
+            return wanted.to_path_buf();
+        }
+        let stem = wanted
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let extension = wanted.extension().map(|e| e.to_string_lossy().into_owned());
+        (1..)
+            .map(|n| {
+                let name = match &extension {
+                    Some(ext) => format!("{stem} ({n}).{ext}"),
+                    None => format!("{stem} ({n})"),
+                };
+                wanted.with_file_name(name)
+            })
+            .find(|candidate| !taken(candidate))
+            .expect("unbounded candidate iterator")
+    }
+
+    fn execute_action(&self, action: &PlannedAction, context: &ExecutionContext) -> ItemStatus {
+        if self.dry_run {
+            return ItemStatus::DryRun;
+        }
+        let result = match (action.kind, &action.destination) {
+            (ActionKind::DeleteEmpty, _) => self.fs.remove_file(&action.source),
+            (ActionKind::TrashStale, _) => move_to_trash(
+                self.fs.as_ref(),
+                &context.home,
+                &action.source,
+                context.owner,
+            )
+            .map(|_| ()),
+            (ActionKind::RemoveEmptyDirs, _) => {
+                return match remove_empty_dirs(self.fs.as_ref(), &action.source) {
+                    Ok(true) => ItemStatus::Deleted,
+                    Ok(false) => ItemStatus::Skipped {
+                        reason: "folder still has unorganized files".to_string(),
+                    },
+                    Err(e) => ItemStatus::Failed {
+                        error: ErrorHandler::handle_directory_operation(
+                            e,
+                            operation_name(action.kind),
+                            &action.source,
+                        )
+                        .to_string(),
+                    },
+                };
+            }
+            (kind, Some(destination)) => {
+                let destination = if self.fs.symlink_metadata(destination).is_ok() {
+                    match self.collision_policy {
+                        CollisionPolicy::Overwrite => destination.clone(),
+                        CollisionPolicy::Rename => {
+                            // Claim the new name under the lock so that
+                            // concurrent items never pick the same one
+                            let mut claimed = context.claimed.lock().unwrap();
+                            let renamed = self.free_name(destination, &claimed);
+                            claimed.insert(renamed.clone());
+                            renamed
+                        }
+                        CollisionPolicy::Skip => {
+                            return ItemStatus::Skipped {
+                                reason: format!("{} already exists", destination.display()),
+                            };
+                        }
+                    }
+                } else {
+                    destination.clone()
+                };
+                self.move_entry(kind, &action.source, &destination, context)
+            }
+            (_, None) => {
+                return ItemStatus::Failed {
+                    error: "no destination planned".to_string(),
+                }
+            }
+        };
+        match result {
+            Ok(()) if action.kind == ActionKind::DeleteEmpty => ItemStatus::Deleted,
+            Ok(()) => ItemStatus::Moved,
+            Err(e) => ItemStatus::Failed {
+                error: ErrorHandler::handle_file_operation(
+                    e,
+                    operation_name(action.kind),
+                    &action.source,
+                )
+                .to_string(),
+            },
+        }
+    }
+
+    fn move_entry(
+        &self,
+        kind: ActionKind,
+        source: &Path,
+        destination: &Path,
+        context: &ExecutionContext,
+    ) -> std::io::Result<()> {
+        let fs = self.fs.as_ref();
+        if let Some(parent) = destination.parent() {
+            create_dirs(fs, parent, context.owner)?;
+        }
+        match kind {
+            ActionKind::MoveFolder => move_dir_recursive(fs, source, destination),
+            ActionKind::ExtractArchive => {
+                let format = ArchiveFormat::detect(source).ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a readable archive")
+                })?;
+                let limits = &self.config.archives;
+                extract(fs, source, format, destination, limits, context.owner)?;
+                move_to_trash(fs, &context.home, source, context.owner).map(|_| ())
+            }
+            _ => move_file(fs, source, destination),
+        }
+    }
+}
+
+/// Accumulates the actions for one user while walking the source directories
+struct Planner<'a> {
+    organizer: &'a Organizer,
+    home: &'a Path,
+    classifier: FolderClassifier<'a>,
+    /// Destinations already handed out, so two sources never share one.
+    reserved: HashSet<PathBuf>,
+    actions: Vec<PlannedAction>,
+    skipped: Vec<SkippedEntry>,
+    unrecognized: BTreeMap<String, ExtensionTally>,
+    /// Time retention rules measure the age of files against.
+    now: SystemTime,
+}
+
+impl<'a> Planner<'a> {
+    fn new(organizer: &'a Organizer, home: &'a Path) -> Self {
+        Self {
+            organizer,
+            home,
+            now: SystemTime::now(),
+            classifier: FolderClassifier::new(&organizer.config, organizer.fs.as_ref()),
+            reserved: HashSet::new(),
+            actions: Vec::new(),
+            skipped: Vec::new(),
+            unrecognized: BTreeMap::new(),
+        }
+    }
+
+    fn plan_source(&mut self, source: &SourceDir) {
+        let fs = self.organizer.fs.clone();
+        let Ok(mut entries) = fs.read_dir(&source.path) else {
+            return;
+        };
+        entries.sort();
+        let config = &self.organizer.config;
+        let rules = IgnoreRules::for_root(fs.as_ref(), config, &source.path);
+        let first = self.actions.len();
+        for path in entries {
+            let Ok(mut metadata) = fs.symlink_metadata(&path) else {
+                continue;
+            };
+            let is_link = metadata.is_symlink();
+            if is_link && config.traversal.symlinks == SymlinkPolicy::Follow {
+                match fs.metadata(&path) {
+                    Ok(target) => metadata = target,
+                    Err(_) => {
+                        let target = fs.read_link(&path).unwrap_or_default();
+                        self.skipped.push(SkippedEntry {
+                            path,
+                            reason: SkipReason::Symlink { target },
+                        });
+                        continue;
+                    }
+                }
+            }
+            if let Some(reason) =
+                unorganized(fs.as_ref(), &rules, &config.traversal, &path, &metadata)
+            {
+                self.skipped.push(SkippedEntry { path, reason });
+                continue;
+            }
+            if metadata.is_dir() {
+                self.plan_folder(source, &rules, path, is_link);
+            } else if metadata.len == 0 && !is_link {
+                self.actions.push(PlannedAction {
+                    kind: ActionKind::DeleteEmpty,
+                    source: path,
+                    destination: None,
+                    category: None,
+                    size: 0,
+                });
+            } else if let Some(rule) = self.stale_rule(&source.path, &path, &metadata, is_link) {
+                self.plan_cleanup(rule, path, metadata.len);
+            } else if self.category_of(&path) == Some(Category::Archives) && !is_link {
+                self.plan_archive(path, metadata.len);
+            } else if let Some(category) = self.category_of(&path) {
+                let kind = if is_link {
+                    ActionKind::MoveLink
+                } else {
+                    ActionKind::MoveFile
+                };
+                let wanted = self
+                    .category_dir(category)
+                    .join(path.file_name().unwrap_or_default());
+                self.push_move(kind, path, wanted, category, metadata.len);
+            } else {
+                self.count_unrecognized(&path, metadata.len);
+            }
+        }
+        // Keep the serial organizer's order within a source: deletions, folders, files
+        self.actions[first..].sort_by_key(|a| a.kind.phase());
+    }
+
+    /// Plans a subfolder of a source; `is_link` when reached through a followed symlink.
+    fn plan_folder(
+        &mut self,
+        source: &SourceDir,
+        rules: &IgnoreRules,
+        path: PathBuf,
+        is_link: bool,
+    ) {
+        if source.folder_strategy == FolderStrategy::Skip {
+            return;
+        }
+        let class = self.classifier.classify_with(&path, rules);
+        let reason = match class {
+            // Repositories stay whole whatever the strategy
+            FolderClass::Repository { .. } => {
+                self.plan_folder_move(rules, path, Category::Code, is_link);
+                return;
+            }
+            FolderClass::Project { marker } => SkipReason::ProjectFolder { marker },
+            _ if source.folder_strategy == FolderStrategy::Split => {
+                self.plan_split(&source.path, rules, path, is_link);
+                return;
+            }
+            FolderClass::Category(category) => {
+                self.plan_folder_move(rules, path, category, is_link);
+                return;
+            }
+            FolderClass::Mixed => SkipReason::MixedFolder,
+            FolderClass::Unknown => SkipReason::UnrecognizedFolder,
+        };
+        self.skipped.push(SkippedEntry { path, reason });
+    }
+
+    /// Moves a folder as a unit into the folder of `category`.
+    fn plan_folder_move(
+        &mut self,
+        rules: &IgnoreRules,
+        path: PathBuf,
+        category: Category,
+        is_link: bool,
+    ) {
+        let kind = if is_link {
+            ActionKind::MoveLink
+        } else {
+            ActionKind::MoveFolder
+        };
+        let wanted = self
+            .category_dir(category)
+            .join(path.file_name().unwrap_or_default());
+        let size = self.tree_size(&path, rules);
+        self.push_move(kind, path, wanted, category, size);
+    }
+
+    /// Moves each recognised file of `folder` to its category, keeping its path
+    /// relative to `source_root`, then cleans up the emptied directories unless
+    /// `folder` is a symlink. Entries left alone are reported as skipped.
+    fn plan_split(
+        &mut self,
+        source_root: &Path,
+        rules: &IgnoreRules,
+        folder: PathBuf,
+        is_link: bool,
+    ) {
+        let fs = self.organizer.fs.clone();
+        let mut files = Vec::new();
+        walk_entries(
+            fs.as_ref(),
+            &folder,
+            rules,
+            &self.organizer.config.traversal,
+            |walked| match walked {
+                Walked::Entry { path, metadata } if !metadata.is_dir() => {
+                    files.push((path.to_path_buf(), metadata.len))
+                }
+                Walked::Entry { .. } => {}
+                Walked::Skipped(entry) => self.skipped.push(entry),
+            },
+        );
+        files.sort();
+        for (file, size) in files {
+            let Some(category) = self.category_of(&file) else {
+                self.count_unrecognized(&file, size);
+                continue;
+            };
+            let kind = if fs.symlink_metadata(&file).is_ok_and(|m| m.is_symlink()) {
+                ActionKind::MoveLink
+            } else {
+                ActionKind::MoveFile
+            };
+            let relative = file.strip_prefix(source_root).unwrap_or(&file);
+            let wanted = self.category_dir(category).join(relative);
+            self.push_move(kind, file, wanted, category, size);
+        }
+        if !is_link {
+            self.actions.push(PlannedAction {
+                kind: ActionKind::RemoveEmptyDirs,
+                source: folder,
+                destination: None,
+                category: None,
+                size: 0,
+            });
+        }
+    }
+
+    /// Plans an archive of a source directory. Listing its contents sends it
+    /// to the category dominating them, or to Archives when none does or it
+    /// cannot be read; with `ArchiveConfig::extract` it is unpacked there.
+    fn plan_archive(&mut self, path: PathBuf, size: u64) {
+        let config = &self.organizer.config;
+        let listed = ArchiveFormat::detect(&path)
+            .filter(|_| config.archives.inspect && size <= config.archives.max_inspected_bytes)
+            .and_then(|format| {
+                list_entries(self.organizer.fs.as_ref(), &path, format, &config.archives).ok()
+            });
+        let Some(entries) = listed else {
+            let wanted = self
+                .category_dir(Category::Archives)
+                .join(path.file_name().unwrap_or_default());
+            self.push_move(ActionKind::MoveFile, path, wanted, Category::Archives, size);
+            return;
+        };
+        let category =
+            match classify_profile(&content_profile(&entries, config), &config.classifier) {
+                FolderClass::Category(category) => category,
+                _ => Category::Archives,
+            };
+        if config.archives.extract {
+            let wanted = self.category_dir(category).join(ArchiveFormat::stem(&path));
+            self.push_move(ActionKind::ExtractArchive, path, wanted, category, size);
+        } else {
+            let wanted = self
+                .category_dir(category)
+                .join(path.file_name().unwrap_or_default());
+            self.push_move(ActionKind::MoveFile, path, wanted, category, size);
+        }
+    }
+
+    /// Retention rule a file of `source_dir` falls under; links are never stale.
+    fn stale_rule(
+        &self,
+        source_dir: &Path,
+        path: &Path,
+        metadata: &Metadata,
+        is_link: bool,
+    ) -> Option<&'a RetentionRule> {
+        let organizer = self.organizer;
+        if is_link {
+            return None;
+        }
+        matching_rule(
+            &organizer.config,
+            &organizer.language,
+            source_dir,
+            path,
+            metadata,
+            self.now,
+        )
+    }
+
+    /// Trashes a stale file or moves it to the folder of `rule`.
+    fn plan_cleanup(&mut self, rule: &RetentionRule, path: PathBuf, size: u64) {
+        let (kind, destination) = match (rule.action, &rule.move_to) {
+            (RetentionAction::Move, Some(folder)) => {
+                let wanted = self
+                    .home
+                    .join(folder)
+                    .join(path.file_name().unwrap_or_default());
+                (ActionKind::MoveStale, Some(self.reserve(wanted)))
+            }
+            _ => (ActionKind::TrashStale, None),
+        };
+        self.actions.push(PlannedAction {
+            kind,
+            source: path,
+            destination,
+            category: None,
+            size,
+        });
+    }
+
+    /// Bytes of the files below `folder` that a walk with `rules` reaches.
+    fn tree_size(&self, folder: &Path, rules: &IgnoreRules) -> u64 {
+        let mut size = 0;
+        walk_entries(
+            self.organizer.fs.as_ref(),
+            folder,
+            rules,
+            &self.organizer.config.traversal,
+            |walked| {
+                if let Walked::Entry { metadata, .. } = walked {
+                    if !metadata.is_dir() {
+                        size += metadata.len;
+                    }
+                }
+            },
+        );
+        size
+    }
+
+    fn category_of(&self, path: &Path) -> Option<Category> {
+        path.extension()
+            .and_then(|e| self.organizer.config.get_category(&e.to_string_lossy()))
+    }
+
+    /// Counts a file left alone when its extension has no destination folder.
+    fn count_unrecognized(&mut self, path: &Path, size: u64) {
+        let extension = file_extension(path);
+        if self.organizer.config.get_category(&extension).is_none() {
+            self.unrecognized
+                .entry(extension)
+                .or_default()
+                .add_file(size);
+        }
+    }
+
+    fn category_dir(&self, category: Category) -> PathBuf {
+        self.organizer.resolver.category_dir(self.home, category)
+    }
+
+    /// Hands out `wanted`, or a free name instead with `CollisionPolicy::Rename`.
+    fn reserve(&mut self, wanted: PathBuf) -> PathBuf {
+        let destination = match self.organizer.collision_policy {
+            CollisionPolicy::Rename => self.organizer.free_name(&wanted, &self.reserved),
+            CollisionPolicy::Overwrite | CollisionPolicy::Skip => wanted,
+        };
+        self.reserved.insert(destination.clone());
+        destination
+    }
+
+    fn push_move(
+        &mut self,
+        kind: ActionKind,
+        source: PathBuf,
+        wanted: PathBuf,
+        category: Category,
+        size: u64,
+    ) {
+        let destination = self.reserve(wanted);
+        self.actions.push(PlannedAction {
+            kind,
+            source,
+            destination: Some(destination),
+            category: Some(category),
+            size,
+        });
+    }
+}
+
+/// Builds the rayon pool used to execute plans, falling back to serial execution on failure
+fn build_pool(threads: usize, name: &'static str) -> Option<Arc<ThreadPool>> {
+    match rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.max(1))
+        .thread_name(move |i| format!("rustganizer-{name}-{i}"))
+        .build()
+    {
+        Ok(pool) => Some(Arc::new(pool)),
+        Err(e) => {
+            warn!(
+                "Failed to build {} thread pool, running serially: {}",
+                name, e
+            );
+            None
+        }
+    }
+}
+
+const CANCELLED: &str = "cancelled";
+
+/// State shared by the items of one `execute` call
+struct ExecutionContext {
+    /// Home of the user, whose trash receives unpacked archives and stale files.
+    home: PathBuf,
+    /// Owner for created folders when running as root.
+    owner: Option<Owner>,
+    total: usize,
+    completed: AtomicUsize,
+    /// Destinations that are planned or already taken by renamed items.
+    claimed: Mutex<HashSet<PathBuf>>,
+}
+
+fn unexpected_reply(username: &str) -> Error {
+    Error::Worker {
+        message: format!("unexpected reply from the worker for {username}"),
+    }
+}
+
+fn operation_name(kind: ActionKind) -> &'static str {
+    match kind {
+        ActionKind::MoveFile => "move file",
+        ActionKind::MoveFolder => "move folder",
+        ActionKind::MoveLink => "move symlink",
+        ActionKind::DeleteEmpty => "delete empty file",
+        ActionKind::RemoveEmptyDirs => "remove empty folders",
+        ActionKind::ExtractArchive => "extract archive",
+        ActionKind::TrashStale => "trash stale file",
+        ActionKind::MoveStale => "move stale file",
+    }
+}
+
+fn record_outcome(report: &mut RunReport, outcome: &ItemOutcome) {
+    match &outcome.status {
+        ItemStatus::Moved | ItemStatus::DryRun => {
+            if let Some(category) = outcome.action.category {
+                report.stats.increment(category);
+            } else if outcome.action.kind.is_cleanup() {
+                report.stats.cleaned += 1;
+            }
+        }
+        ItemStatus::Failed { error } => report.errors.push(error.clone()),
+        ItemStatus::Skipped { reason } if reason == CANCELLED => report.cancelled = true,
+        ItemStatus::Deleted | ItemStatus::Skipped { .. } => {}
+    }
+}
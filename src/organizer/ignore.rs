@@ -0,0 +1,198 @@
+//! Gitignore-style rules for entries the organizer must leave alone
+
+use crate::config::Config;
+use crate::platform::filesystem::FileSystem;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::io;
+use std::path::Path;
+use tracing::warn;
+
+/// Name of the per-directory ignore file
+pub const IGNORE_FILE_NAME: &str = ".rustganizerignore";
+
+/// Stack of gitignore layers, from the outermost directory to the innermost.
+///
+/// Like git, a pattern in a deeper ignore file overrides the ones above it,
+/// including `!pattern` whitelists.
+#[derive(Clone, Default)]
+pub struct IgnoreRules {
+    layers: Vec<Gitignore>,
+}
+
+impl IgnoreRules {
+    /// The global patterns from `Config::ignore_patterns`, anchored at `root`.
+    pub fn global(config: &Config, root: &Path) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in &config.ignore_patterns {
+            if let Err(e) = builder.add_line(None, pattern) {
+                warn!("Invalid ignore pattern {:?} in config: {}", pattern, e);
+            }
+        }
+        let mut rules = Self::default();
+        rules.push(builder);
+        rules
+    }
+
+    /// Rules for the entries of `root`: the global patterns plus the ignore file of `root`.
+    pub fn for_root(fs: &dyn FileSystem, config: &Config, root: &Path) -> Self {
+        Self::global(config, root).with_dir(fs, root)
+    }
+
+    /// Returns these rules extended with the ignore file in `dir`, if there is one.
+    pub fn with_dir(&self, fs: &dyn FileSystem, dir: &Path) -> Self {
+        let file = dir.join(IGNORE_FILE_NAME);
+        let Ok(contents) = fs.read_to_string(&file) else {
+            return self.clone();
+        };
+        let mut builder = GitignoreBuilder::new(dir);
+        for line in contents.lines() {
+            if let Err(e) = builder.add_line(Some(file.clone()), line) {
+                warn!("Invalid ignore pattern {:?} in {:?}: {}", line, file, e);
+            }
+        }
+        let mut rules = self.clone();
+        rules.push(builder);
+        rules
+    }
+
+    /// Returns the pattern ignoring `path`, or `None` if the entry may be organized.
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<String> {
+        for layer in self.layers.iter().rev() {
+            if !path.starts_with(layer.path()) || path == layer.path() {
+                continue;
+            }
+            let matched = layer.matched_path_or_any_parents(path, is_dir);
+            if matched.is_whitelist() {
+                return None;
+            }
+            if let Some(glob) = matched.inner().filter(|_| matched.is_ignore()) {
+                return Some(glob.original().to_string());
+            }
+        }
+        None
+    }
+
+    fn push(&mut self, builder: GitignoreBuilder) {
+        match builder.build() {
+            Ok(layer) if !layer.is_empty() => self.layers.push(layer),
+            Ok(_) => {}
+            Err(e) => warn!("Failed to build ignore rules: {}", e),
+        }
+    }
+}
+
+/// Pins an entry of `source_dir` by appending an anchored pattern to its ignore file.
+///
+/// Returns the pattern that was added.
+pub fn pin_entry(fs: &dyn FileSystem, source_dir: &Path, entry: &Path) -> io::Result<String> {
+    let relative = entry.strip_prefix(source_dir).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not inside {}", entry.display(), source_dir.display()),
+        )
+    })?;
+    let mut pattern = String::from("/");
+    for (i, component) in relative.components().enumerate() {
+        if i > 0 {
+            pattern.push('/');
+        }
+        pattern.push_str(&escape_pattern(&component.as_os_str().to_string_lossy()));
+    }
+    if fs.symlink_metadata(entry).is_ok_and(|m| m.is_dir()) {
+        pattern.push('/');
+    }
+
+    let file = source_dir.join(IGNORE_FILE_NAME);
+    let mut contents = match fs.read_to_string(&file) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    if contents.lines().any(|line| line == pattern) {
+        return Ok(pattern);
+    }
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&pattern);
+    contents.push('\n');
+    fs.write(&file, contents.as_bytes())?;
+    Ok(pattern)
+}
+
+/// Escapes gitignore metacharacters so a file name matches only itself
+fn escape_pattern(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for (i, c) in name.chars().enumerate() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') || (i == 0 && matches!(c, '#' | '!')) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::filesystem::MemoryFileSystem;
+
+    #[test]
+    fn test_global_and_directory_rules() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/d/.rustganizerignore", "*.iso\n/keep/\n");
+        fs.add_file("/d/sub/.rustganizerignore", "!important.iso\n");
+        let config = Config {
+            ignore_patterns: vec!["*.lnk".to_string()],
+            ..Config::default()
+        };
+
+        let rules = IgnoreRules::for_root(&fs, &config, Path::new("/d"));
+        assert_eq!(
+            rules.matched(Path::new("/d/app.lnk"), false),
+            Some("*.lnk".to_string())
+        );
+        assert_eq!(
+            rules.matched(Path::new("/d/x/disk.iso"), false),
+            Some("*.iso".to_string())
+        );
+        assert_eq!(
+            rules.matched(Path::new("/d/keep/a.mp3"), false),
+            Some("/keep/".to_string())
+        );
+        assert_eq!(rules.matched(Path::new("/d/song.mp3"), false), None);
+
+        let nested = rules.with_dir(&fs, Path::new("/d/sub"));
+        assert_eq!(
+            nested.matched(Path::new("/d/sub/important.iso"), false),
+            None
+        );
+        assert!(nested
+            .matched(Path::new("/d/sub/other.iso"), false)
+            .is_some());
+    }
+
+    #[test]
+    fn test_pin_entry_appends_escaped_pattern() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/d/.rustganizerignore", "*.tmp");
+        fs.add_file("/d/#notes[1].txt", "x");
+        fs.add_dir("/d/folder");
+
+        let pattern = pin_entry(&fs, Path::new("/d"), Path::new("/d/#notes[1].txt")).unwrap();
+        assert_eq!(pattern, "/\\#notes\\[1\\].txt");
+        pin_entry(&fs, Path::new("/d"), Path::new("/d/folder")).unwrap();
+        pin_entry(&fs, Path::new("/d"), Path::new("/d/folder")).unwrap();
+
+        let contents = fs
+            .read_to_string(Path::new("/d/.rustganizerignore"))
+            .unwrap();
+        assert_eq!(contents, "*.tmp\n/\\#notes\\[1\\].txt\n/folder/\n");
+
+        let rules = IgnoreRules::for_root(&fs, &Config::default(), Path::new("/d"));
+        assert!(rules
+            .matched(Path::new("/d/#notes[1].txt"), false)
+            .is_some());
+        assert!(rules.matched(Path::new("/d/folder"), true).is_some());
+    }
+}
@@ -1,4 +1,14 @@
 pub mod analyzer;
+pub mod archive;
 pub mod async_analyzer;
+pub mod classifier;
+pub mod engine;
+pub mod ignore;
 pub mod mover;
+pub mod resolver;
+pub mod retention;
+pub mod suggestions;
+pub mod trash;
 pub mod types;
+pub mod walk;
+pub mod worker;
@@ -1,236 +1,176 @@
 // File moving and organizing logic will go here.
 
 use crate::config::Config;
-use crate::organizer::analyzer::{analyze_folder, get_majority_type};
-use crate::organizer::types::FileStats;
-#[cfg(target_os = "macos")]
-use crate::platform::user::MacUserProvider;
-#[cfg(all(unix, not(target_os = "macos")))]
-use crate::platform::user::UnixUserProvider;
+use crate::error::Error;
+use crate::logging::log_file_move;
+use crate::organizer::engine::Organizer;
+use crate::organizer::types::{CollisionPolicy, FileStats};
+use crate::platform::filesystem::{FileSystem, Metadata, Owner};
 use crate::platform::user::UserProvider;
-#[cfg(target_os = "windows")]
-use crate::platform::user::WindowsUserProvider;
-use std::collections::HashMap;
-use std::fs;
 use std::io;
 use std::path::Path;
-use std::sync::{Arc, Mutex};
-use std::thread;
+use std::sync::Arc;
 
-fn move_dir_recursive(src: &Path, dst: &Path) -> io::Result<()> {
-    if !dst.exists() {
-        return fs::rename(src, dst);
+/// Permission bits of folders created in another user's home
+const CREATED_DIR_MODE: u32 = 0o755;
+
+/// Creates `dir` and its missing parents.
+///
+/// With an `owner`, every folder created here is given to it with
+/// `CREATED_DIR_MODE`, so running as root leaves no root-owned folders behind.
+pub(crate) fn create_dirs(fs: &dyn FileSystem, dir: &Path, owner: Option<Owner>) -> io::Result<()> {
+    let missing: Vec<&Path> = dir.ancestors().take_while(|a| !fs.exists(a)).collect();
+    if missing.is_empty() {
+        return Ok(());
     }
-    // If dst exists, move all files and folders from src into dst
-    for entry in fs::read_dir(src)? {
-        let entry = entry?;
-        let src_path = entry.path();
-        let dst_path = dst.join(entry.file_name());
-        if src_path.is_dir() {
-            move_dir_recursive(&src_path, &dst_path)?;
-        } else {
-            fs::rename(&src_path, &dst_path)?;
+    fs.create_dir_all(dir)?;
+    if let Some(owner) = owner {
+        for created in missing.into_iter().rev() {
+            fs.set_owner(created, owner)?;
+            fs.set_mode(created, CREATED_DIR_MODE)?;
         }
     }
-    fs::remove_dir(src)?;
     Ok(())
 }
 
-/// Organizes files for a user, supporting both English and Spanish Windows folder names.
-pub fn organize_files(username: &str, lang: &str, config: &Config) -> Result<FileStats, String> {
-    let username = username.trim();
-    if username.is_empty() {
-        return Err(config.get_error_message(lang, "empty_username", username));
-    }
-    #[cfg(target_os = "windows")]
-    let user_provider = WindowsUserProvider;
-    #[cfg(target_os = "macos")]
-    let user_provider = MacUserProvider;
-    #[cfg(all(unix, not(target_os = "macos")))]
-    let user_provider = UnixUserProvider;
-    let user_dir_path = match user_provider.user_home(username) {
-        Some(path) => path,
-        None => {
-            return Err(config.get_error_message(lang, "user_not_found", username));
-        }
-    };
-    let user_dir_path = user_dir_path.to_string_lossy();
-    let music_count = Arc::new(Mutex::new(0));
-    let video_count = Arc::new(Mutex::new(0));
-    let images_count = Arc::new(Mutex::new(0));
-    let docs_count = Arc::new(Mutex::new(0));
-    let download_dir = format!(
-        "{}/{}",
-        user_dir_path,
-        config.get_localized_dir(lang, "Downloads")
-    );
-    let desktop_dir = format!(
-        "{}/{}",
-        user_dir_path,
-        config.get_localized_dir(lang, "Desktop")
-    );
-    let music_dir = format!(
-        "{}/{}",
-        user_dir_path,
-        config.get_localized_dir(lang, "Music")
-    );
-    let videos_dir = format!(
-        "{}/{}",
-        user_dir_path,
-        config.get_localized_dir(lang, "Videos")
-    );
-    let images_dir = format!(
-        "{}/{}",
-        user_dir_path,
-        config.get_localized_dir(lang, "Pictures")
-    );
-    let docs_files_dir = format!(
-        "{}/{}",
-        user_dir_path,
-        config.get_localized_dir(lang, "Documents")
-    );
-    for dir in [
-        &music_dir,
-        &videos_dir,
-        &images_dir,
-        &docs_files_dir,
-        &desktop_dir,
-    ] {
-        if !Path::new(dir).exists() {
-            fs::create_dir_all(dir).unwrap_or_else(|e| {
-                eprintln!("Failed to create directory {dir}: {e}");
-            });
+/// Gives `dst` the owner and permission bits of the entry it was copied from.
+fn copy_attributes(fs: &dyn FileSystem, from: &Metadata, dst: &Path) -> io::Result<()> {
+    if let Some(owner) = from.owner {
+        if fs.symlink_metadata(dst)?.owner != Some(owner) {
+            fs.set_owner(dst, owner)?;
         }
     }
-    let dirs = vec![download_dir.to_string(), desktop_dir.to_string()];
-    let mut handles = vec![];
-    for dir in dirs {
-        let music_dir = music_dir.to_string();
-        let videos_dir = videos_dir.to_string();
-        let images_dir = images_dir.to_string();
-        let docs_files_dir = docs_files_dir.to_string();
-        let music_count = Arc::clone(&music_count);
-        let video_count = Arc::clone(&video_count);
-        let images_count = Arc::clone(&images_count);
-        let docs_count = Arc::clone(&docs_count);
-        let is_desktop = dir.contains(&config.get_localized_dir(lang, "Desktop"));
-        let config = config.clone();
-        let handle = thread::spawn(move || {
-            let mut folders_to_process = Vec::new();
-            let mut files_to_process = Vec::new();
-            let mut processed_paths = HashMap::new();
-            if let Ok(entries) = fs::read_dir(&dir) {
-                for entry in entries.filter_map(|e| e.ok()) {
-                    let path = entry.path();
-                    if path.is_dir() {
-                        if !is_desktop {
-                            folders_to_process.push(path);
-                        }
-                    } else if path.is_file() {
-                        if let Ok(metadata) = fs::metadata(&path) {
-                            if metadata.len() == 0 {
-                                if let Err(e) = fs::remove_file(&path) {
-                                    eprintln!("Failed to remove empty file {path:?}: {e}");
-                                }
-                                continue;
-                            }
-                        }
-                        files_to_process.push(path);
-                    }
-                }
+    match from.mode {
+        Some(mode) if !from.is_symlink() => fs.set_mode(dst, mode),
+        _ => Ok(()),
+    }
+}
+
+/// Moves a folder, merging its contents into `dst` when that already exists.
+pub(crate) fn move_dir_recursive(fs: &dyn FileSystem, src: &Path, dst: &Path) -> io::Result<()> {
+    if !fs.exists(dst) {
+        match fs.rename(src, dst) {
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                fs.create_dir_all(dst)?;
+                copy_attributes(fs, &fs.symlink_metadata(src)?, dst)?;
             }
-            for folder_path in &folders_to_process {
-                let stats = analyze_folder(folder_path, &config);
-                if let Some(majority_type) = get_majority_type(&stats) {
-                    let target_dir = match majority_type {
-                        "music" => &music_dir,
-                        "video" => &videos_dir,
-                        "image" => &images_dir,
-                        "docs" => &docs_files_dir,
-                        _ => continue,
-                    };
-                    if let Some(folder_name) = folder_path.file_name() {
-                        let target_path = Path::new(target_dir).join(folder_name);
-                        if let Err(e) = move_dir_recursive(folder_path, &target_path) {
-                            eprintln!("Error moving folder {folder_path:?}: {e}");
-                        } else {
-                            processed_paths.insert(
-                                folder_path.to_str().unwrap().to_string(),
-                                target_path.to_str().unwrap().to_string(),
-                            );
-                            let count = match majority_type {
-                                "music" => &music_count,
-                                "video" => &video_count,
-                                "image" => &images_count,
-                                "docs" => &docs_count,
-                                _ => continue,
-                            };
-                            let mut count = count.lock().unwrap();
-                            *count += 1;
-                        }
-                    }
-                }
+            result => {
+                log_file_move(src, dst, result.is_ok());
+                return result;
             }
-            for file_path in &files_to_process {
-                if let Some(extension) = file_path.extension() {
-                    let ext = extension.to_str().unwrap().to_lowercase();
-                    let file_extensions = config.get_file_extensions();
-                    let target_dir = if file_extensions.music.contains(&ext.as_str()) {
-                        &music_dir
-                    } else if file_extensions.videos.contains(&ext.as_str()) {
-                        &videos_dir
-                    } else if file_extensions.images.contains(&ext.as_str()) {
-                        &images_dir
-                    } else if file_extensions.docs.contains(&ext.as_str()) {
-                        &docs_files_dir
-                    } else {
-                        continue;
-                    };
-                    if let Some(file_name) = file_path.file_name() {
-                        let target_path = Path::new(target_dir).join(file_name);
-                        if let Err(e) = fs::rename(file_path, &target_path) {
-                            eprintln!("Error moving file {file_path:?}: {e}");
-                        } else {
-                            processed_paths.insert(
-                                file_path.to_str().unwrap().to_string(),
-                                target_path.to_str().unwrap().to_string(),
-                            );
-                            let count = if file_extensions.music.contains(&ext.as_str()) {
-                                &music_count
-                            } else if file_extensions.videos.contains(&ext.as_str()) {
-                                &video_count
-                            } else if file_extensions.images.contains(&ext.as_str()) {
-                                &images_count
-                            } else if file_extensions.docs.contains(&ext.as_str()) {
-                                &docs_count
-                            } else {
-                                continue;
-                            };
-                            let mut count = count.lock().unwrap();
-                            *count += 1;
-                        }
-                    }
-                }
+        }
+    }
+    // If dst exists, move all files and folders from src into dst
+    for src_path in fs.read_dir(src)? {
+        let Some(file_name) = src_path.file_name() else {
+            continue;
+        };
+        let dst_path = dst.join(file_name);
+        if fs.symlink_metadata(&src_path)?.is_dir() {
+            move_dir_recursive(fs, &src_path, &dst_path)?;
+        } else {
+            move_file(fs, &src_path, &dst_path)?;
+        }
+    }
+    fs.remove_dir(src)?;
+    Ok(())
+}
+
+/// Renames a file or symlink, falling back to copy and delete when crossing devices.
+///
+/// Symlinks are recreated at the destination rather than copied through, and
+/// the copy keeps the owner and permission bits of the original.
+pub(crate) fn move_file(fs: &dyn FileSystem, src: &Path, dst: &Path) -> io::Result<()> {
+    let result = rename_or_copy(fs, src, dst);
+    log_file_move(src, dst, result.is_ok());
+    result
+}
+
+fn rename_or_copy(fs: &dyn FileSystem, src: &Path, dst: &Path) -> io::Result<()> {
+    match fs.rename(src, dst) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            let metadata = fs.symlink_metadata(src)?;
+            if metadata.is_symlink() {
+                fs.symlink(&fs.read_link(src)?, dst)?;
+            } else {
+                fs.copy(src, dst)?;
             }
-        });
-        handles.push(handle);
+            copy_attributes(fs, &metadata, dst)?;
+            fs.remove_file(src)
+        }
+        result => result,
+    }
+}
+
+/// Removes `dir` and every directory below it that contains no files.
+///
+/// Returns whether `dir` itself was removed.
+pub(crate) fn remove_empty_dirs(fs: &dyn FileSystem, dir: &Path) -> io::Result<bool> {
+    let mut empty = true;
+    for child in fs.read_dir(dir)? {
+        if fs.symlink_metadata(&child)?.is_dir() {
+            empty &= remove_empty_dirs(fs, &child)?;
+        } else {
+            empty = false;
+        }
     }
-    for handle in handles {
-        handle.join().map_err(|_| "Thread panicked".to_string())?;
+    if empty {
+        fs.remove_dir(dir)?;
     }
-    let result = {
-        let music_count = music_count.lock().unwrap();
-        let video_count = video_count.lock().unwrap();
-        let images_count = images_count.lock().unwrap();
-        let docs_count = docs_count.lock().unwrap();
-        FileStats {
-            music: *music_count,
-            videos: *video_count,
-            images: *images_count,
-            docs: *docs_count,
+    Ok(empty)
+}
+
+/// Organizes files for a user, supporting both English and Spanish Windows folder names.
+///
+/// Compatibility wrapper around [`Organizer`] that keeps the historical
+/// behaviour of replacing existing files and merging existing folders.
+pub fn organize_files(username: &str, lang: &str, config: &Config) -> Result<FileStats, String> {
+    Organizer::builder()
+        .config(config.clone())
+        .language(lang)
+        .collision_policy(CollisionPolicy::Overwrite)
+        .build()
+        .run(username)
+        .map(|report| report.stats)
+        .map_err(|e| localized_error(config, lang, &e))
+}
+
+/// Organizes files for a user using an injected user provider and filesystem.
+///
+/// The organizer runs on clones of both, so a clone of `fs` must see the
+/// same files, as those of `MemoryFileSystem` do.
+pub fn organize_files_with<U, F>(
+    username: &str,
+    lang: &str,
+    config: &Config,
+    user_provider: &U,
+    fs: &F,
+) -> Result<FileStats, String>
+where
+    U: UserProvider + Clone + 'static,
+    F: FileSystem + Clone + 'static,
+{
+    Organizer::builder()
+        .config(config.clone())
+        .language(lang)
+        .user_provider(Arc::new(user_provider.clone()))
+        .filesystem(Arc::new(fs.clone()))
+        .collision_policy(CollisionPolicy::Overwrite)
+        .build()
+        .run(username)
+        .map(|report| report.stats)
+        .map_err(|e| localized_error(config, lang, &e))
+}
+
+/// Renders an organizer error with the localized messages from `Config`.
+pub fn localized_error(config: &Config, lang: &str, error: &Error) -> String {
+    match error {
+        Error::EmptyUsername => config.get_error_message(lang, "empty_username", ""),
+        Error::UserNotFound { username } => {
+            config.get_error_message(lang, "user_not_found", username)
         }
-    };
-    Ok(result)
+        other => other.to_string(),
+    }
 }
 
 #[cfg(test)]
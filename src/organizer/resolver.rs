@@ -0,0 +1,57 @@
+//! Resolution of source and destination folders inside a user's home
+
+use crate::config::Config;
+use crate::organizer::types::{Category, FolderStrategy};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A directory the organizer collects files from
+#[derive(Debug, Clone)]
+pub struct SourceDir {
+    pub path: PathBuf,
+    /// How subfolders are handled.
+    pub folder_strategy: FolderStrategy,
+}
+
+/// Maps a user's home directory to the folders the organizer reads and writes
+pub trait FolderResolver: Send + Sync {
+    /// Directories whose entries get organized.
+    fn source_dirs(&self, home: &Path) -> Vec<SourceDir>;
+    /// Destination directory for a category.
+    fn category_dir(&self, home: &Path, category: Category) -> PathBuf;
+}
+
+/// Resolves folders by their localized names, e.g. `Descargas` for Spanish
+pub struct LocalizedFolderResolver {
+    config: Arc<Config>,
+    lang: String,
+}
+
+impl LocalizedFolderResolver {
+    pub fn new(config: Arc<Config>, lang: impl Into<String>) -> Self {
+        Self {
+            config,
+            lang: lang.into(),
+        }
+    }
+}
+
+impl FolderResolver for LocalizedFolderResolver {
+    fn source_dirs(&self, home: &Path) -> Vec<SourceDir> {
+        self.config
+            .sources
+            .iter()
+            .map(|source| SourceDir {
+                path: home.join(self.config.get_localized_dir(&self.lang, &source.name)),
+                folder_strategy: source.folder_strategy,
+            })
+            .collect()
+    }
+
+    fn category_dir(&self, home: &Path, category: Category) -> PathBuf {
+        home.join(
+            self.config
+                .get_localized_dir(&self.lang, category.logical_dir()),
+        )
+    }
+}
@@ -0,0 +1,111 @@
+//! Age-based cleanup of stale files in source directories
+//!
+//! Retention rules are checked for the files at the top of a source
+//! directory before they are classified; a file one of them matches is
+//! trashed or moved to the rule's folder instead of being organized.
+
+use crate::config::{Config, RetentionRule};
+use crate::organizer::analyzer::file_extension;
+use crate::platform::filesystem::Metadata;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// First rule of `config` that `path`, a file directly inside `source_dir`,
+/// falls under at `now`. Source names are localized in `language`.
+pub fn matching_rule<'a>(
+    config: &'a Config,
+    language: &str,
+    source_dir: &Path,
+    path: &Path,
+    metadata: &Metadata,
+    now: SystemTime,
+) -> Option<&'a RetentionRule> {
+    // Files without a modification time are never considered stale
+    let age = now.duration_since(metadata.modified?).ok()?;
+    let extension = file_extension(path);
+    let category = config
+        .get_file_category(&extension)
+        .map(|file_type| file_type.category);
+    config.retention.iter().find(|rule| {
+        let in_source = rule.sources.iter().any(|name| {
+            let localized = config.get_localized_dir(language, name);
+            source_dir.file_name() == Some(localized.as_ref())
+        });
+        age >= Duration::from_secs(rule.older_than_days.saturating_mul(SECONDS_PER_DAY))
+            && (rule.extensions.is_empty() || rule.extensions.contains(&extension))
+            && (rule.categories.is_empty()
+                || category.is_some_and(|c| rule.categories.iter().any(|r| r == c)))
+            && (rule.sources.is_empty() || in_source)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::organizer::types::RetentionAction;
+    use crate::platform::filesystem::FileKind;
+
+    fn days_old(days: u64, now: SystemTime) -> Metadata {
+        Metadata {
+            kind: FileKind::File,
+            len: 1,
+            modified: Some(now - Duration::from_secs(days * SECONDS_PER_DAY)),
+            dev: 0,
+            owner: None,
+            mode: None,
+        }
+    }
+
+    #[test]
+    fn test_first_rule_matching_age_and_every_filter_wins() {
+        let config = Config {
+            retention: vec![
+                RetentionRule {
+                    older_than_days: 30,
+                    extensions: Vec::new(),
+                    categories: vec!["installers".to_string()],
+                    sources: Vec::new(),
+                    action: RetentionAction::Trash,
+                    move_to: None,
+                },
+                RetentionRule {
+                    older_than_days: 90,
+                    extensions: Vec::new(),
+                    categories: Vec::new(),
+                    sources: vec!["Downloads".to_string()],
+                    action: RetentionAction::Move,
+                    move_to: Some("Old downloads".into()),
+                },
+            ],
+            ..Config::default()
+        };
+        config.validate().unwrap();
+        let now = SystemTime::now();
+        let downloads = Path::new("/home/a/Descargas");
+        let desktop = Path::new("/home/a/Escritorio");
+        let rule = |dir: &Path, name: &str, days| {
+            let path = dir.join(name);
+            matching_rule(&config, "es", dir, &path, &days_old(days, now), now)
+                .map(|rule| rule.action)
+        };
+
+        assert_eq!(rule(desktop, "setup.deb", 31), Some(RetentionAction::Trash));
+        assert_eq!(
+            rule(desktop, "tool.AppImage", 31),
+            Some(RetentionAction::Trash)
+        );
+        assert_eq!(rule(desktop, "setup.deb", 29), None);
+        assert_eq!(rule(desktop, "song.mp3", 120), None);
+        assert_eq!(
+            rule(downloads, "song.mp3", 120),
+            Some(RetentionAction::Move)
+        );
+        assert_eq!(
+            rule(downloads, "setup.deb", 120),
+            Some(RetentionAction::Trash)
+        );
+        assert_eq!(rule(downloads, "song.mp3", 89), None);
+    }
+}
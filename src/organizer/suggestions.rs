@@ -0,0 +1,138 @@
+//! Category suggestions for extensions the configuration does not know
+
+use crate::catalog;
+use crate::config::Config;
+use crate::organizer::types::{Category, ExtensionTally};
+use std::collections::BTreeMap;
+
+/// Category an unrecognised extension most likely belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub extension: String,
+    pub category: Category,
+    /// Files and bytes of the extension that were left alone.
+    pub tally: ExtensionTally,
+}
+
+/// Folders suggested for the catalog categories that have none of their own:
+/// installers are packaged software like archives, fonts go with documents.
+const NO_DESTINATION: [(&str, Category); 2] = [
+    ("installers", Category::Archives),
+    ("fonts", Category::Docs),
+];
+
+/// Organizer category the built-in catalog gives `extension`, if any.
+pub fn suggest_category(extension: &str) -> Option<Category> {
+    let entry = catalog::lookup(&extension.to_lowercase())?;
+    Category::from_name(entry.category).or_else(|| {
+        NO_DESTINATION
+            .iter()
+            .find(|(name, _)| *name == entry.category)
+            .map(|(_, category)| *category)
+    })
+}
+
+/// The `limit` unrecognised extensions with the most bytes, largest first.
+pub fn top_unrecognized(
+    unrecognized: &BTreeMap<String, ExtensionTally>,
+    limit: usize,
+) -> Vec<(&str, ExtensionTally)> {
+    let mut top: Vec<(&str, ExtensionTally)> = unrecognized
+        .iter()
+        .map(|(extension, tally)| (extension.as_str(), *tally))
+        .collect();
+    // Stable sort: ties stay in alphabetical order
+    top.sort_by_key(|(_, tally)| std::cmp::Reverse((tally.bytes, tally.files)));
+    top.truncate(limit);
+    top
+}
+
+/// Suggested mappings for `unrecognized`, largest first, leaving out what
+/// `config` has learned in the meantime.
+pub fn suggestions(
+    unrecognized: &BTreeMap<String, ExtensionTally>,
+    config: &Config,
+) -> Vec<Suggestion> {
+    top_unrecognized(unrecognized, usize::MAX)
+        .into_iter()
+        .filter(|(extension, _)| config.get_category(extension).is_none())
+        .filter_map(|(extension, tally)| {
+            Some(Suggestion {
+                extension: extension.to_string(),
+                category: suggest_category(extension)?,
+                tally,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tally(files: usize, bytes: u64) -> ExtensionTally {
+        ExtensionTally { files, bytes }
+    }
+
+    #[test]
+    fn test_suggestions_come_from_the_catalog() {
+        assert_eq!(suggest_category("M4A"), Some(Category::Music));
+        assert_eq!(suggest_category("webm"), Some(Category::Videos));
+        assert_eq!(suggest_category("heic"), Some(Category::Images));
+        assert_eq!(suggest_category("xlsx"), Some(Category::Docs));
+        assert_eq!(suggest_category("deb"), Some(Category::Archives));
+        assert_eq!(suggest_category("woff2"), Some(Category::Docs));
+        assert_eq!(suggest_category("xyz"), None);
+
+        let unrecognized = BTreeMap::from([
+            ("docx".to_string(), tally(2, 100)),
+            ("webp".to_string(), tally(5, 900)),
+            ("xyz".to_string(), tally(9, 5000)),
+            ("".to_string(), tally(1, 10)),
+        ]);
+        // With the catalog on, webp and docx would be recognised in the first place
+        let mut config = Config::default();
+        config.file_extensions.catalog = false;
+        let extensions: Vec<(String, Category)> = suggestions(&unrecognized, &config)
+            .into_iter()
+            .map(|s| (s.extension, s.category))
+            .collect();
+        assert_eq!(
+            extensions,
+            [
+                ("webp".to_string(), Category::Images),
+                ("docx".to_string(), Category::Docs)
+            ]
+        );
+
+        assert!(config.add_extension(Category::Images, ".WEBP"));
+        assert!(!config.add_extension(Category::Docs, "webp"));
+        assert_eq!(config.get_category("webp"), Some(Category::Images));
+        assert_eq!(suggestions(&unrecognized, &config).len(), 1);
+        assert_eq!(top_unrecognized(&unrecognized, 2)[0].0, "xyz");
+    }
+
+    #[test]
+    fn test_catalog_entries_without_destination_are_suggested() {
+        let unrecognized = BTreeMap::from([
+            ("deb".to_string(), tally(1, 700)),
+            ("ttf".to_string(), tally(3, 300)),
+            ("xyz".to_string(), tally(9, 5000)),
+        ]);
+        let mut config = Config::default();
+        let extensions: Vec<(String, Category)> = suggestions(&unrecognized, &config)
+            .into_iter()
+            .map(|s| (s.extension, s.category))
+            .collect();
+        assert_eq!(
+            extensions,
+            [
+                ("deb".to_string(), Category::Archives),
+                ("ttf".to_string(), Category::Docs)
+            ]
+        );
+
+        assert!(config.add_extension(Category::Archives, "deb"));
+        assert_eq!(suggestions(&unrecognized, &config).len(), 1);
+    }
+}
@@ -0,0 +1,105 @@
+//! Moving files to the user's trash instead of deleting them
+//!
+//! Follows the freedesktop.org trash specification: the file goes to
+//! `~/.local/share/Trash/files` and a `.trashinfo` file in `info` records
+//! where it came from and when, so file managers can restore it.
+
+use crate::organizer::mover::{create_dirs, move_file};
+use crate::platform::filesystem::{FileSystem, Owner};
+use chrono::Local;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Trash directory of the user whose home is `home`.
+pub fn trash_dir(home: &Path) -> PathBuf {
+    home.join(".local").join("share").join("Trash")
+}
+
+/// Moves `path` to the trash in `home`, returning where it ended up.
+pub(crate) fn move_to_trash(
+    fs: &dyn FileSystem,
+    home: &Path,
+    path: &Path,
+    owner: Option<Owner>,
+) -> io::Result<PathBuf> {
+    let trash = trash_dir(home);
+    let (files, info) = (trash.join("files"), trash.join("info"));
+    create_dirs(fs, &files, owner)?;
+    create_dirs(fs, &info, owner)?;
+
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "nothing to trash"))?
+        .to_string_lossy()
+        .into_owned();
+    let (trashed, info_file) = (1..)
+        .map(|n| match n {
+            1 => name.clone(),
+            n => format!("{name}.{n}"),
+        })
+        .map(|name| (files.join(&name), info.join(format!("{name}.trashinfo"))))
+        .find(|(trashed, info_file)| {
+            fs.symlink_metadata(trashed).is_err() && fs.symlink_metadata(info_file).is_err()
+        })
+        .expect("unbounded candidate iterator");
+
+    // The spec wants local time without a zone
+    let deleted = Local::now().format("%Y-%m-%dT%H:%M:%S");
+    let contents = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={deleted}\n",
+        percent_encode(&path.to_string_lossy())
+    );
+    fs.write(&info_file, contents.as_bytes())?;
+    if let Some(owner) = owner {
+        fs.set_owner(&info_file, owner)?;
+    }
+    if let Err(e) = move_file(fs, path, &trashed) {
+        let _ = fs.remove_file(&info_file);
+        return Err(e);
+    }
+    Ok(trashed)
+}
+
+/// Escapes a path as the URL the `Path` key expects, keeping the slashes.
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::filesystem::MemoryFileSystem;
+
+    #[test]
+    fn test_trashed_files_keep_their_origin() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/home/a/Downloads/old song.zip", "one");
+        fs.add_file("/home/a/Desktop/old song.zip", "two");
+        let home = Path::new("/home/a");
+
+        let first =
+            move_to_trash(&fs, home, Path::new("/home/a/Downloads/old song.zip"), None).unwrap();
+        let second =
+            move_to_trash(&fs, home, Path::new("/home/a/Desktop/old song.zip"), None).unwrap();
+
+        let trash = trash_dir(home);
+        assert_eq!(first, trash.join("files/old song.zip"));
+        assert_eq!(second, trash.join("files/old song.zip.2"));
+        assert_eq!(fs.contents(&second).unwrap(), b"two");
+        let info = fs
+            .read_to_string(&trash.join("info/old song.zip.2.trashinfo"))
+            .unwrap();
+        assert!(info.starts_with("[Trash Info]\nPath=/home/a/Desktop/old%20song.zip\n"));
+        assert!(info.contains("DeletionDate=20"));
+        assert!(!fs.exists(Path::new("/home/a/Desktop/old song.zip")));
+    }
+}
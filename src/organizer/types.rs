@@ -1,13 +1,25 @@
 // Structs and enums for organizer module.
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct FileStats {
     pub music: usize,
     pub videos: usize,
     pub images: usize,
     pub docs: usize,
+    #[serde(default)]
+    pub archives: usize,
+    #[serde(default)]
+    pub code: usize,
+    /// Stale files trashed or moved away by retention rules, not part of `total`
+    #[serde(default)]
+    pub cleaned: usize,
 }
 
 impl FileStats {
@@ -22,5 +34,435 @@ impl FileStats {
         self.videos += other.videos;
         self.images += other.images;
         self.docs += other.docs;
+        self.archives += other.archives;
+        self.code += other.code;
+        self.cleaned += other.cleaned;
+    }
+
+    /// Returns the count for a category.
+    pub fn get(&self, category: Category) -> usize {
+        match category {
+            Category::Music => self.music,
+            Category::Videos => self.videos,
+            Category::Images => self.images,
+            Category::Docs => self.docs,
+            Category::Archives => self.archives,
+            Category::Code => self.code,
+        }
+    }
+
+    /// Increments the count for a category by one.
+    pub fn increment(&mut self, category: Category) {
+        match category {
+            Category::Music => self.music += 1,
+            Category::Videos => self.videos += 1,
+            Category::Images => self.images += 1,
+            Category::Docs => self.docs += 1,
+            Category::Archives => self.archives += 1,
+            Category::Code => self.code += 1,
+        }
+    }
+
+    /// Sum of all category counts.
+    pub fn total(&self) -> usize {
+        self.music + self.videos + self.images + self.docs + self.archives + self.code
+    }
+}
+
+/// Number and total size of files sharing something, such as an extension
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtensionTally {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl ExtensionTally {
+    /// Counts one more file of `bytes`.
+    pub fn add_file(&mut self, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+    }
+
+    pub fn add(&mut self, other: &ExtensionTally) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Category a file or folder is organized into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Music,
+    Videos,
+    Images,
+    Docs,
+    Archives,
+    /// Source files and whole project checkouts
+    Code,
+}
+
+impl Category {
+    pub const ALL: [Category; 6] = [
+        Category::Music,
+        Category::Videos,
+        Category::Images,
+        Category::Docs,
+        Category::Archives,
+        Category::Code,
+    ];
+
+    /// Short name as used by `get_majority_type` and `Config::get_file_category`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Music => "music",
+            Category::Videos => "video",
+            Category::Images => "image",
+            Category::Docs => "docs",
+            Category::Archives => "archives",
+            Category::Code => "code",
+        }
+    }
+
+    /// Logical directory name, localized through `Config::get_localized_dir`.
+    pub fn logical_dir(&self) -> &'static str {
+        match self {
+            Category::Music => "Music",
+            Category::Videos => "Videos",
+            Category::Images => "Pictures",
+            Category::Docs => "Documents",
+            Category::Archives => "Archives",
+            Category::Code => "Projects",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Category::ALL.into_iter().find(|c| c.as_str() == name)
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What to do when the destination of a move already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    /// Replace existing files and merge into existing folders.
+    Overwrite,
+    /// Pick a free name such as `report (1).pdf`.
+    #[default]
+    Rename,
+    /// Leave the source where it is.
+    Skip,
+}
+
+/// How subfolders of a source directory are organized
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FolderStrategy {
+    /// Move the folder as a unit when one category dominates it.
+    #[default]
+    MoveWhole,
+    /// Move every recognised file to its own category, keeping its relative path.
+    Split,
+    /// Leave folders alone.
+    Skip,
+}
+
+/// What to do with symlinks found in source directories
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymlinkPolicy {
+    /// Leave links where they are and report them.
+    #[default]
+    Skip,
+    /// Organize the link itself by its own name, never touching the target.
+    MoveLink,
+    /// Look through links when analyzing, guarding against loops; the link is what moves.
+    Follow,
+}
+
+/// What a retention rule does with a stale file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionAction {
+    /// Move it to the user's trash.
+    Trash,
+    /// Move it to the rule's `move_to` folder.
+    Move,
+}
+
+/// Kind of a planned filesystem operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    MoveFile,
+    MoveFolder,
+    /// Move a symlink itself, leaving its target untouched.
+    MoveLink,
+    DeleteEmpty,
+    /// Remove the directories left empty below a split folder.
+    RemoveEmptyDirs,
+    /// Unpack an archive into a folder at the destination, then trash it.
+    ExtractArchive,
+    /// Move a file a retention rule found stale to the trash.
+    TrashStale,
+    /// Move a file a retention rule found stale to the rule's folder.
+    MoveStale,
+}
+
+impl ActionKind {
+    /// Execution phase; all actions of a phase finish before the next one starts.
+    pub fn phase(&self) -> u8 {
+        match self {
+            ActionKind::DeleteEmpty => 0,
+            ActionKind::MoveFolder => 1,
+            ActionKind::MoveFile
+            | ActionKind::MoveLink
+            | ActionKind::ExtractArchive
+            | ActionKind::TrashStale
+            | ActionKind::MoveStale => 2,
+            ActionKind::RemoveEmptyDirs => 3,
+        }
+    }
+
+    /// Whether the action carries out a retention rule.
+    pub fn is_cleanup(&self) -> bool {
+        matches!(self, ActionKind::TrashStale | ActionKind::MoveStale)
+    }
+}
+
+/// A single operation computed by `Organizer::plan`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedAction {
+    pub kind: ActionKind,
+    pub source: PathBuf,
+    pub destination: Option<PathBuf>,
+    pub category: Option<Category>,
+    /// Bytes moved, all files below it for a folder.
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// Why an entry of a source directory is left where it is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The folder contains a project marker such as `.git`.
+    ProjectFolder { marker: String },
+    /// No category dominates the folder.
+    MixedFolder,
+    /// The folder holds too few recognised files.
+    UnrecognizedFolder,
+    /// The entry matches an ignore pattern.
+    Ignored { pattern: String },
+    /// A symlink that is not organized, or whose target is missing.
+    Symlink { target: PathBuf },
+    /// A followed symlink leading back to a directory already visited.
+    SymlinkLoop { target: PathBuf },
+    /// A FIFO, socket or device file.
+    SpecialFile { kind: String },
+    /// A directory below `TraversalConfig::max_depth`, not looked into.
+    DepthLimit,
+    /// A directory on another filesystem, not looked into.
+    OtherFilesystem,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::ProjectFolder { marker } => write!(f, "project folder ({marker})"),
+            SkipReason::MixedFolder => f.write_str("mixed folder"),
+            SkipReason::UnrecognizedFolder => f.write_str("unrecognized folder"),
+            SkipReason::Ignored { pattern } => write!(f, "ignored by `{pattern}`"),
+            SkipReason::Symlink { target } => write!(f, "symlink to {}", target.display()),
+            SkipReason::SymlinkLoop { target } => {
+                write!(f, "symlink loop through {}", target.display())
+            }
+            SkipReason::SpecialFile { kind } => f.write_str(kind),
+            SkipReason::DepthLimit => f.write_str("too deep"),
+            SkipReason::OtherFilesystem => f.write_str("on another filesystem"),
+        }
+    }
+}
+
+/// An entry the plan deliberately leaves alone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedEntry {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// Everything the organizer intends to do for one user
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Plan {
+    pub username: String,
+    pub home: PathBuf,
+    pub actions: Vec<PlannedAction>,
+    pub skipped: Vec<SkippedEntry>,
+    /// Files left alone because no category knows their extension, by
+    /// lowercase extension; `""` for files without one.
+    #[serde(default)]
+    pub unrecognized: BTreeMap<String, ExtensionTally>,
+}
+
+/// Result of executing a single planned action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ItemStatus {
+    Moved,
+    Deleted,
+    DryRun,
+    Skipped { reason: String },
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemOutcome {
+    pub action: PlannedAction,
+    pub status: ItemStatus,
+}
+
+/// Summary of an organizer run for one user
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunReport {
+    pub username: String,
+    pub stats: FileStats,
+    pub items: Vec<ItemOutcome>,
+    /// Entries left alone by the plan.
+    pub skipped: Vec<SkippedEntry>,
+    pub errors: Vec<String>,
+    pub dry_run: bool,
+    pub cancelled: bool,
+    /// Files left alone because no category knows their extension.
+    #[serde(default)]
+    pub unrecognized: BTreeMap<String, ExtensionTally>,
+}
+
+/// Outcome of organizing one user as part of a multi-user run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserRunResult {
+    pub username: String,
+    /// Report of the run, absent when the user could not be organized at all.
+    pub report: Option<RunReport>,
+    /// Why the user could not be organized.
+    pub error: Option<String>,
+    /// Start of the run in seconds since the Unix epoch.
+    #[serde(default)]
+    pub started_at: u64,
+    #[serde(default)]
+    pub duration_ms: u64,
+    /// Directories that were organized.
+    #[serde(default)]
+    pub source_dirs: Vec<PathBuf>,
+}
+
+/// Plan of one user as part of a multi-user run, for review before executing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserPlan {
+    pub username: String,
+    /// Absent when the user could not be planned.
+    pub plan: Option<Plan>,
+    /// Why the user could not be planned.
+    pub error: Option<String>,
+}
+
+/// Per-user breakdown of a run over several users
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MultiUserReport {
+    pub users: Vec<UserRunResult>,
+    pub totals: FileStats,
+}
+
+impl MultiUserReport {
+    /// Unrecognised extensions of all users together.
+    pub fn unrecognized(&self) -> BTreeMap<String, ExtensionTally> {
+        let mut merged: BTreeMap<String, ExtensionTally> = BTreeMap::new();
+        for report in self.users.iter().filter_map(|u| u.report.as_ref()) {
+            for (extension, tally) in &report.unrecognized {
+                merged.entry(extension.clone()).or_default().add(tally);
+            }
+        }
+        merged
+    }
+
+    /// Every error of the run, prefixed with the user it belongs to.
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for user in &self.users {
+            if let Some(error) = &user.error {
+                errors.push(format!("{}: {error}", user.username));
+            }
+            if let Some(report) = &user.report {
+                errors.extend(
+                    report
+                        .errors
+                        .iter()
+                        .map(|e| format!("{}: {e}", user.username)),
+                );
+            }
+        }
+        errors
+    }
+}
+
+/// Progress notifications emitted while executing a plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    Started {
+        username: String,
+        total: usize,
+    },
+    Item {
+        completed: usize,
+        total: usize,
+        outcome: ItemOutcome,
+    },
+    Finished {
+        username: String,
+    },
+}
+
+/// Receiver for progress notifications
+pub trait ProgressSink: Send + Sync {
+    fn on_progress(&self, event: &ProgressEvent);
+}
+
+impl<F> ProgressSink for F
+where
+    F: Fn(&ProgressEvent) + Send + Sync,
+{
+    fn on_progress(&self, event: &ProgressEvent) {
+        self(event)
+    }
+}
+
+/// Progress sink that discards every event
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn on_progress(&self, _event: &ProgressEvent) {}
+}
+
+/// Shared flag used to stop a running organizer
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
     }
 }
@@ -0,0 +1,223 @@
+//! Bounded folder traversal shared by the analyzer, classifier and planner
+
+use crate::config::TraversalConfig;
+use crate::organizer::ignore::{IgnoreRules, IGNORE_FILE_NAME};
+use crate::organizer::types::{SkipReason, SkippedEntry, SymlinkPolicy};
+use crate::platform::filesystem::{FileSystem, Metadata};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Something found while walking a tree
+pub enum Walked<'a> {
+    /// An entry to consider. Under `SymlinkPolicy::MoveLink` links come
+    /// through with their own metadata; under `Follow` with their target's.
+    Entry {
+        path: &'a Path,
+        metadata: &'a Metadata,
+    },
+    /// An entry left alone, with the reason.
+    Skipped(SkippedEntry),
+}
+
+/// Visits every entry below `root` in sorted order per directory.
+///
+/// `rules` are the ignore rules in effect above `root`; the ignore file of
+/// every directory read, `root` included, is added on the way down. Ignored
+/// entries, special files and symlinks the policy does not organize are
+/// reported as skipped. Directories past `max_depth` or on another
+/// filesystem are visited but not descended into.
+pub fn walk_entries(
+    fs: &dyn FileSystem,
+    root: &Path,
+    rules: &IgnoreRules,
+    traversal: &TraversalConfig,
+    mut visit: impl FnMut(Walked<'_>),
+) {
+    let root_dev = fs.metadata(root).map(|m| m.dev).unwrap_or_default();
+    let follow = traversal.symlinks == SymlinkPolicy::Follow;
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+    if follow {
+        visited.extend(fs.canonicalize(root));
+    }
+    let mut pending = vec![(root.to_path_buf(), rules.clone(), 0usize)];
+    while let Some((dir, rules, depth)) = pending.pop() {
+        let Ok(mut children) = fs.read_dir(&dir) else {
+            continue;
+        };
+        children.sort();
+        let has_ignore_file = children
+            .iter()
+            .any(|c| c.file_name().is_some_and(|n| n == IGNORE_FILE_NAME));
+        let rules = if has_ignore_file {
+            rules.with_dir(fs, &dir)
+        } else {
+            rules
+        };
+        let depth = depth + 1;
+        let mut subdirs = Vec::new();
+        for child in children {
+            let Ok(mut metadata) = fs.symlink_metadata(&child) else {
+                continue;
+            };
+            if metadata.is_symlink() && follow {
+                match fs.metadata(&child) {
+                    Ok(target) => metadata = target,
+                    Err(_) => {
+                        let target = fs.read_link(&child).unwrap_or_default();
+                        visit(skipped(child, SkipReason::Symlink { target }));
+                        continue;
+                    }
+                }
+            }
+            if let Some(reason) = unorganized(fs, &rules, traversal, &child, &metadata) {
+                visit(skipped(child, reason));
+                continue;
+            }
+            if metadata.is_dir() && follow {
+                let Ok(canonical) = fs.canonicalize(&child) else {
+                    continue;
+                };
+                if !visited.insert(canonical.clone()) {
+                    visit(skipped(
+                        child,
+                        SkipReason::SymlinkLoop { target: canonical },
+                    ));
+                    continue;
+                }
+            }
+            visit(Walked::Entry {
+                path: &child,
+                metadata: &metadata,
+            });
+            if !metadata.is_dir() {
+                continue;
+            }
+            if traversal.max_depth.is_some_and(|max| depth >= max) {
+                visit(skipped(child, SkipReason::DepthLimit));
+            } else if traversal.same_filesystem && metadata.dev != root_dev {
+                visit(skipped(child, SkipReason::OtherFilesystem));
+            } else {
+                subdirs.push(child);
+            }
+        }
+        // Reversed so the stack pops subdirectories in sorted order
+        pending.extend(subdirs.into_iter().rev().map(|d| (d, rules.clone(), depth)));
+    }
+}
+
+/// Why an entry is left alone regardless of its content, if it is.
+///
+/// `metadata` is the entry's own, or its target's when symlinks are followed.
+pub fn unorganized(
+    fs: &dyn FileSystem,
+    rules: &IgnoreRules,
+    traversal: &TraversalConfig,
+    path: &Path,
+    metadata: &Metadata,
+) -> Option<SkipReason> {
+    if let Some(pattern) = rules.matched(path, metadata.is_dir()) {
+        return Some(SkipReason::Ignored { pattern });
+    }
+    if metadata.kind.is_special() {
+        return Some(SkipReason::SpecialFile {
+            kind: metadata.kind.as_str().to_string(),
+        });
+    }
+    if metadata.is_symlink() && traversal.symlinks == SymlinkPolicy::Skip {
+        return Some(SkipReason::Symlink {
+            target: fs.read_link(path).unwrap_or_default(),
+        });
+    }
+    None
+}
+
+fn skipped(path: PathBuf, reason: SkipReason) -> Walked<'static> {
+    Walked::Skipped(SkippedEntry { path, reason })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::platform::filesystem::{FileKind, MemoryFileSystem};
+
+    fn walk(fs: &MemoryFileSystem, traversal: &TraversalConfig) -> (Vec<String>, Vec<String>) {
+        let root = Path::new("/d");
+        let rules = IgnoreRules::global(&Config::default(), root);
+        let (mut entries, mut skipped) = (Vec::new(), Vec::new());
+        walk_entries(fs, root, &rules, traversal, |walked| match walked {
+            Walked::Entry { path, .. } => entries.push(path.display().to_string()),
+            Walked::Skipped(entry) => {
+                skipped.push(format!("{} ({})", entry.path.display(), entry.reason))
+            }
+        });
+        (entries, skipped)
+    }
+
+    #[test]
+    fn test_special_files_and_depth_limit() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/d/a/b/c/deep.mp3", "x");
+        fs.add_special("/d/pipe", FileKind::Fifo);
+        fs.add_symlink("/d/root", "/");
+        let traversal = TraversalConfig {
+            max_depth: Some(2),
+            ..TraversalConfig::default()
+        };
+
+        let (entries, skipped) = walk(&fs, &traversal);
+
+        assert_eq!(entries, vec!["/d/a", "/d/a/b"]);
+        assert_eq!(
+            skipped,
+            vec![
+                "/d/pipe (fifo)",
+                "/d/root (symlink to /)",
+                "/d/a/b (too deep)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_follow_guards_against_loops() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/d/album/a.mp3", "x");
+        fs.add_symlink("/d/album/again", "..");
+        fs.add_symlink("/d/shortcut.mp3", "album/a.mp3");
+        fs.add_symlink("/d/broken", "/nowhere");
+        let traversal = TraversalConfig {
+            symlinks: SymlinkPolicy::Follow,
+            ..TraversalConfig::default()
+        };
+
+        let (entries, skipped) = walk(&fs, &traversal);
+
+        assert_eq!(
+            entries,
+            vec!["/d/album", "/d/shortcut.mp3", "/d/album/a.mp3"]
+        );
+        assert_eq!(
+            skipped,
+            vec![
+                "/d/broken (symlink to /nowhere)",
+                "/d/album/again (symlink loop through /d)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_move_link_reports_links_as_entries() {
+        let fs = MemoryFileSystem::new();
+        fs.add_dir("/data");
+        fs.add_symlink("/d/data", "/data");
+        let traversal = TraversalConfig {
+            symlinks: SymlinkPolicy::MoveLink,
+            ..TraversalConfig::default()
+        };
+
+        let (entries, skipped) = walk(&fs, &traversal);
+
+        assert_eq!(entries, vec!["/d/data"]);
+        assert!(skipped.is_empty());
+    }
+}
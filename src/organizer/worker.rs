@@ -0,0 +1,263 @@
+//! Privilege-separated organizing: one worker process per user
+//!
+//! The parent, usually running as root, starts the current executable with
+//! [`WORKER_ARG`] under the uid/gid of the home's owner. The request goes to
+//! the worker's stdin as JSON and the worker answers on stdout with one JSON
+//! [`WorkerMessage`] per line: progress events, log events and audit records,
+//! then a plan, a report or an error, depending on the [`WorkerTask`].
+
+use crate::config::{
+    ArchiveConfig, ClassifierConfig, Config, FileExtensions, PerformanceConfig, RetentionRule,
+    SourceDirConfig, TraversalConfig,
+};
+use crate::error::{Error, Result};
+use crate::logging::{initialize_forwarded_logging, log_forwarded};
+use crate::organizer::engine::Organizer;
+use crate::organizer::mover::localized_error;
+use crate::organizer::types::{
+    CancellationToken, CollisionPolicy, Plan, ProgressEvent, ProgressSink, RunReport,
+};
+use crate::platform::audit::{AuditRecord, AuditSink};
+use crate::platform::filesystem::{FileSystem, Owner};
+use crate::platform::user::StaticUserProvider;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+/// First argument that turns the executable into a worker
+pub const WORKER_ARG: &str = "--organize-worker";
+
+/// What a worker is asked to do
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerRequest {
+    pub username: String,
+    pub home: PathBuf,
+    pub language: String,
+    pub collision_policy: CollisionPolicy,
+    pub dry_run: bool,
+    #[serde(default)]
+    pub file_extensions: FileExtensions,
+    pub classifier: ClassifierConfig,
+    pub sources: Vec<SourceDirConfig>,
+    pub ignore_patterns: Vec<String>,
+    pub traversal: TraversalConfig,
+    #[serde(default)]
+    pub archives: ArchiveConfig,
+    #[serde(default)]
+    pub retention: Vec<RetentionRule>,
+    #[serde(default)]
+    pub performance: PerformanceConfig,
+    /// Send an audit record for every filesystem mutation.
+    #[serde(default)]
+    pub audit: bool,
+    /// Least severe level of the log events sent back, as in `LoggingConfig::level`.
+    #[serde(default)]
+    pub log_level: String,
+    #[serde(default)]
+    pub task: WorkerTask,
+}
+
+/// What a worker does with the user's home
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "task", rename_all = "snake_case")]
+pub enum WorkerTask {
+    /// Plan and execute, answering with a report.
+    #[default]
+    Run,
+    /// Only plan, answering with the plan.
+    Plan,
+    /// Execute a reviewed plan, answering with a report.
+    Execute { plan: Plan },
+}
+
+/// Final answer of a worker
+#[derive(Debug)]
+pub enum WorkerReply {
+    Plan(Plan),
+    Report(RunReport),
+}
+
+impl WorkerRequest {
+    /// The organizer configuration the worker runs with.
+    pub fn config(&self) -> Config {
+        Config {
+            file_extensions: self.file_extensions.clone(),
+            classifier: self.classifier.clone(),
+            sources: self.sources.clone(),
+            ignore_patterns: self.ignore_patterns.clone(),
+            traversal: self.traversal.clone(),
+            archives: self.archives.clone(),
+            retention: self.retention.clone(),
+            performance: self.performance.clone(),
+            ..Config::default()
+        }
+    }
+}
+
+/// One line of worker output
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerMessage {
+    Progress { event: ProgressEvent },
+    Log { level: String, message: String },
+    Audit { record: AuditRecord },
+    Plan { plan: Plan },
+    Report { report: RunReport },
+    Error { message: String },
+}
+
+/// Runs a worker: reads the request from stdin and writes messages to stdout.
+///
+/// Returns the process exit code.
+pub fn run_worker() -> i32 {
+    let mut input = String::new();
+    let request: WorkerRequest = match io::stdin()
+        .read_to_string(&mut input)
+        .map_err(Error::from)
+        .and_then(|_| serde_json::from_str(&input).map_err(Error::from))
+    {
+        Ok(request) => request,
+        Err(e) => {
+            send(&WorkerMessage::Error {
+                message: e.to_string(),
+            });
+            return 1;
+        }
+    };
+    initialize_forwarded_logging(&request.log_level, |level, message| {
+        send(&WorkerMessage::Log {
+            level: level.to_string(),
+            message,
+        })
+    });
+    let config = request.config();
+    let message = match organize(&request, &config) {
+        Ok(WorkerReply::Plan(plan)) => WorkerMessage::Plan { plan },
+        Ok(WorkerReply::Report(report)) => WorkerMessage::Report { report },
+        Err(e) => WorkerMessage::Error {
+            message: localized_error(&config, &request.language, &e),
+        },
+    };
+    let code = i32::from(matches!(message, WorkerMessage::Error { .. }));
+    send(&message);
+    code
+}
+
+fn organize(request: &WorkerRequest, config: &Config) -> Result<WorkerReply> {
+    let progress = |event: &ProgressEvent| {
+        send(&WorkerMessage::Progress {
+            event: event.clone(),
+        })
+    };
+    let mut builder = Organizer::builder();
+    if request.audit {
+        let audit = |record: &AuditRecord| {
+            send(&WorkerMessage::Audit {
+                record: record.clone(),
+            })
+        };
+        builder = builder.audit(Arc::new(audit));
+    }
+    let organizer = builder
+        .config(config.clone())
+        .language(request.language.clone())
+        .user_provider(Arc::new(
+            StaticUserProvider::new().with_user(&request.username, &request.home),
+        ))
+        .filesystem(worker_filesystem(&request.home)?)
+        .collision_policy(request.collision_policy)
+        .dry_run(request.dry_run)
+        .progress(Arc::new(progress))
+        .build();
+    match &request.task {
+        WorkerTask::Run => organizer.run(&request.username).map(WorkerReply::Report),
+        WorkerTask::Plan => organizer.plan(&request.username).map(WorkerReply::Plan),
+        WorkerTask::Execute { plan } => organizer.execute(plan).map(WorkerReply::Report),
+    }
+}
+
+#[cfg(unix)]
+fn worker_filesystem(home: &Path) -> Result<Arc<dyn FileSystem>> {
+    Ok(Arc::new(crate::platform::dirfd::DirFdFileSystem::open(
+        home,
+    )?))
+}
+
+#[cfg(not(unix))]
+fn worker_filesystem(_home: &Path) -> Result<Arc<dyn FileSystem>> {
+    Ok(Arc::new(crate::platform::filesystem::RealFileSystem))
+}
+
+fn send(message: &WorkerMessage) {
+    let Ok(line) = serde_json::to_string(message) else {
+        return;
+    };
+    let mut stdout = io::stdout().lock();
+    // The parent going away is not something the worker can report
+    let _ = writeln!(stdout, "{line}").and_then(|_| stdout.flush());
+}
+
+/// Runs `request` in a worker started from `command` as `owner`.
+///
+/// Progress is forwarded to `progress`, log events to the parent's logger and
+/// audit records to `audit`; cancelling kills the worker.
+#[cfg(unix)]
+pub(crate) fn spawn_worker(
+    command: &Path,
+    request: &WorkerRequest,
+    owner: Owner,
+    progress: &dyn ProgressSink,
+    audit: Option<&dyn AuditSink>,
+    cancellation: &CancellationToken,
+) -> Result<WorkerReply> {
+    use std::os::unix::process::CommandExt;
+
+    let mut child = Command::new(command)
+        .arg(WORKER_ARG)
+        .uid(owner.uid)
+        .gid(owner.gid)
+        .env_clear()
+        .current_dir(&request.home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin.write_all(&serde_json::to_vec(request)?)?;
+    drop(stdin);
+
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut outcome = None;
+    for line in BufReader::new(stdout).lines() {
+        if cancellation.is_cancelled() {
+            let _ = child.kill();
+            break;
+        }
+        match serde_json::from_str(&line?)? {
+            WorkerMessage::Progress { event } => progress.on_progress(&event),
+            WorkerMessage::Log { level, message } => {
+                if let Ok(level) = level.parse() {
+                    log_forwarded(&request.username, level, &message);
+                }
+            }
+            WorkerMessage::Audit { record } => {
+                if let Some(audit) = audit {
+                    audit.record(&record);
+                }
+            }
+            WorkerMessage::Plan { plan } => outcome = Some(Ok(WorkerReply::Plan(plan))),
+            WorkerMessage::Report { report } => outcome = Some(Ok(WorkerReply::Report(report))),
+            WorkerMessage::Error { message } => outcome = Some(Err(Error::Worker { message })),
+        }
+    }
+    let status = child.wait()?;
+    match outcome {
+        Some(result) => result,
+        None if cancellation.is_cancelled() => Err(Error::Cancelled),
+        None => Err(Error::Worker {
+            message: format!("worker for {} exited with {status}", request.username),
+        }),
+    }
+}
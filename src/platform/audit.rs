@@ -0,0 +1,297 @@
+//! Append-only audit trail of every filesystem mutation
+//!
+//! [`AuditedFileSystem`] wraps another [`FileSystem`] and hands one
+//! [`AuditRecord`] per mutating call, failed ones included, to an
+//! [`AuditSink`]. [`AuditLog`] writes them to a file as JSON lines.
+
+use crate::platform::filesystem::{FileSystem, Metadata, Owner, ReadSeek};
+use crate::platform::user::effective_uid;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Kind of filesystem mutation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    Rename,
+    Copy,
+    RemoveFile,
+    RemoveDir,
+    CreateDir,
+    Write,
+    Symlink,
+    SetOwner,
+    SetMode,
+}
+
+/// One filesystem mutation and its outcome
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// RFC 3339 timestamp with microseconds.
+    pub time: String,
+    /// Process that made the call.
+    pub pid: u32,
+    /// Effective uid of that process, where there is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    pub operation: AuditOperation,
+    pub path: PathBuf,
+    /// Target of a rename or copy; what a symlink points to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Owner>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// Why the call failed; absent when it succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    fn new(operation: AuditOperation, path: &Path) -> Self {
+        Self {
+            time: humantime::format_rfc3339_micros(SystemTime::now()).to_string(),
+            pid: std::process::id(),
+            uid: effective_uid(),
+            operation,
+            path: path.to_path_buf(),
+            destination: None,
+            owner: None,
+            mode: None,
+            error: None,
+        }
+    }
+
+    fn destination(mut self, destination: &Path) -> Self {
+        self.destination = Some(destination.to_path_buf());
+        self
+    }
+
+    fn outcome<T>(mut self, result: &io::Result<T>) -> Self {
+        self.error = result.as_ref().err().map(|e| e.to_string());
+        self
+    }
+}
+
+/// Receiver for audit records
+pub trait AuditSink: Send + Sync {
+    fn record(&self, record: &AuditRecord);
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(&AuditRecord) + Send + Sync,
+{
+    fn record(&self, record: &AuditRecord) {
+        self(record)
+    }
+}
+
+/// Audit sink appending one JSON line per record to a file
+pub struct AuditLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    /// Opens `path` for appending, creating it and its parents if needed.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AuditSink for AuditLog {
+    fn record(&self, record: &AuditRecord) {
+        let Ok(mut line) = serde_json::to_string(record) else {
+            return;
+        };
+        line.push('\n');
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        // Each line goes out in one unbuffered write so a crash loses at most that line
+        if let Err(e) = file.write_all(line.as_bytes()) {
+            tracing::error!("Failed to write audit log {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Filesystem that audits every mutation made through it
+pub struct AuditedFileSystem {
+    inner: Arc<dyn FileSystem>,
+    sink: Arc<dyn AuditSink>,
+}
+
+impl AuditedFileSystem {
+    pub fn new(inner: Arc<dyn FileSystem>, sink: Arc<dyn AuditSink>) -> Self {
+        Self { inner, sink }
+    }
+
+    fn audit<T>(&self, record: AuditRecord, result: io::Result<T>) -> io::Result<T> {
+        self.sink.record(&record.outcome(&result));
+        result
+    }
+}
+
+impl FileSystem for AuditedFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.inner.read_dir(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.inner.symlink_metadata(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let record = AuditRecord::new(AuditOperation::Rename, from).destination(to);
+        self.audit(record, self.inner.rename(from, to))
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        let record = AuditRecord::new(AuditOperation::Copy, from).destination(to);
+        self.audit(record, self.inner.copy(from, to))
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let record = AuditRecord::new(AuditOperation::RemoveFile, path);
+        self.audit(record, self.inner.remove_file(path))
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        let record = AuditRecord::new(AuditOperation::RemoveDir, path);
+        self.audit(record, self.inner.remove_dir(path))
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let record = AuditRecord::new(AuditOperation::CreateDir, path);
+        self.audit(record, self.inner.create_dir_all(path))
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.inner.read_to_string(path)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn ReadSeek>> {
+        self.inner.open(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let record = AuditRecord::new(AuditOperation::Write, path);
+        self.audit(record, self.inner.write(path, contents))
+    }
+
+    fn write_from(&self, path: &Path, contents: &mut dyn Read) -> io::Result<u64> {
+        let record = AuditRecord::new(AuditOperation::Write, path);
+        self.audit(record, self.inner.write_from(path, contents))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.inner.read_link(path)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        let record = AuditRecord::new(AuditOperation::Symlink, link).destination(target);
+        self.audit(record, self.inner.symlink(target, link))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.inner.canonicalize(path)
+    }
+
+    fn set_owner(&self, path: &Path, owner: Owner) -> io::Result<()> {
+        let record = AuditRecord {
+            owner: Some(owner),
+            ..AuditRecord::new(AuditOperation::SetOwner, path)
+        };
+        self.audit(record, self.inner.set_owner(path, owner))
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+        let record = AuditRecord {
+            mode: Some(mode),
+            ..AuditRecord::new(AuditOperation::SetMode, path)
+        };
+        self.audit(record, self.inner.set_mode(path, mode))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::filesystem::MemoryFileSystem;
+
+    #[test]
+    fn test_mutations_are_audited_with_their_outcome() {
+        let memory = Arc::new(MemoryFileSystem::new());
+        memory.add_file("/home/a/song.mp3", b"x");
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let collected = records.clone();
+        let sink = move |r: &AuditRecord| collected.lock().unwrap().push(r.clone());
+        let fs = AuditedFileSystem::new(memory, Arc::new(sink));
+
+        fs.create_dir_all(Path::new("/home/a/Music")).unwrap();
+        fs.rename(
+            Path::new("/home/a/song.mp3"),
+            Path::new("/home/a/Music/song.mp3"),
+        )
+        .unwrap();
+        fs.read_dir(Path::new("/home/a")).unwrap();
+        assert!(fs.remove_file(Path::new("/home/a/missing")).is_err());
+
+        let records = records.lock().unwrap();
+        let operations: Vec<AuditOperation> = records.iter().map(|r| r.operation).collect();
+        assert_eq!(
+            operations,
+            [
+                AuditOperation::CreateDir,
+                AuditOperation::Rename,
+                AuditOperation::RemoveFile
+            ]
+        );
+        assert_eq!(
+            records[1].destination.as_deref(),
+            Some(Path::new("/home/a/Music/song.mp3"))
+        );
+        assert!(records[1].error.is_none());
+        assert!(records[2].error.is_some());
+    }
+
+    #[test]
+    fn test_audit_log_appends_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs").join("audit.jsonl");
+        for _ in 0..2 {
+            let log = AuditLog::open(&path).unwrap();
+            log.record(&AuditRecord::new(
+                AuditOperation::RemoveDir,
+                Path::new("/x"),
+            ));
+        }
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let records: Vec<AuditRecord> = contents
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert!(contents.contains("\"operation\":\"remove_dir\""));
+    }
+}
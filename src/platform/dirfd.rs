@@ -0,0 +1,435 @@
+//! `FileSystem` that walks below a root one directory fd at a time
+//!
+//! Every path below the root is opened component by component with
+//! `openat(O_NOFOLLOW)`, and the final operation uses the `*at` call on the
+//! parent's fd. Swapping an intermediate directory for a symlink between a
+//! check and a rename therefore fails instead of redirecting the operation.
+//! The final component keeps `std::fs` semantics: `metadata`, `read_dir` and
+//! reads follow it, everything else acts on the link itself. Paths outside
+//! the root fall back to `std::fs`.
+
+use crate::platform::filesystem::{
+    FileKind, FileSystem, Metadata, Owner, ReadSeek, RealFileSystem,
+};
+use std::ffi::{CString, OsStr};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const DIR_FLAGS: libc::c_int = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+
+/// Where a path lives relative to the root
+enum Location {
+    Root,
+    /// A directory fd and the name of the entry inside it.
+    Entry(OwnedFd, CString),
+    Outside,
+}
+
+/// `FileSystem` using directory-fd relative operations below `root`
+pub struct DirFdFileSystem {
+    root: PathBuf,
+    root_fd: OwnedFd,
+}
+
+impl DirFdFileSystem {
+    /// Opens `root`, which may itself be reached through symlinks.
+    pub fn open(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        let path = cstring(root.as_os_str())?;
+        // SAFETY: `path` is a valid NUL-terminated string
+        let fd = cvt(unsafe { libc::open(path.as_ptr(), DIR_FLAGS) })?;
+        Ok(Self {
+            root,
+            // SAFETY: `fd` was just opened and is owned by nobody else
+            root_fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn locate(&self, path: &Path) -> io::Result<Location> {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return Ok(Location::Outside);
+        };
+        let mut names = Vec::new();
+        for component in relative.components() {
+            match component {
+                Component::Normal(name) => names.push(name),
+                Component::CurDir => {}
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} leaves {}", path.display(), self.root.display()),
+                    ))
+                }
+            }
+        }
+        let Some((last, parents)) = names.split_last() else {
+            return Ok(Location::Root);
+        };
+        let mut dir = self.root_fd.try_clone()?;
+        for name in parents {
+            dir = open_at(&dir, &cstring(name)?, DIR_FLAGS | libc::O_NOFOLLOW, 0)?;
+        }
+        Ok(Location::Entry(dir, cstring(last)?))
+    }
+
+    fn stat(&self, path: &Path, flags: libc::c_int) -> io::Result<Metadata> {
+        let (dir, name) = match self.locate(path)? {
+            Location::Root => return RealFileSystem.metadata(path),
+            Location::Outside if flags == 0 => return RealFileSystem.metadata(path),
+            Location::Outside => return RealFileSystem.symlink_metadata(path),
+            Location::Entry(dir, name) => (dir, name),
+        };
+        let mut stat = MaybeUninit::<libc::stat>::uninit();
+        // SAFETY: valid fd and name; `stat` is written on success
+        cvt(unsafe { libc::fstatat(dir.as_raw_fd(), name.as_ptr(), stat.as_mut_ptr(), flags) })?;
+        // SAFETY: fstatat succeeded
+        Ok(metadata_from_stat(&unsafe { stat.assume_init() }))
+    }
+
+    /// Opens a file below the root; the final component is followed unless
+    /// `flags` contains `O_NOFOLLOW`.
+    fn open_file(&self, path: &Path, flags: libc::c_int, mode: libc::mode_t) -> io::Result<File> {
+        match self.locate(path)? {
+            Location::Entry(dir, name) => Ok(File::from(open_at(
+                &dir,
+                &name,
+                flags | libc::O_CLOEXEC,
+                mode,
+            )?)),
+            Location::Root => Err(io::Error::new(
+                io::ErrorKind::IsADirectory,
+                format!("{} is a directory", path.display()),
+            )),
+            Location::Outside => {
+                let mut options = std::fs::OpenOptions::new();
+                if flags & libc::O_WRONLY != 0 {
+                    options.write(true).create(true).truncate(true);
+                } else {
+                    options.read(true);
+                }
+                options.open(path)
+            }
+        }
+    }
+}
+
+impl FileSystem for DirFdFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let fd = match self.locate(path)? {
+            Location::Root => self.root_fd.try_clone()?,
+            Location::Entry(dir, name) => open_at(&dir, &name, DIR_FLAGS, 0)?,
+            Location::Outside => return RealFileSystem.read_dir(path),
+        };
+        // SAFETY: fdopendir takes ownership of a valid directory fd
+        let stream = unsafe { libc::fdopendir(fd.into_raw_fd()) };
+        if stream.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let mut children = Vec::new();
+        loop {
+            // SAFETY: `stream` is a valid open directory stream
+            let entry = unsafe { libc::readdir(stream) };
+            if entry.is_null() {
+                break;
+            }
+            // SAFETY: readdir returned a valid entry whose name is NUL-terminated
+            let name = unsafe { std::ffi::CStr::from_ptr((*entry).d_name.as_ptr()) };
+            let name = OsStr::from_bytes(name.to_bytes());
+            if name != "." && name != ".." {
+                children.push(path.join(name));
+            }
+        }
+        // SAFETY: `stream` is valid and closed exactly once
+        unsafe { libc::closedir(stream) };
+        Ok(children)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.stat(path, 0)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.stat(path, libc::AT_SYMLINK_NOFOLLOW)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match (self.locate(from)?, self.locate(to)?) {
+            (Location::Entry(from_dir, from_name), Location::Entry(to_dir, to_name)) => {
+                // SAFETY: valid fds and names
+                cvt(unsafe {
+                    libc::renameat(
+                        from_dir.as_raw_fd(),
+                        from_name.as_ptr(),
+                        to_dir.as_raw_fd(),
+                        to_name.as_ptr(),
+                    )
+                })
+                .map(drop)
+            }
+            _ => RealFileSystem.rename(from, to),
+        }
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        let mut source = self.open_file(from, libc::O_RDONLY, 0)?;
+        let mode = source.metadata().map(|m| {
+            use std::os::unix::fs::PermissionsExt;
+            m.permissions().mode() & 0o7777
+        })?;
+        let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_NOFOLLOW;
+        let mut destination = self.open_file(to, flags, mode as libc::mode_t)?;
+        let copied = io::copy(&mut source, &mut destination)?;
+        self.set_mode(to, mode)?;
+        Ok(copied)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        match self.locate(path)? {
+            // SAFETY: valid fd and name
+            Location::Entry(dir, name) => {
+                cvt(unsafe { libc::unlinkat(dir.as_raw_fd(), name.as_ptr(), 0) }).map(drop)
+            }
+            _ => RealFileSystem.remove_file(path),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        match self.locate(path)? {
+            // SAFETY: valid fd and name
+            Location::Entry(dir, name) => {
+                cvt(unsafe { libc::unlinkat(dir.as_raw_fd(), name.as_ptr(), libc::AT_REMOVEDIR) })
+                    .map(drop)
+            }
+            _ => RealFileSystem.remove_dir(path),
+        }
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let relative = match path.strip_prefix(&self.root) {
+            Ok(relative) => relative,
+            Err(_) => return RealFileSystem.create_dir_all(path),
+        };
+        let mut dir = self.root_fd.try_clone()?;
+        for component in relative.components() {
+            let Component::Normal(name) = component else {
+                continue;
+            };
+            let name = cstring(name)?;
+            // SAFETY: valid fd and name
+            let created = unsafe { libc::mkdirat(dir.as_raw_fd(), name.as_ptr(), 0o777) };
+            if created == -1 {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::AlreadyExists {
+                    return Err(error);
+                }
+            }
+            dir = open_at(&dir, &name, DIR_FLAGS | libc::O_NOFOLLOW, 0)?;
+        }
+        Ok(())
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let mut contents = String::new();
+        self.open_file(path, libc::O_RDONLY, 0)?
+            .read_to_string(&mut contents)?;
+        Ok(contents)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn ReadSeek>> {
+        Ok(Box::new(self.open_file(path, libc::O_RDONLY, 0)?))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_NOFOLLOW;
+        self.open_file(path, flags, 0o666)?.write_all(contents)
+    }
+
+    fn write_from(&self, path: &Path, contents: &mut dyn Read) -> io::Result<u64> {
+        let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_NOFOLLOW;
+        io::copy(contents, &mut self.open_file(path, flags, 0o666)?)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let (dir, name) = match self.locate(path)? {
+            Location::Entry(dir, name) => (dir, name),
+            _ => return RealFileSystem.read_link(path),
+        };
+        let mut buffer = vec![0u8; libc::PATH_MAX as usize];
+        // SAFETY: valid fd and name; the buffer length is passed along
+        let len = unsafe {
+            libc::readlinkat(
+                dir.as_raw_fd(),
+                name.as_ptr(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+            )
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        buffer.truncate(len as usize);
+        Ok(PathBuf::from(OsStr::from_bytes(&buffer)))
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        match self.locate(link)? {
+            Location::Entry(dir, name) => {
+                let target = cstring(target.as_os_str())?;
+                // SAFETY: valid fd and names
+                cvt(unsafe { libc::symlinkat(target.as_ptr(), dir.as_raw_fd(), name.as_ptr()) })
+                    .map(drop)
+            }
+            _ => RealFileSystem.symlink(target, link),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        RealFileSystem.canonicalize(path)
+    }
+
+    fn set_owner(&self, path: &Path, owner: Owner) -> io::Result<()> {
+        match self.locate(path)? {
+            // SAFETY: valid fd and name
+            Location::Entry(dir, name) => cvt(unsafe {
+                libc::fchownat(
+                    dir.as_raw_fd(),
+                    name.as_ptr(),
+                    owner.uid,
+                    owner.gid,
+                    libc::AT_SYMLINK_NOFOLLOW,
+                )
+            })
+            .map(drop),
+            _ => RealFileSystem.set_owner(path, owner),
+        }
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+        match self.locate(path)? {
+            // SAFETY: valid fd and name
+            Location::Entry(dir, name) => cvt(unsafe {
+                libc::fchmodat(dir.as_raw_fd(), name.as_ptr(), mode as libc::mode_t, 0)
+            })
+            .map(drop),
+            _ => RealFileSystem.set_mode(path, mode),
+        }
+    }
+}
+
+fn open_at(
+    dir: &OwnedFd,
+    name: &CString,
+    flags: libc::c_int,
+    mode: libc::mode_t,
+) -> io::Result<OwnedFd> {
+    // SAFETY: valid fd and name
+    let fd = cvt(unsafe {
+        libc::openat(
+            dir.as_raw_fd(),
+            name.as_ptr(),
+            flags,
+            libc::c_uint::from(mode),
+        )
+    })?;
+    // SAFETY: `fd` was just opened and is owned by nobody else
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn cstring(name: &OsStr) -> io::Result<CString> {
+    CString::new(name.as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+fn cvt(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+// Field widths of `stat` differ between platforms
+#[allow(clippy::unnecessary_cast)]
+fn metadata_from_stat(stat: &libc::stat) -> Metadata {
+    let kind = match stat.st_mode & libc::S_IFMT {
+        libc::S_IFREG => FileKind::File,
+        libc::S_IFDIR => FileKind::Dir,
+        libc::S_IFLNK => FileKind::Symlink,
+        libc::S_IFIFO => FileKind::Fifo,
+        libc::S_IFSOCK => FileKind::Socket,
+        libc::S_IFBLK => FileKind::BlockDevice,
+        libc::S_IFCHR => FileKind::CharDevice,
+        _ => FileKind::Other,
+    };
+    let modified = u64::try_from(stat.st_mtime)
+        .ok()
+        .map(|secs| SystemTime::UNIX_EPOCH + Duration::new(secs, stat.st_mtime_nsec as u32));
+    Metadata {
+        kind,
+        len: stat.st_size as u64,
+        modified,
+        dev: stat.st_dev as u64,
+        owner: Some(Owner {
+            uid: stat.st_uid,
+            gid: stat.st_gid,
+        }),
+        mode: Some(stat.st_mode as u32 & 0o7777),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operations_below_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let fs = DirFdFileSystem::open(dir.path()).unwrap();
+        let downloads = dir.path().join("Downloads");
+        let music = dir.path().join("Music/rock");
+
+        fs.create_dir_all(&downloads).unwrap();
+        fs.write(&downloads.join("song.mp3"), b"abc").unwrap();
+        fs.create_dir_all(&music).unwrap();
+        fs.rename(&downloads.join("song.mp3"), &music.join("song.mp3"))
+            .unwrap();
+
+        assert_eq!(fs.read_dir(&music).unwrap(), vec![music.join("song.mp3")]);
+        let metadata = fs.metadata(&music.join("song.mp3")).unwrap();
+        assert!(metadata.is_file());
+        assert_eq!(metadata.len, 3);
+        assert_eq!(fs.read_to_string(&music.join("song.mp3")).unwrap(), "abc");
+        assert_eq!(
+            fs.copy(&music.join("song.mp3"), &downloads.join("copy.mp3"))
+                .unwrap(),
+            3
+        );
+        fs.remove_file(&downloads.join("copy.mp3")).unwrap();
+        fs.remove_dir(&downloads).unwrap();
+        assert!(!downloads.exists());
+    }
+
+    #[test]
+    fn test_symlinked_intermediate_directory_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let fs = DirFdFileSystem::open(dir.path()).unwrap();
+        let link = dir.path().join("Music");
+        fs.symlink(outside.path(), &link).unwrap();
+
+        assert!(fs.symlink_metadata(&link).unwrap().is_symlink());
+        assert!(fs.metadata(&link).unwrap().is_dir());
+        assert!(fs.write(&link.join("planted.txt"), b"x").is_err());
+        assert!(fs.create_dir_all(&link.join("sub")).is_err());
+        assert!(!outside.path().join("planted.txt").exists());
+        assert_eq!(fs.read_link(&link).unwrap(), outside.path());
+    }
+}
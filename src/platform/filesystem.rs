@@ -0,0 +1,930 @@
+//! Filesystem abstraction so the organizer can run against real disks or in memory
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// A file opened for reading, see [`FileSystem::open`]
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// Kind of a filesystem entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Dir,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+    Other,
+}
+
+impl FileKind {
+    /// FIFOs, sockets, devices and anything else that is neither a file, a directory nor a link.
+    pub fn is_special(&self) -> bool {
+        !matches!(self, FileKind::File | FileKind::Dir | FileKind::Symlink)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileKind::File => "file",
+            FileKind::Dir => "directory",
+            FileKind::Symlink => "symlink",
+            FileKind::Fifo => "fifo",
+            FileKind::Socket => "socket",
+            FileKind::BlockDevice => "block device",
+            FileKind::CharDevice => "character device",
+            FileKind::Other => "special file",
+        }
+    }
+}
+
+/// Numeric owner of a filesystem entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Owner {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// Subset of file metadata the organizer relies on
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    pub kind: FileKind,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    /// Device the entry lives on; always 0 where the platform does not expose it.
+    pub dev: u64,
+    /// Owner, where the platform has numeric owners.
+    pub owner: Option<Owner>,
+    /// Permission bits, where the platform has them.
+    pub mode: Option<u32>,
+}
+
+impl Metadata {
+    pub fn is_file(&self) -> bool {
+        self.kind == FileKind::File
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Dir
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.kind == FileKind::Symlink
+    }
+}
+
+impl From<fs::Metadata> for Metadata {
+    fn from(metadata: fs::Metadata) -> Self {
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_dir() {
+            FileKind::Dir
+        } else if file_type.is_file() {
+            FileKind::File
+        } else {
+            special_kind(&file_type)
+        };
+        Self {
+            kind,
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            dev: device_id(&metadata),
+            owner: owner_of(&metadata),
+            mode: mode_of(&metadata),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn special_kind(file_type: &fs::FileType) -> FileKind {
+    use std::os::unix::fs::FileTypeExt;
+    if file_type.is_fifo() {
+        FileKind::Fifo
+    } else if file_type.is_socket() {
+        FileKind::Socket
+    } else if file_type.is_block_device() {
+        FileKind::BlockDevice
+    } else if file_type.is_char_device() {
+        FileKind::CharDevice
+    } else {
+        FileKind::Other
+    }
+}
+
+#[cfg(not(unix))]
+fn special_kind(_file_type: &fs::FileType) -> FileKind {
+    FileKind::Other
+}
+
+#[cfg(unix)]
+fn device_id(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.dev()
+}
+
+#[cfg(not(unix))]
+fn device_id(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+#[cfg(unix)]
+fn owner_of(metadata: &fs::Metadata) -> Option<Owner> {
+    use std::os::unix::fs::MetadataExt;
+    Some(Owner {
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+    })
+}
+
+#[cfg(not(unix))]
+fn owner_of(_metadata: &fs::Metadata) -> Option<Owner> {
+    None
+}
+
+#[cfg(unix)]
+fn mode_of(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn mode_of(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Operations the organizer performs on the filesystem.
+///
+/// Paths handed to every method are absolute or relative to the process
+/// working directory, exactly as with `std::fs`.
+pub trait FileSystem: Send + Sync {
+    /// Returns the paths of the direct children of `path`.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+    /// Returns metadata for `path`, following symlinks.
+    fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+    /// Returns metadata for `path` without following a final symlink.
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata>;
+    /// Renames a file or directory, replacing an existing destination file.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Copies the contents of a file, returning the number of bytes copied.
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64>;
+    /// Removes a file.
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    /// Removes an empty directory.
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+    /// Creates a directory and all of its missing parents.
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Reads a whole file as UTF-8 text.
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+    /// Opens a file for reading.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn ReadSeek>>;
+    /// Creates or truncates a file and writes `contents` to it.
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    /// Like `write`, copying from `contents` as it is read; returns the bytes written.
+    fn write_from(&self, path: &Path, contents: &mut dyn Read) -> io::Result<u64>;
+    /// Returns the target of a symlink.
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+    /// Creates a symlink at `link` pointing at `target`.
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()>;
+    /// Returns the absolute path of `path` with every symlink resolved.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+    /// Changes the owner of `path` without following a final symlink.
+    ///
+    /// A no-op where the platform has no numeric owners.
+    fn set_owner(&self, path: &Path, owner: Owner) -> io::Result<()>;
+    /// Changes the permission bits of `path`. A no-op where the platform has none.
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()>;
+
+    /// Returns true if `path` points at an existing entry.
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+}
+
+/// `FileSystem` backed by `std::fs`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        fs::metadata(path).map(Metadata::from)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        fs::symlink_metadata(path).map(Metadata::from)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        fs::copy(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn ReadSeek>> {
+        Ok(Box::new(fs::File::open(path)?))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn write_from(&self, path: &Path, contents: &mut dyn Read) -> io::Result<u64> {
+        io::copy(contents, &mut fs::File::create(path)?)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    #[cfg(unix)]
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    #[cfg(windows)]
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        let resolved = link
+            .parent()
+            .map_or(target.to_path_buf(), |p| p.join(target));
+        if resolved.is_dir() {
+            std::os::windows::fs::symlink_dir(target, link)
+        } else {
+            std::os::windows::fs::symlink_file(target, link)
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    #[cfg(unix)]
+    fn set_owner(&self, path: &Path, owner: Owner) -> io::Result<()> {
+        std::os::unix::fs::lchown(path, Some(owner.uid), Some(owner.gid))
+    }
+
+    #[cfg(not(unix))]
+    fn set_owner(&self, _path: &Path, _owner: Owner) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+
+    #[cfg(not(unix))]
+    fn set_mode(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum MemoryNode {
+    Dir,
+    File {
+        contents: Vec<u8>,
+        modified: SystemTime,
+    },
+    Symlink {
+        target: PathBuf,
+    },
+    Special(FileKind),
+}
+
+/// Owner and permission bits of a memory entry
+type MemoryAttrs = (Option<Owner>, Option<u32>);
+
+/// Maximum number of symlinks followed while resolving one path, as in Linux
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// In-memory `FileSystem` for deterministic tests.
+///
+/// Follows POSIX semantics closely enough for the organizer: renaming a file
+/// over another file replaces it, renaming onto a non-empty directory fails
+/// and every operation requires the parent directory to exist. Symlinks are
+/// resolved like the kernel does, relative targets against the link's parent.
+/// Clones share the same tree.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFileSystem {
+    nodes: Arc<Mutex<BTreeMap<PathBuf, MemoryNode>>>,
+    /// Owner and mode per path; locked after `nodes`.
+    attrs: Arc<Mutex<BTreeMap<PathBuf, MemoryAttrs>>>,
+    /// Owner given to entries created through the `FileSystem` methods.
+    current_owner: Arc<Mutex<Option<Owner>>>,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a directory and its parents.
+    pub fn add_dir(&self, path: impl AsRef<Path>) {
+        let mut nodes = self.nodes.lock().unwrap();
+        Self::insert_dir_all(&mut nodes, path.as_ref());
+    }
+
+    /// Creates a file with the given contents, creating parent directories as needed.
+    pub fn add_file(&self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) {
+        self.add_file_modified(path, contents, SystemTime::now());
+    }
+
+    /// Like `add_file`, with an explicit modification time.
+    pub fn add_file_modified(
+        &self,
+        path: impl AsRef<Path>,
+        contents: impl Into<Vec<u8>>,
+        modified: SystemTime,
+    ) {
+        self.insert(
+            path.as_ref(),
+            MemoryNode::File {
+                contents: contents.into(),
+                modified,
+            },
+        );
+    }
+
+    /// Creates a symlink, creating parent directories as needed.
+    pub fn add_symlink(&self, path: impl AsRef<Path>, target: impl Into<PathBuf>) {
+        self.insert(
+            path.as_ref(),
+            MemoryNode::Symlink {
+                target: target.into(),
+            },
+        );
+    }
+
+    /// Creates a FIFO, socket or device node, creating parent directories as needed.
+    pub fn add_special(&self, path: impl AsRef<Path>, kind: FileKind) {
+        self.insert(path.as_ref(), MemoryNode::Special(kind));
+    }
+
+    /// Sets the owner of entries created from now on through the `FileSystem`
+    /// methods, as the process identity would on a real disk.
+    pub fn set_current_owner(&self, owner: Option<Owner>) {
+        *self.current_owner.lock().unwrap() = owner;
+    }
+
+    /// Returns the contents of a file, if it exists.
+    pub fn contents(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        let nodes = self.nodes.lock().unwrap();
+        let path = Self::resolve(&nodes, path.as_ref(), true).ok()?;
+        match nodes.get(&path) {
+            Some(MemoryNode::File { contents, .. }) => Some(contents.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns every path currently stored, in sorted order.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.nodes.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Records the current owner for an entry created through the `FileSystem` methods.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let nodes = self.nodes.lock().unwrap();
+        let path = &Self::resolve(&nodes, path, true)?;
+        match nodes.get(path) {
+            Some(MemoryNode::File { contents, .. }) => Ok(contents.clone()),
+            Some(MemoryNode::Dir) => Err(is_a_directory(path)),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a regular file", path.display()),
+            )),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn mark_created(&self, path: PathBuf) {
+        if let Some(owner) = *self.current_owner.lock().unwrap() {
+            self.attrs.lock().unwrap().insert(path, (Some(owner), None));
+        }
+    }
+
+    fn insert(&self, path: &Path, node: MemoryNode) {
+        let mut nodes = self.nodes.lock().unwrap();
+        if let Some(parent) = path.parent() {
+            Self::insert_dir_all(&mut nodes, parent);
+        }
+        nodes.insert(path.to_path_buf(), node);
+    }
+
+    fn insert_dir_all(nodes: &mut BTreeMap<PathBuf, MemoryNode>, path: &Path) {
+        for ancestor in path.ancestors() {
+            if ancestor.as_os_str().is_empty() || Self::is_root(ancestor) {
+                continue;
+            }
+            nodes
+                .entry(ancestor.to_path_buf())
+                .or_insert(MemoryNode::Dir);
+        }
+    }
+
+    /// Resolves the symlinks in `path`; the final component only if `follow_last`.
+    fn resolve(
+        nodes: &BTreeMap<PathBuf, MemoryNode>,
+        path: &Path,
+        follow_last: bool,
+    ) -> io::Result<PathBuf> {
+        let mut pending: Vec<PathBuf> = path
+            .components()
+            .rev()
+            .map(|c| PathBuf::from(c.as_os_str()))
+            .collect();
+        let mut resolved = PathBuf::new();
+        let mut hops = 0;
+        while let Some(component) = pending.pop() {
+            match component.components().next() {
+                Some(Component::CurDir) | None => continue,
+                Some(Component::ParentDir) => {
+                    resolved.pop();
+                    continue;
+                }
+                Some(Component::Normal(_)) => {}
+                Some(Component::RootDir | Component::Prefix(_)) => {
+                    resolved.push(&component);
+                    continue;
+                }
+            }
+            let candidate = resolved.join(&component);
+            let is_last = pending.is_empty();
+            match nodes.get(&candidate) {
+                Some(MemoryNode::Symlink { target }) if !is_last || follow_last => {
+                    hops += 1;
+                    if hops > MAX_SYMLINK_HOPS {
+                        return Err(io::Error::other(format!(
+                            "too many levels of symbolic links in {}",
+                            path.display()
+                        )));
+                    }
+                    pending.extend(
+                        target
+                            .components()
+                            .rev()
+                            .map(|c| PathBuf::from(c.as_os_str())),
+                    );
+                    if target.is_absolute() {
+                        resolved = PathBuf::new();
+                    }
+                }
+                _ => resolved = candidate,
+            }
+        }
+        Ok(resolved)
+    }
+
+    fn is_root(path: &Path) -> bool {
+        path.parent().is_none()
+    }
+
+    fn node_exists(nodes: &BTreeMap<PathBuf, MemoryNode>, path: &Path) -> bool {
+        Self::is_root(path) || nodes.contains_key(path)
+    }
+
+    fn is_dir(nodes: &BTreeMap<PathBuf, MemoryNode>, path: &Path) -> bool {
+        Self::is_root(path) || matches!(nodes.get(path), Some(MemoryNode::Dir))
+    }
+
+    fn has_children(nodes: &BTreeMap<PathBuf, MemoryNode>, path: &Path) -> bool {
+        nodes.keys().any(|p| p.parent() == Some(path))
+    }
+
+    fn require_parent(nodes: &BTreeMap<PathBuf, MemoryNode>, path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) if !Self::is_dir(nodes, parent) => Err(not_found(parent)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} not found", path.display()),
+    )
+}
+
+fn is_a_directory(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::IsADirectory,
+        format!("{} is a directory", path.display()),
+    )
+}
+
+fn not_a_directory(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotADirectory,
+        format!("{} is not a directory", path.display()),
+    )
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let nodes = self.nodes.lock().unwrap();
+        let resolved = Self::resolve(&nodes, path, true)?;
+        if !Self::node_exists(&nodes, &resolved) {
+            return Err(not_found(path));
+        }
+        if !Self::is_dir(&nodes, &resolved) {
+            return Err(not_a_directory(path));
+        }
+        Ok(nodes
+            .keys()
+            .filter(|p| p.parent() == Some(resolved.as_path()))
+            .filter_map(|p| p.file_name().map(|name| path.join(name)))
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let resolved = Self::resolve(&self.nodes.lock().unwrap(), path, true)?;
+        self.symlink_metadata(&resolved)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let nodes = self.nodes.lock().unwrap();
+        let path = Self::resolve(&nodes, path, false)?;
+        let (kind, len, modified) = if Self::is_root(&path) {
+            (FileKind::Dir, 0, None)
+        } else {
+            match nodes.get(&path) {
+                Some(MemoryNode::Dir) => (FileKind::Dir, 0, None),
+                Some(MemoryNode::File { contents, modified }) => {
+                    (FileKind::File, contents.len() as u64, Some(*modified))
+                }
+                Some(MemoryNode::Symlink { target }) => {
+                    (FileKind::Symlink, target.as_os_str().len() as u64, None)
+                }
+                Some(MemoryNode::Special(kind)) => (*kind, 0, None),
+                None => return Err(not_found(&path)),
+            }
+        };
+        let (owner, mode) = self
+            .attrs
+            .lock()
+            .unwrap()
+            .get(&path)
+            .copied()
+            .unwrap_or_default();
+        Ok(Metadata {
+            kind,
+            len,
+            modified,
+            dev: 0,
+            owner,
+            mode,
+        })
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let from = &Self::resolve(&nodes, from, false)?;
+        let to = &Self::resolve(&nodes, to, false)?;
+        let source = nodes.get(from).cloned().ok_or_else(|| not_found(from))?;
+        Self::require_parent(&nodes, to)?;
+        if from == to {
+            return Ok(());
+        }
+        match (&source, nodes.get(to)) {
+            (MemoryNode::Dir, Some(existing)) if !matches!(existing, MemoryNode::Dir) => {
+                return Err(not_a_directory(to));
+            }
+            (source, Some(MemoryNode::Dir)) if !matches!(source, MemoryNode::Dir) => {
+                return Err(is_a_directory(to));
+            }
+            (MemoryNode::Dir, Some(MemoryNode::Dir)) if Self::has_children(&nodes, to) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::DirectoryNotEmpty,
+                    format!("{} is not empty", to.display()),
+                ));
+            }
+            _ => {}
+        }
+        if to.starts_with(from) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot move {} into itself", from.display()),
+            ));
+        }
+        let moved: Vec<PathBuf> = nodes
+            .keys()
+            .filter(|p| p.starts_with(from))
+            .cloned()
+            .collect();
+        let mut attrs = self.attrs.lock().unwrap();
+        attrs.remove(to);
+        for old in moved {
+            let node = nodes.remove(&old).expect("key collected above");
+            let relative = old.strip_prefix(from).expect("filtered by prefix");
+            let new = if relative.as_os_str().is_empty() {
+                to.to_path_buf()
+            } else {
+                to.join(relative)
+            };
+            if let Some(attr) = attrs.remove(&old) {
+                attrs.insert(new.clone(), attr);
+            }
+            nodes.insert(new, node);
+        }
+        Ok(())
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let from = &Self::resolve(&nodes, from, true)?;
+        let to = &Self::resolve(&nodes, to, true)?;
+        let contents = match nodes.get(from) {
+            Some(MemoryNode::File { contents, .. }) => contents.clone(),
+            Some(MemoryNode::Dir) => return Err(is_a_directory(from)),
+            Some(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is not a regular file", from.display()),
+                ));
+            }
+            None => return Err(not_found(from)),
+        };
+        Self::require_parent(&nodes, to)?;
+        let len = contents.len() as u64;
+        nodes.insert(
+            to.to_path_buf(),
+            MemoryNode::File {
+                contents,
+                modified: SystemTime::now(),
+            },
+        );
+        // Like `std::fs::copy`: permission bits are copied, the owner is the caller
+        let mut attrs = self.attrs.lock().unwrap();
+        let mode = attrs.get(from).and_then(|(_, mode)| *mode);
+        attrs.insert(
+            to.to_path_buf(),
+            (*self.current_owner.lock().unwrap(), mode),
+        );
+        Ok(len)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let path = &Self::resolve(&nodes, path, false)?;
+        match nodes.get(path) {
+            Some(MemoryNode::Dir) => Err(is_a_directory(path)),
+            Some(_) => {
+                nodes.remove(path);
+                self.attrs.lock().unwrap().remove(path);
+                Ok(())
+            }
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let path = &Self::resolve(&nodes, path, false)?;
+        match nodes.get(path) {
+            Some(MemoryNode::Dir) if Self::has_children(&nodes, path) => Err(io::Error::new(
+                io::ErrorKind::DirectoryNotEmpty,
+                format!("{} is not empty", path.display()),
+            )),
+            Some(MemoryNode::Dir) => {
+                nodes.remove(path);
+                self.attrs.lock().unwrap().remove(path);
+                Ok(())
+            }
+            Some(_) => Err(not_a_directory(path)),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let path = &Self::resolve(&nodes, path, true)?;
+        if let Some(existing) = path
+            .ancestors()
+            .find(|a| nodes.get(*a).is_some_and(|n| !matches!(n, MemoryNode::Dir)))
+        {
+            return Err(not_a_directory(existing));
+        }
+        let created: Vec<PathBuf> = path
+            .ancestors()
+            .filter(|a| !a.as_os_str().is_empty() && !Self::node_exists(&nodes, a))
+            .map(Path::to_path_buf)
+            .collect();
+        Self::insert_dir_all(&mut nodes, path);
+        for dir in created {
+            self.mark_created(dir);
+        }
+        Ok(())
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let contents = self.read(path)?;
+        String::from_utf8(contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn ReadSeek>> {
+        Ok(Box::new(io::Cursor::new(self.read(path)?)))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let path = &Self::resolve(&nodes, path, true)?;
+        Self::require_parent(&nodes, path)?;
+        if matches!(nodes.get(path), Some(MemoryNode::Dir)) {
+            return Err(is_a_directory(path));
+        }
+        let previous = nodes.insert(
+            path.to_path_buf(),
+            MemoryNode::File {
+                contents: contents.to_vec(),
+                modified: SystemTime::now(),
+            },
+        );
+        if previous.is_none() {
+            self.mark_created(path.to_path_buf());
+        }
+        Ok(())
+    }
+
+    fn write_from(&self, path: &Path, contents: &mut dyn Read) -> io::Result<u64> {
+        let mut data = Vec::new();
+        contents.read_to_end(&mut data)?;
+        self.write(path, &data)?;
+        Ok(data.len() as u64)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let nodes = self.nodes.lock().unwrap();
+        let resolved = Self::resolve(&nodes, path, false)?;
+        match nodes.get(&resolved) {
+            Some(MemoryNode::Symlink { target }) => Ok(target.clone()),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a symlink", path.display()),
+            )),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let link = Self::resolve(&nodes, link, false)?;
+        Self::require_parent(&nodes, &link)?;
+        if Self::node_exists(&nodes, &link) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", link.display()),
+            ));
+        }
+        nodes.insert(
+            link.clone(),
+            MemoryNode::Symlink {
+                target: target.to_path_buf(),
+            },
+        );
+        self.mark_created(link);
+        Ok(())
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let nodes = self.nodes.lock().unwrap();
+        let resolved = Self::resolve(&nodes, path, true)?;
+        if !Self::node_exists(&nodes, &resolved) {
+            return Err(not_found(path));
+        }
+        Ok(resolved)
+    }
+
+    fn set_owner(&self, path: &Path, owner: Owner) -> io::Result<()> {
+        let nodes = self.nodes.lock().unwrap();
+        let path = Self::resolve(&nodes, path, false)?;
+        if !Self::node_exists(&nodes, &path) {
+            return Err(not_found(&path));
+        }
+        self.attrs.lock().unwrap().entry(path).or_default().0 = Some(owner);
+        Ok(())
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+        let nodes = self.nodes.lock().unwrap();
+        let path = Self::resolve(&nodes, path, true)?;
+        if !Self::node_exists(&nodes, &path) {
+            return Err(not_found(&path));
+        }
+        self.attrs.lock().unwrap().entry(path).or_default().1 = Some(mode);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_rename_moves_descendants() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/home/a/Downloads/album/track.mp3", "x");
+        fs.add_dir("/home/a/Music");
+
+        fs.rename(
+            Path::new("/home/a/Downloads/album"),
+            Path::new("/home/a/Music/album"),
+        )
+        .unwrap();
+
+        assert!(fs.exists(Path::new("/home/a/Music/album/track.mp3")));
+        assert!(!fs.exists(Path::new("/home/a/Downloads/album")));
+    }
+
+    #[test]
+    fn test_memory_rename_requires_parent() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/a/file.txt", "x");
+
+        let err = fs
+            .rename(Path::new("/a/file.txt"), Path::new("/missing/file.txt"))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_memory_read_dir_and_remove() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/d/one.txt", "1");
+        fs.add_file("/d/sub/two.txt", "22");
+
+        let mut children = fs.read_dir(Path::new("/d")).unwrap();
+        children.sort();
+        assert_eq!(
+            children,
+            vec![PathBuf::from("/d/one.txt"), PathBuf::from("/d/sub")]
+        );
+        assert_eq!(fs.metadata(Path::new("/d/sub/two.txt")).unwrap().len, 2);
+
+        assert!(fs.remove_dir(Path::new("/d/sub")).is_err());
+        fs.remove_file(Path::new("/d/sub/two.txt")).unwrap();
+        fs.remove_dir(Path::new("/d/sub")).unwrap();
+        assert!(!fs.exists(Path::new("/d/sub")));
+    }
+
+    #[test]
+    fn test_memory_symlinks_resolve() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/data/music/a.mp3", "abc");
+        fs.add_symlink("/home/a/Downloads/library", "../../../data/music");
+        fs.add_symlink("/home/a/Downloads/loop", "/home/a/Downloads/loop");
+
+        let link = Path::new("/home/a/Downloads/library");
+        assert!(fs.symlink_metadata(link).unwrap().is_symlink());
+        assert!(fs.metadata(link).unwrap().is_dir());
+        assert_eq!(
+            fs.read_dir(link).unwrap(),
+            vec![PathBuf::from("/home/a/Downloads/library/a.mp3")]
+        );
+        assert_eq!(
+            fs.canonicalize(&link.join("a.mp3")).unwrap(),
+            PathBuf::from("/data/music/a.mp3")
+        );
+        assert!(fs.metadata(Path::new("/home/a/Downloads/loop")).is_err());
+
+        // Renaming moves the link, not its target
+        fs.rename(link, Path::new("/home/a/library")).unwrap();
+        assert!(fs.exists(Path::new("/data/music/a.mp3")));
+        assert_eq!(
+            fs.read_link(Path::new("/home/a/library")).unwrap(),
+            PathBuf::from("../../../data/music")
+        );
+    }
+}
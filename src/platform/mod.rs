@@ -1 +1,5 @@
+pub mod audit;
+#[cfg(unix)]
+pub mod dirfd;
+pub mod filesystem;
 pub mod user;
@@ -1,12 +1,37 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 
-pub trait UserProvider {
+pub trait UserProvider: Send + Sync {
     /// Returns a list of usernames available on the system.
     fn list_users(&self) -> Vec<String>;
     /// Returns the home directory for a given username, or None if not found.
     fn user_home(&self, username: &str) -> Option<PathBuf>;
 }
 
+/// Returns the user provider for the platform the binary was built for.
+pub fn system_user_provider() -> Arc<dyn UserProvider> {
+    #[cfg(target_os = "windows")]
+    let provider = WindowsUserProvider;
+    #[cfg(target_os = "macos")]
+    let provider = MacUserProvider;
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let provider = UnixUserProvider;
+    Arc::new(provider)
+}
+
+/// Effective uid of the process, `None` where the platform has no numeric users.
+#[cfg(unix)]
+pub fn effective_uid() -> Option<u32> {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    Some(unsafe { libc::geteuid() })
+}
+
+#[cfg(not(unix))]
+pub fn effective_uid() -> Option<u32> {
+    None
+}
+
 #[cfg(target_os = "windows")]
 pub struct WindowsUserProvider;
 
@@ -93,3 +118,32 @@ impl UserProvider for UnixUserProvider {
         }
     }
 }
+
+/// User provider backed by a fixed username to home directory map.
+///
+/// Useful for tests and for organizing arbitrary directory trees that are not
+/// real user homes.
+#[derive(Debug, Default, Clone)]
+pub struct StaticUserProvider {
+    homes: BTreeMap<String, PathBuf>,
+}
+
+impl StaticUserProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_user(mut self, username: impl Into<String>, home: impl Into<PathBuf>) -> Self {
+        self.homes.insert(username.into(), home.into());
+        self
+    }
+}
+
+impl UserProvider for StaticUserProvider {
+    fn list_users(&self) -> Vec<String> {
+        self.homes.keys().cloned().collect()
+    }
+    fn user_home(&self, username: &str) -> Option<PathBuf> {
+        self.homes.get(username).cloned()
+    }
+}
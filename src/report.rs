@@ -0,0 +1,475 @@
+//! Machine-readable exports of a run: JSON, CSV and a self-contained HTML page
+
+use crate::error::{Error, Result};
+use crate::organizer::types::{Category, ItemStatus, MultiUserReport, RunReport};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// Output format of an exported report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Json,
+    Csv,
+    Html,
+}
+
+impl ReportFormat {
+    pub const ALL: [ReportFormat; 3] = [ReportFormat::Json, ReportFormat::Csv, ReportFormat::Html];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Json => "json",
+            ReportFormat::Csv => "csv",
+            ReportFormat::Html => "html",
+        }
+    }
+
+    /// Format matching the extension of `path`, if it is a known one.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "htm" => Some(ReportFormat::Html),
+            other => other.parse().ok(),
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ReportFormat::ALL
+            .into_iter()
+            .find(|f| f.extension().eq_ignore_ascii_case(s))
+            .ok_or_else(|| Error::InvalidConfig {
+                message: format!("unknown report format `{s}`, expected json, csv or html"),
+            })
+    }
+}
+
+/// A run together with where and when it happened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedReport {
+    pub host: String,
+    /// RFC 3339 timestamp of the export.
+    pub generated_at: String,
+    #[serde(flatten)]
+    pub run: MultiUserReport,
+}
+
+impl ExportedReport {
+    /// Wraps a run with the current host name and time.
+    pub fn new(run: MultiUserReport) -> Self {
+        Self {
+            host: sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string()),
+            generated_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            run,
+        }
+    }
+
+    /// Renders the report in `format`.
+    pub fn render(&self, format: ReportFormat) -> Result<String> {
+        match format {
+            ReportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            ReportFormat::Csv => Ok(render_csv(self)),
+            ReportFormat::Html => Ok(render_html(self)),
+        }
+    }
+
+    /// Writes the report to `path`, in `format` or the one its extension names.
+    pub fn save(&self, path: &Path, format: Option<ReportFormat>) -> Result<ReportFormat> {
+        let format = format
+            .or_else(|| ReportFormat::from_path(path))
+            .ok_or_else(|| Error::InvalidConfig {
+                message: format!(
+                    "cannot tell the report format of {}, use .json, .csv or .html",
+                    path.display()
+                ),
+            })?;
+        std::fs::write(path, self.render(format)?).map_err(|source| Error::FileOperation {
+            operation: "save report".to_string(),
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(format)
+    }
+}
+
+const CSV_HEADER: &str = "record,user,action,status,category,source,destination,detail";
+
+/// One row per item, skipped entry, unrecognised extension and error, then
+/// per-user and overall totals.
+fn render_csv(export: &ExportedReport) -> String {
+    let mut out = String::from(CSV_HEADER);
+    out.push('\n');
+    let mut row = |fields: [&str; 8]| {
+        let escaped: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&escaped.join(","));
+        out.push('\n');
+    };
+    for user in &export.run.users {
+        let name = user.username.as_str();
+        if let Some(error) = &user.error {
+            row(["error", name, "", "", "", "", "", error]);
+        }
+        let Some(report) = &user.report else {
+            continue;
+        };
+        for item in &report.items {
+            let (status, detail) = status_parts(&item.status);
+            let category = item.action.category.map(|c| c.as_str()).unwrap_or("");
+            let source = item.action.source.display().to_string();
+            let destination = item
+                .action
+                .destination
+                .as_ref()
+                .map(|d| d.display().to_string())
+                .unwrap_or_default();
+            row([
+                "item",
+                name,
+                action_name(item.action.kind),
+                status,
+                category,
+                &source,
+                &destination,
+                detail,
+            ]);
+        }
+        for skipped in &report.skipped {
+            let source = skipped.path.display().to_string();
+            let reason = skipped.reason.to_string();
+            row(["skipped", name, "", "", "", &source, "", &reason]);
+        }
+        for (extension, tally) in &report.unrecognized {
+            let detail = format!("{} files, {} bytes", tally.files, tally.bytes);
+            row(["unrecognized", name, "", "", "", extension, "", &detail]);
+        }
+        for category in Category::ALL {
+            let count = report.stats.get(category).to_string();
+            row(["total", name, "", "", category.as_str(), "", "", &count]);
+        }
+        let cleaned = report.stats.cleaned.to_string();
+        row(["cleaned", name, "", "", "", "", "", &cleaned]);
+    }
+    for category in Category::ALL {
+        let count = export.run.totals.get(category).to_string();
+        row(["total", "", "", "", category.as_str(), "", "", &count]);
+    }
+    let cleaned = export.run.totals.cleaned.to_string();
+    row(["cleaned", "", "", "", "", "", "", &cleaned]);
+    out
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) || field.trim() != field {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn action_name(kind: crate::organizer::types::ActionKind) -> &'static str {
+    use crate::organizer::types::ActionKind;
+    match kind {
+        ActionKind::MoveFile => "move_file",
+        ActionKind::MoveFolder => "move_folder",
+        ActionKind::MoveLink => "move_link",
+        ActionKind::DeleteEmpty => "delete_empty",
+        ActionKind::RemoveEmptyDirs => "remove_empty_dirs",
+        ActionKind::ExtractArchive => "extract_archive",
+        ActionKind::TrashStale => "trash_stale",
+        ActionKind::MoveStale => "move_stale",
+    }
+}
+
+fn status_parts(status: &ItemStatus) -> (&'static str, &str) {
+    match status {
+        ItemStatus::Moved => ("moved", ""),
+        ItemStatus::Deleted => ("deleted", ""),
+        ItemStatus::DryRun => ("dry_run", ""),
+        ItemStatus::Skipped { reason } => ("skipped", reason),
+        ItemStatus::Failed { error } => ("failed", error),
+    }
+}
+
+const HTML_STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin-bottom:1.5em}\
+th,td{border:1px solid #ccc;padding:.3em .6em;text-align:left}\
+th{background:#f0f0f0}td.n{text-align:right}\
+.failed{color:#b00020}.skipped{color:#8a6d00}";
+
+fn render_html(export: &ExportedReport) -> String {
+    let mut out = String::new();
+    let title = format!("Rustganizer report for {}", escape_html(&export.host));
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title>\
+         <style>{HTML_STYLE}</style></head><body>\n<h1>{title}</h1>\n<p>Generated {}</p>\n",
+        escape_html(&export.generated_at)
+    );
+
+    out.push_str("<h2>Totals</h2>\n<table><tr><th>User</th>");
+    for category in Category::ALL {
+        let _ = write!(out, "<th>{}</th>", category.logical_dir());
+    }
+    out.push_str("<th>Cleaned</th><th>Errors</th></tr>\n");
+    for user in &export.run.users {
+        let _ = write!(out, "<tr><td>{}</td>", escape_html(&user.username));
+        match &user.report {
+            Some(report) => {
+                for category in Category::ALL {
+                    let _ = write!(out, "<td class=\"n\">{}</td>", report.stats.get(category));
+                }
+                let _ = write!(out, "<td class=\"n\">{}</td>", report.stats.cleaned);
+                let _ = write!(out, "<td class=\"n\">{}</td>", report.errors.len());
+            }
+            None => {
+                let _ = write!(
+                    out,
+                    "<td colspan=\"{}\" class=\"failed\">{}</td>",
+                    Category::ALL.len() + 2,
+                    escape_html(user.error.as_deref().unwrap_or("not organized"))
+                );
+            }
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("<tr><th>Total</th>");
+    for category in Category::ALL {
+        let _ = write!(
+            out,
+            "<th class=\"n\">{}</th>",
+            export.run.totals.get(category)
+        );
+    }
+    let _ = write!(
+        out,
+        "<th class=\"n\">{}</th><th class=\"n\">{}</th></tr>\n</table>\n",
+        export.run.totals.cleaned,
+        export.run.errors().len()
+    );
+
+    for user in &export.run.users {
+        if let Some(report) = &user.report {
+            render_html_user(&mut out, report);
+        }
+    }
+    out.push_str("</body></html>\n");
+    out
+}
+
+fn render_html_user(out: &mut String, report: &RunReport) {
+    let _ = writeln!(out, "<h2>{}</h2>", escape_html(&report.username));
+    if report.dry_run {
+        out.push_str("<p>Dry run, nothing was changed.</p>\n");
+    }
+    if report.cancelled {
+        out.push_str("<p class=\"skipped\">The run was cancelled.</p>\n");
+    }
+    if !report.items.is_empty() {
+        out.push_str(
+            "<table><tr><th>Action</th><th>Status</th><th>Category</th>\
+             <th>Source</th><th>Destination</th><th>Detail</th></tr>\n",
+        );
+        for item in &report.items {
+            let (status, detail) = status_parts(&item.status);
+            let _ = writeln!(
+                out,
+                "<tr class=\"{status}\"><td>{}</td><td>{status}</td><td>{}</td>\
+                 <td>{}</td><td>{}</td><td>{}</td></tr>",
+                action_name(item.action.kind),
+                item.action.category.map(|c| c.as_str()).unwrap_or(""),
+                escape_html(&item.action.source.display().to_string()),
+                escape_html(
+                    &item
+                        .action
+                        .destination
+                        .as_ref()
+                        .map(|d| d.display().to_string())
+                        .unwrap_or_default()
+                ),
+                escape_html(detail),
+            );
+        }
+        out.push_str("</table>\n");
+    }
+    if !report.skipped.is_empty() {
+        out.push_str("<h3>Left alone</h3>\n<table><tr><th>Path</th><th>Reason</th></tr>\n");
+        for skipped in &report.skipped {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td></tr>",
+                escape_html(&skipped.path.display().to_string()),
+                escape_html(&skipped.reason.to_string())
+            );
+        }
+        out.push_str("</table>\n");
+    }
+    if !report.unrecognized.is_empty() {
+        out.push_str(
+            "<h3>Unrecognised extensions</h3>\n\
+             <table><tr><th>Extension</th><th>Files</th><th>Bytes</th></tr>\n",
+        );
+        for (extension, tally) in &report.unrecognized {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td></tr>",
+                escape_html(extension),
+                tally.files,
+                tally.bytes
+            );
+        }
+        out.push_str("</table>\n");
+    }
+    if !report.errors.is_empty() {
+        out.push_str("<h3>Errors</h3>\n<ul class=\"failed\">\n");
+        for error in &report.errors {
+            let _ = writeln!(out, "<li>{}</li>", escape_html(error));
+        }
+        out.push_str("</ul>\n");
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::organizer::types::{
+        ActionKind, ExtensionTally, FileStats, ItemOutcome, PlannedAction, SkipReason,
+        SkippedEntry, UserRunResult,
+    };
+    use std::path::PathBuf;
+
+    fn sample() -> ExportedReport {
+        let stats = FileStats {
+            music: 1,
+            ..FileStats::default()
+        };
+        let alice = RunReport {
+            username: "alice".to_string(),
+            stats: stats.clone(),
+            items: vec![
+                ItemOutcome {
+                    action: PlannedAction {
+                        kind: ActionKind::MoveFile,
+                        source: PathBuf::from("/home/alice/Downloads/a, \"b\".mp3"),
+                        destination: Some(PathBuf::from("/home/alice/Music/a, \"b\".mp3")),
+                        category: Some(Category::Music),
+                        size: 4,
+                    },
+                    status: ItemStatus::Moved,
+                },
+                ItemOutcome {
+                    action: PlannedAction {
+                        kind: ActionKind::MoveFile,
+                        source: PathBuf::from("/home/alice/Downloads/<x>.png"),
+                        destination: Some(PathBuf::from("/home/alice/Pictures/<x>.png")),
+                        category: Some(Category::Images),
+                        size: 4,
+                    },
+                    status: ItemStatus::Failed {
+                        error: "disk full".to_string(),
+                    },
+                },
+            ],
+            skipped: vec![SkippedEntry {
+                path: PathBuf::from("/home/alice/Downloads/repo"),
+                reason: SkipReason::MixedFolder,
+            }],
+            errors: vec!["disk full".to_string()],
+            unrecognized: [(
+                "xyz".to_string(),
+                ExtensionTally {
+                    files: 2,
+                    bytes: 30,
+                },
+            )]
+            .into(),
+            ..RunReport::default()
+        };
+        ExportedReport {
+            host: "ws-01".to_string(),
+            generated_at: "2025-01-01T00:00:00Z".to_string(),
+            run: MultiUserReport {
+                totals: stats,
+                users: vec![
+                    UserRunResult {
+                        username: "alice".to_string(),
+                        report: Some(alice),
+                        error: None,
+                        ..UserRunResult::default()
+                    },
+                    UserRunResult {
+                        username: "bob".to_string(),
+                        report: None,
+                        error: Some("User not found: bob".to_string()),
+                        ..UserRunResult::default()
+                    },
+                ],
+            },
+        }
+    }
+
+    #[test]
+    fn test_csv_quotes_fields_and_lists_totals() {
+        let csv = sample().render(ReportFormat::Csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            "item,alice,move_file,moved,music,\"/home/alice/Downloads/a, \"\"b\"\".mp3\",\
+             \"/home/alice/Music/a, \"\"b\"\".mp3\","
+        );
+        assert!(lines.contains(&"item,alice,move_file,failed,image,/home/alice/Downloads/<x>.png,/home/alice/Pictures/<x>.png,disk full"));
+        assert!(lines.contains(&"skipped,alice,,,,/home/alice/Downloads/repo,,mixed folder"));
+        assert!(lines.contains(&"unrecognized,alice,,,,xyz,,\"2 files, 30 bytes\""));
+        assert!(lines.contains(&"error,bob,,,,,,User not found: bob"));
+        assert!(lines.contains(&"total,alice,,,music,,,1"));
+        assert!(lines.contains(&"total,,,,code,,,0"));
+        assert_eq!(lines.last(), Some(&"cleaned,,,,,,,0"));
+    }
+
+    #[test]
+    fn test_html_is_escaped_and_self_contained() {
+        let html = sample().render(ReportFormat::Html).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("&lt;x&gt;.png"));
+        assert!(!html.contains("<x>"));
+        assert!(html.contains("User not found: bob"));
+        assert!(!html.contains("<link") && !html.contains("<script"));
+    }
+
+    #[test]
+    fn test_json_round_trips_and_format_follows_extension() {
+        let json = sample().render(ReportFormat::Json).unwrap();
+        let parsed: ExportedReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.host, "ws-01");
+        assert_eq!(parsed.run.users.len(), 2);
+
+        assert_eq!(
+            ReportFormat::from_path(Path::new("run.HTM")),
+            Some(ReportFormat::Html)
+        );
+        assert_eq!(ReportFormat::from_path(Path::new("run.txt")), None);
+        assert!("xml".parse::<ReportFormat>().is_err());
+    }
+}
@@ -0,0 +1,651 @@
+//! Unattended runs of the headless `organize` command
+//!
+//! Schedules are kept in the configuration. Installing one generates a
+//! systemd user timer or a crontab entry invoking the CLI; schedules with the
+//! built-in backend are run by [`run_loop`] instead, for systems with neither.
+
+use crate::error::{Error, Result};
+use chrono::{Datelike, Duration, Local, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// Marks the crontab lines a schedule owns, followed by its name.
+const CRON_MARKER: &str = "# rustganizer:";
+
+/// A recurring organize run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    /// Identifies the schedule and names its units; letters, digits, `-` and `_`
+    pub name: String,
+    pub when: Trigger,
+    /// Users to organize; empty organizes every user
+    #[serde(default)]
+    pub users: Vec<String>,
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub backend: Backend,
+}
+
+/// When a schedule runs, written as `hourly`, `daily HH:MM`,
+/// `weekly <day> HH:MM` or `login`, in local time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Trigger {
+    Hourly,
+    Daily(NaiveTime),
+    Weekly(Weekday, NaiveTime),
+    /// When the user logs in, or when the built-in loop starts
+    Login,
+}
+
+/// What runs a schedule
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// A systemd user timer, or a service started at login
+    #[default]
+    Systemd,
+    /// A line in the user's crontab
+    Cron,
+    /// `rustganizer schedule run`
+    Builtin,
+}
+
+/// The command line a schedule invokes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub executable: PathBuf,
+    pub config: PathBuf,
+    pub history_file: Option<PathBuf>,
+}
+
+impl Schedule {
+    /// Checks the name, which ends up in file names and crontab markers.
+    pub fn validate(&self) -> Result<()> {
+        let valid = !self.name.is_empty()
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(Error::InvalidConfig {
+                message: format!(
+                    "invalid schedule name `{}`: use letters, digits, `-` and `_`",
+                    self.name
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Base name of the systemd units, `rustganizer-<name>`.
+    pub fn unit_name(&self) -> String {
+        format!("rustganizer-{}", self.name)
+    }
+
+    /// Arguments of the organize run, starting with the executable.
+    pub fn arguments(&self, invocation: &Invocation) -> Vec<String> {
+        let mut args = vec![
+            invocation.executable.display().to_string(),
+            "--config".to_string(),
+            invocation.config.display().to_string(),
+        ];
+        if let Some(history) = &invocation.history_file {
+            args.extend(["--history-file".to_string(), history.display().to_string()]);
+        }
+        args.push("organize".to_string());
+        if self.users.is_empty() {
+            args.push("--all".to_string());
+        } else {
+            args.extend(self.users.iter().cloned());
+        }
+        if self.dry_run {
+            args.push("--dry-run".to_string());
+        }
+        args
+    }
+
+    /// The `.service` unit and, unless the schedule runs at login, the `.timer` unit.
+    pub fn systemd_units(&self, invocation: &Invocation) -> (String, Option<String>) {
+        let exec: Vec<String> = self
+            .arguments(invocation)
+            .iter()
+            .map(|arg| systemd_quote(arg))
+            .collect();
+        let mut service = format!(
+            "[Unit]\nDescription=Rustganizer schedule {}\n\n\
+             [Service]\nType=oneshot\nExecStart={}\n",
+            self.name,
+            exec.join(" ")
+        );
+        let Some(calendar) = self.when.on_calendar() else {
+            service.push_str("\n[Install]\nWantedBy=default.target\n");
+            return (service, None);
+        };
+        let timer = format!(
+            "[Unit]\nDescription=Rustganizer schedule {} ({})\n\n\
+             [Timer]\nOnCalendar={calendar}\nPersistent=true\n\n\
+             [Install]\nWantedBy=timers.target\n",
+            self.name, self.when
+        );
+        (service, Some(timer))
+    }
+
+    /// The crontab line running the schedule, marked with its name.
+    pub fn crontab_line(&self, invocation: &Invocation) -> String {
+        let command: Vec<String> = self
+            .arguments(invocation)
+            .iter()
+            .map(|arg| shell_quote(arg).replace('%', "\\%"))
+            .collect();
+        format!(
+            "{} {} {CRON_MARKER}{}",
+            self.when.cron_fields(),
+            command.join(" "),
+            self.name
+        )
+    }
+}
+
+impl Trigger {
+    /// systemd `OnCalendar=` expression; `None` for login.
+    pub fn on_calendar(&self) -> Option<String> {
+        match self {
+            Trigger::Hourly => Some("hourly".to_string()),
+            Trigger::Daily(time) => Some(format!("*-*-* {}", time.format("%H:%M:00"))),
+            Trigger::Weekly(day, time) => Some(format!("{day} *-*-* {}", time.format("%H:%M:00"))),
+            Trigger::Login => None,
+        }
+    }
+
+    /// Time fields of a crontab line; cron knows no logins, so those run at boot.
+    pub fn cron_fields(&self) -> String {
+        match self {
+            Trigger::Hourly => "0 * * * *".to_string(),
+            Trigger::Daily(time) => format!("{} {} * * *", time.minute(), time.hour()),
+            Trigger::Weekly(day, time) => format!(
+                "{} {} * * {}",
+                time.minute(),
+                time.hour(),
+                day.num_days_from_sunday()
+            ),
+            Trigger::Login => "@reboot".to_string(),
+        }
+    }
+
+    /// First time strictly after `after` the trigger fires; `None` for login.
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let next = match self {
+            Trigger::Hourly => {
+                let hour = after.date().and_hms_opt(after.hour(), 0, 0)?;
+                hour + Duration::hours(1)
+            }
+            Trigger::Daily(time) => {
+                let today = after.date().and_time(*time);
+                if today > after {
+                    today
+                } else {
+                    today + Duration::days(1)
+                }
+            }
+            Trigger::Weekly(day, time) => {
+                let ahead = (7 + day.num_days_from_monday() as i64
+                    - after.weekday().num_days_from_monday() as i64)
+                    % 7;
+                let next = after.date().and_time(*time) + Duration::days(ahead);
+                if next > after {
+                    next
+                } else {
+                    next + Duration::days(7)
+                }
+            }
+            Trigger::Login => return None,
+        };
+        Some(next)
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::Hourly => write!(f, "hourly"),
+            Trigger::Daily(time) => write!(f, "daily {}", time.format("%H:%M")),
+            Trigger::Weekly(day, time) => {
+                let day = day.to_string().to_lowercase();
+                write!(f, "weekly {day} {}", time.format("%H:%M"))
+            }
+            Trigger::Login => write!(f, "login"),
+        }
+    }
+}
+
+impl FromStr for Trigger {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = || Error::InvalidConfig {
+            message: format!(
+                "invalid schedule `{value}`: use hourly, daily HH:MM, weekly <day> HH:MM or login"
+            ),
+        };
+        let time = |text: &str| NaiveTime::parse_from_str(text, "%H:%M").map_err(|_| invalid());
+        let words: Vec<String> = value.split_whitespace().map(str::to_lowercase).collect();
+        match words.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["hourly"] => Ok(Trigger::Hourly),
+            ["daily", at] => Ok(Trigger::Daily(time(at)?)),
+            ["weekly", day, at] => Ok(Trigger::Weekly(
+                day.parse().map_err(|_| invalid())?,
+                time(at)?,
+            )),
+            ["login"] => Ok(Trigger::Login),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for Trigger {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<Trigger> for String {
+    fn from(trigger: Trigger) -> Self {
+        trigger.to_string()
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Backend::Systemd => "systemd",
+            Backend::Cron => "cron",
+            Backend::Builtin => "builtin",
+        })
+    }
+}
+
+impl FromStr for Backend {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "systemd" => Ok(Backend::Systemd),
+            "cron" => Ok(Backend::Cron),
+            "builtin" => Ok(Backend::Builtin),
+            _ => Err(Error::InvalidConfig {
+                message: format!("unknown schedule backend `{value}`"),
+            }),
+        }
+    }
+}
+
+/// Where systemd looks for the user's units, `systemd/user` in the config directory.
+pub fn systemd_unit_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("systemd").join("user"))
+}
+
+/// Replaces the crontab lines of schedule `name` in `crontab` with `line`, or
+/// removes them when `line` is `None`.
+pub fn merge_crontab(crontab: &str, name: &str, line: Option<&str>) -> String {
+    let marker = format!("{CRON_MARKER}{name}");
+    let mut lines: Vec<&str> = crontab
+        .lines()
+        .filter(|l| !l.trim_end().ends_with(&marker))
+        .collect();
+    lines.extend(line);
+    let mut merged = lines.join("\n");
+    if !merged.is_empty() {
+        merged.push('\n');
+    }
+    merged
+}
+
+/// Names of the schedules with a line in `crontab`.
+pub fn crontab_schedules(crontab: &str) -> Vec<String> {
+    crontab
+        .lines()
+        .filter_map(|line| line.rsplit_once(CRON_MARKER))
+        .map(|(_, name)| name.trim().to_string())
+        .collect()
+}
+
+/// Installs `schedule` with its backend; built-in schedules need nothing.
+pub fn install(schedule: &Schedule, invocation: &Invocation) -> Result<()> {
+    schedule.validate()?;
+    match schedule.backend {
+        Backend::Systemd => {
+            let dir = systemd_unit_dir().ok_or_else(no_config_dir)?;
+            let unit = write_units(schedule, invocation, &dir)?;
+            systemctl(&["daemon-reload"])?;
+            match schedule.when {
+                Trigger::Login => systemctl(&["enable", &unit]),
+                _ => systemctl(&["enable", "--now", &unit]),
+            }
+        }
+        Backend::Cron => {
+            let crontab = read_crontab()?;
+            let line = schedule.crontab_line(invocation);
+            write_crontab(&merge_crontab(&crontab, &schedule.name, Some(&line)))
+        }
+        Backend::Builtin => Ok(()),
+    }
+}
+
+/// Removes what [`install`] set up for `schedule`; a missing unit or line is fine.
+pub fn uninstall(schedule: &Schedule) -> Result<()> {
+    match schedule.backend {
+        Backend::Systemd => {
+            let dir = systemd_unit_dir().ok_or_else(no_config_dir)?;
+            let unit_name = schedule.unit_name();
+            let unit = match schedule.when {
+                Trigger::Login => format!("{unit_name}.service"),
+                _ => format!("{unit_name}.timer"),
+            };
+            if dir.join(&unit).exists() {
+                systemctl(&["disable", "--now", &unit])?;
+            }
+            for suffix in ["timer", "service"] {
+                let path = dir.join(format!("{unit_name}.{suffix}"));
+                match std::fs::remove_file(&path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                        return Err(Error::FileOperation {
+                            operation: "remove unit".to_string(),
+                            path,
+                            source: e,
+                        })
+                    }
+                    _ => {}
+                }
+            }
+            systemctl(&["daemon-reload"])
+        }
+        Backend::Cron => {
+            let crontab = read_crontab()?;
+            let merged = merge_crontab(&crontab, &schedule.name, None);
+            if merged == crontab {
+                return Ok(());
+            }
+            write_crontab(&merged)
+        }
+        Backend::Builtin => Ok(()),
+    }
+}
+
+/// Whether `schedule` is set up with its backend; `crontab` is the user's
+/// current crontab. Built-in schedules count as installed.
+pub fn is_installed(schedule: &Schedule, unit_dir: Option<&Path>, crontab: &str) -> bool {
+    match schedule.backend {
+        Backend::Systemd => unit_dir.is_some_and(|dir| {
+            dir.join(format!("{}.service", schedule.unit_name()))
+                .exists()
+        }),
+        Backend::Cron => crontab_schedules(crontab).contains(&schedule.name),
+        Backend::Builtin => true,
+    }
+}
+
+/// Writes the units of `schedule` to `dir` and returns the one to enable.
+pub fn write_units(schedule: &Schedule, invocation: &Invocation, dir: &Path) -> Result<String> {
+    let file_error = |path: &Path, source| Error::FileOperation {
+        operation: "write unit".to_string(),
+        path: path.to_path_buf(),
+        source,
+    };
+    std::fs::create_dir_all(dir).map_err(|e| file_error(dir, e))?;
+    let (service, timer) = schedule.systemd_units(invocation);
+    let service_name = format!("{}.service", schedule.unit_name());
+    let service_path = dir.join(&service_name);
+    std::fs::write(&service_path, service).map_err(|e| file_error(&service_path, e))?;
+    let Some(timer) = timer else {
+        return Ok(service_name);
+    };
+    let timer_name = format!("{}.timer", schedule.unit_name());
+    let timer_path = dir.join(&timer_name);
+    std::fs::write(&timer_path, timer).map_err(|e| file_error(&timer_path, e))?;
+    Ok(timer_name)
+}
+
+/// The user's crontab, empty when they have none.
+pub fn read_crontab() -> Result<String> {
+    let output = Command::new("crontab")
+        .arg("-l")
+        .env("LC_ALL", "C")
+        .output()
+        .map_err(|e| command_error("crontab -l", e))?;
+    if !output.status.success() {
+        // `crontab -l` also fails when there is no crontab yet
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("no crontab for") {
+            return Ok(String::new());
+        }
+        return Err(Error::Schedule {
+            message: format!(
+                "crontab -l exited with {}: {}",
+                output.status,
+                stderr.trim()
+            ),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn write_crontab(crontab: &str) -> Result<()> {
+    let mut child = Command::new("crontab")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| command_error("crontab -", e))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin
+        .write_all(crontab.as_bytes())
+        .map_err(|e| command_error("crontab -", e))?;
+    drop(stdin);
+    let status = child.wait().map_err(|e| command_error("crontab -", e))?;
+    if !status.success() {
+        return Err(Error::Schedule {
+            message: format!("crontab - exited with {status}"),
+        });
+    }
+    Ok(())
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let command = format!("systemctl --user {}", args.join(" "));
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()
+        .map_err(|e| command_error(&command, e))?;
+    if !status.success() {
+        return Err(Error::Schedule {
+            message: format!("{command} exited with {status}"),
+        });
+    }
+    Ok(())
+}
+
+fn command_error(command: &str, e: std::io::Error) -> Error {
+    Error::Schedule {
+        message: format!("could not run {command}: {e}"),
+    }
+}
+
+fn no_config_dir() -> Error {
+    Error::Schedule {
+        message: "there is no config directory for systemd units".to_string(),
+    }
+}
+
+/// Runs `run` for each schedule when it is due, in local time, until none
+/// is left to wait for. Login schedules run once when the loop starts.
+pub fn run_loop(schedules: &[Schedule], mut run: impl FnMut(&Schedule)) {
+    for schedule in schedules.iter().filter(|s| s.when == Trigger::Login) {
+        run(schedule);
+    }
+    let mut last = Local::now().naive_local();
+    loop {
+        let Some(due) = schedules
+            .iter()
+            .filter_map(|s| s.when.next_after(last))
+            .min()
+        else {
+            return;
+        };
+        // Sleeping in short steps notices suspends and clock changes
+        loop {
+            let now = Local::now().naive_local();
+            if now >= due {
+                break;
+            }
+            let wait = (due - now).to_std().unwrap_or_default();
+            std::thread::sleep(wait.min(std::time::Duration::from_secs(60)));
+        }
+        for schedule in schedules
+            .iter()
+            .filter(|s| s.when.next_after(last) == Some(due))
+        {
+            run(schedule);
+        }
+        // Runs missed while these ran are skipped, not caught up
+        last = Local::now().naive_local().max(due);
+    }
+}
+
+/// Quotes `arg` for a POSIX shell when it needs it.
+fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/-_.=:,+@".contains(c))
+    {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Quotes `arg` for `ExecStart=`, escaping systemd's specifiers.
+fn systemd_quote(arg: &str) -> String {
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    if !escaped.is_empty() && !escaped.contains(char::is_whitespace) && escaped == arg {
+        return escaped;
+    }
+    format!("\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn invocation() -> Invocation {
+        Invocation {
+            executable: PathBuf::from("/usr/bin/rustganizer"),
+            config: PathBuf::from("/home/a/.config/rust ganizer/config.toml"),
+            history_file: None,
+        }
+    }
+
+    #[test]
+    fn test_triggers_parse_and_find_their_next_run() {
+        let daily: Trigger = "daily 02:00".parse().unwrap();
+        let weekly: Trigger = "Weekly MON 09:30".parse().unwrap();
+        assert_eq!(weekly.to_string(), "weekly mon 09:30");
+        assert!("daily 25:00".parse::<Trigger>().is_err());
+        assert!("monthly".parse::<Trigger>().is_err());
+
+        // 2025-01-08 was a Wednesday
+        assert_eq!(
+            daily.next_after(at("2025-01-08 01:59")),
+            Some(at("2025-01-08 02:00"))
+        );
+        assert_eq!(
+            daily.next_after(at("2025-01-08 02:00")),
+            Some(at("2025-01-09 02:00"))
+        );
+        assert_eq!(
+            weekly.next_after(at("2025-01-08 12:00")),
+            Some(at("2025-01-13 09:30"))
+        );
+        assert_eq!(
+            weekly.next_after(at("2025-01-06 09:30")),
+            Some(at("2025-01-13 09:30"))
+        );
+        assert_eq!(
+            Trigger::Hourly.next_after(at("2025-01-08 23:15")),
+            Some(at("2025-01-09 00:00"))
+        );
+        assert_eq!(Trigger::Login.next_after(at("2025-01-08 23:15")), None);
+    }
+
+    #[test]
+    fn test_units_and_crontab_lines_invoke_the_cli() {
+        let schedule = Schedule {
+            name: "nightly".to_string(),
+            when: "daily 02:00".parse().unwrap(),
+            users: vec!["alice".to_string()],
+            dry_run: true,
+            backend: Backend::Systemd,
+        };
+        let (service, timer) = schedule.systemd_units(&invocation());
+        assert!(service.contains(
+            "ExecStart=/usr/bin/rustganizer --config \"/home/a/.config/rust ganizer/config.toml\" organize alice --dry-run\n"
+        ), "{service}");
+        assert!(timer.unwrap().contains("OnCalendar=*-*-* 02:00:00\n"));
+        assert_eq!(
+            schedule.crontab_line(&invocation()),
+            "0 2 * * * /usr/bin/rustganizer --config '/home/a/.config/rust ganizer/config.toml' organize alice --dry-run # rustganizer:nightly"
+        );
+
+        let login = Schedule {
+            when: Trigger::Login,
+            users: Vec::new(),
+            ..schedule
+        };
+        let (service, timer) = login.systemd_units(&invocation());
+        assert!(timer.is_none());
+        assert!(service.contains("organize --all --dry-run\n"));
+        assert!(service.contains("WantedBy=default.target"));
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            write_units(&login, &invocation(), dir.path()).unwrap(),
+            "rustganizer-nightly.service"
+        );
+        assert!(is_installed(&login, Some(dir.path()), ""));
+    }
+
+    #[test]
+    fn test_crontab_merge_replaces_only_the_schedule_line() {
+        let crontab = "MAILTO=a\n0 2 * * * old # rustganizer:nightly\n5 * * * * backup\n";
+        let merged = merge_crontab(
+            crontab,
+            "nightly",
+            Some("0 3 * * * new # rustganizer:nightly"),
+        );
+        assert_eq!(
+            merged,
+            "MAILTO=a\n5 * * * * backup\n0 3 * * * new # rustganizer:nightly\n"
+        );
+        assert_eq!(crontab_schedules(&merged), ["nightly"]);
+        assert_eq!(
+            merge_crontab(&merged, "nightly", None),
+            "MAILTO=a\n5 * * * * backup\n"
+        );
+        assert_eq!(merge_crontab("", "nightly", None), "");
+    }
+}
@@ -0,0 +1,440 @@
+//! Analysis browser: per-folder breakdowns and what a run would do with each entry
+//!
+//! Entering a directory lists its entries at once; their analyses are computed
+//! in the background by an [`AsyncAnalyzer`] and shown as they come in.
+//! Leaving the directory cancels whatever is still pending.
+
+use crate::organizer::analyzer::FolderAnalysis;
+use crate::organizer::async_analyzer::AsyncAnalyzer;
+use crate::organizer::engine::Organizer;
+use crate::organizer::types::{ActionKind, CancellationToken, Category, Plan};
+use crate::ui::summary::format_size;
+use cursive::traits::*;
+use cursive::views::{Dialog, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// List value of the row leading to the parent directory
+const PARENT: usize = usize::MAX;
+
+/// What a run would do with `entry`, a direct child of the planned directory.
+pub fn describe_entry(plan: &Plan, entry: &Path) -> String {
+    if let Some(action) = plan.actions.iter().find(|a| a.source == entry) {
+        let destination = action.destination.as_deref().unwrap_or(Path::new(""));
+        return match action.kind {
+            ActionKind::DeleteEmpty => "delete (empty file)".to_string(),
+            ActionKind::RemoveEmptyDirs => {
+                let moved = plan
+                    .actions
+                    .iter()
+                    .filter(|a| a.source != entry && a.source.starts_with(entry))
+                    .count();
+                format!("split: {moved} files moved by category")
+            }
+            ActionKind::MoveFile | ActionKind::MoveFolder | ActionKind::MoveLink => {
+                format!("move to {}", destination.display())
+            }
+            ActionKind::ExtractArchive => format!("extract to {}", destination.display()),
+            ActionKind::TrashStale => "trash (stale)".to_string(),
+            ActionKind::MoveStale => format!("move (stale) to {}", destination.display()),
+        };
+    }
+    if let Some(skipped) = plan.skipped.iter().find(|s| s.path == entry) {
+        return format!("leave: {}", skipped.reason);
+    }
+    "leave: not recognised".to_string()
+}
+
+/// Renders counts and sizes per category, the largest files and the
+/// unrecognised extensions of a tree.
+pub fn analysis_text(analysis: &FolderAnalysis) -> String {
+    let mut lines = vec![format!(
+        "{} files, {}",
+        analysis.files,
+        format_size(analysis.total_bytes)
+    )];
+    lines.push(String::new());
+    lines.push(format!(
+        "{:<10}  {:>6}  {:>10}",
+        "Category", "Files", "Size"
+    ));
+    for category in Category::ALL {
+        lines.push(format!(
+            "{:<10}  {:>6}  {:>10}",
+            category.logical_dir(),
+            analysis.stats.get(category),
+            format_size(analysis.category_bytes(category)),
+        ));
+    }
+    if !analysis.largest.is_empty() {
+        lines.push(String::new());
+        lines.push("Largest files:".to_string());
+        for (path, size) in &analysis.largest {
+            lines.push(format!("{:>10}  {}", format_size(*size), path.display()));
+        }
+    }
+    if !analysis.unrecognized.is_empty() {
+        let mut extensions: Vec<_> = analysis.unrecognized.iter().collect();
+        extensions.sort_by_key(|(_, tally)| std::cmp::Reverse(tally.bytes));
+        lines.push(String::new());
+        lines.push("Unrecognised extensions:".to_string());
+        for (extension, tally) in extensions {
+            let extension = if extension.is_empty() {
+                "(none)".to_string()
+            } else {
+                format!(".{extension}")
+            };
+            lines.push(format!(
+                "{extension:<10}  {:>6}  {:>10}",
+                tally.files,
+                format_size(tally.bytes)
+            ));
+        }
+    }
+    lines.join("\n")
+}
+
+/// One entry of the browsed directory
+struct Entry {
+    path: PathBuf,
+    is_dir: bool,
+    /// Absent until the background analysis reaches the entry.
+    analysis: Option<FolderAnalysis>,
+    decision: Option<String>,
+}
+
+/// The directory being browsed and what is known about its entries so far
+struct Browser {
+    organizer: Arc<Organizer>,
+    username: String,
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    /// Cancels the analysis of the directory when leaving it.
+    token: CancellationToken,
+}
+
+impl Browser {
+    fn totals(&self) -> FolderAnalysis {
+        let mut totals = FolderAnalysis::default();
+        for analysis in self.entries.iter().filter_map(|e| e.analysis.as_ref()) {
+            totals.merge(analysis);
+        }
+        totals
+    }
+
+    fn analyzed(&self) -> usize {
+        self.entries.iter().filter(|e| e.analysis.is_some()).count()
+    }
+}
+
+/// Opens the browser in `dir`, explaining entries as a run for `username` would.
+pub fn show_analysis_browser(
+    s: &mut Cursive,
+    organizer: Arc<Organizer>,
+    username: String,
+    dir: PathBuf,
+) {
+    let browser = Arc::new(Mutex::new(Browser {
+        organizer,
+        username,
+        dir: dir.clone(),
+        entries: Vec::new(),
+        token: CancellationToken::new(),
+    }));
+
+    let mut list = SelectView::<usize>::new();
+    let selected = browser.clone();
+    list.set_on_select(move |s, index: &usize| show_details(s, &selected, *index));
+    let submitted = browser.clone();
+    list.set_on_submit(move |s, index: &usize| open(s, &submitted, *index));
+
+    let closed = browser.clone();
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new("").with_name("browser_status"))
+                .child(
+                    LinearLayout::horizontal()
+                        .child(
+                            list.with_name("browser_list")
+                                .scrollable()
+                                .fixed_size((60, 22)),
+                        )
+                        .child(
+                            TextView::new("")
+                                .with_name("browser_details")
+                                .scrollable()
+                                .fixed_size((60, 22)),
+                        ),
+                ),
+        )
+        .title("Analyze folders")
+        .button("Close", move |s| {
+            closed.lock().unwrap().token.cancel();
+            s.pop_layer();
+        }),
+    );
+    navigate(s, &browser, dir);
+}
+
+/// Enters the selected directory, or the parent for the `..` row.
+fn open(s: &mut Cursive, browser: &Arc<Mutex<Browser>>, index: usize) {
+    let target = {
+        let browser = browser.lock().unwrap();
+        if index == PARENT {
+            browser.dir.parent().map(Path::to_path_buf)
+        } else {
+            browser
+                .entries
+                .get(index)
+                .filter(|e| e.is_dir)
+                .map(|e| e.path.clone())
+        }
+    };
+    if let Some(dir) = target {
+        navigate(s, browser, dir);
+    }
+}
+
+/// Lists `dir` and starts analyzing its entries in the background.
+fn navigate(s: &mut Cursive, browser: &Arc<Mutex<Browser>>, dir: PathBuf) {
+    let fs = browser.lock().unwrap().organizer.filesystem().clone();
+    let mut paths: Vec<(PathBuf, bool)> = match fs.read_dir(&dir) {
+        Ok(entries) => entries
+            .into_iter()
+            .map(|path| {
+                let is_dir = fs.symlink_metadata(&path).is_ok_and(|m| m.is_dir());
+                (path, is_dir)
+            })
+            .collect(),
+        Err(e) => {
+            s.add_layer(Dialog::info(format!("{}: {e}", dir.display())));
+            return;
+        }
+    };
+    // Directories first, then by name
+    paths.sort_by(|(a, a_dir), (b, b_dir)| b_dir.cmp(a_dir).then_with(|| a.cmp(b)));
+
+    let token = CancellationToken::new();
+    let (organizer, username) = {
+        let mut state = browser.lock().unwrap();
+        state.token.cancel();
+        state.token = token.clone();
+        state.dir = dir.clone();
+        state.entries = paths
+            .iter()
+            .map(|(path, is_dir)| Entry {
+                path: path.clone(),
+                is_dir: *is_dir,
+                analysis: None,
+                decision: None,
+            })
+            .collect();
+        (state.organizer.clone(), state.username.clone())
+    };
+    refresh(s, browser, true);
+
+    let cb_sink = s.cb_sink().clone();
+    let browser = browser.clone();
+    std::thread::spawn(move || {
+        let config = organizer.config().clone();
+        // Each update applies only while the directory is still the one shown
+        let send = |update: Box<dyn FnOnce(&mut Browser) + Send>| {
+            let browser = browser.clone();
+            let token = token.clone();
+            cb_sink
+                .send(Box::new(move |s| {
+                    if token.is_cancelled() {
+                        return;
+                    }
+                    update(&mut browser.lock().unwrap());
+                    refresh(s, &browser, false);
+                }))
+                .is_ok()
+        };
+
+        let decisions: HashMap<PathBuf, String> = match organizer.explain(&username, &dir) {
+            Ok(plan) => paths
+                .iter()
+                .map(|(path, _)| (path.clone(), describe_entry(&plan, path)))
+                .collect(),
+            Err(e) => paths
+                .iter()
+                .map(|(path, _)| (path.clone(), e.to_string()))
+                .collect(),
+        };
+        let applied = send(Box::new(move |state| {
+            for entry in &mut state.entries {
+                entry.decision = decisions.get(&entry.path).cloned();
+            }
+        }));
+        if !applied {
+            return;
+        }
+
+        let Ok(runtime) = tokio::runtime::Builder::new_current_thread().build() else {
+            return;
+        };
+        let analyzer = AsyncAnalyzer::new(Arc::new(config));
+        let paths = paths.into_iter().map(|(path, _)| path).collect();
+        let analyses = analyzer.analyze_trees(fs, paths, &token, |index, analysis| {
+            send(Box::new(move |state| {
+                if let Some(entry) = state.entries.get_mut(index) {
+                    entry.analysis = Some(analysis);
+                }
+            }))
+        });
+        runtime.block_on(analyses);
+    });
+}
+
+/// Redraws the status line and the list; `reset` moves the selection to the top.
+fn refresh(s: &mut Cursive, browser: &Arc<Mutex<Browser>>, reset: bool) {
+    let (status, labels) = {
+        let state = browser.lock().unwrap();
+        let totals = state.totals();
+        let status = format!(
+            "{}\nAnalyzed {} of {} entries: {} files, {}",
+            state.dir.display(),
+            state.analyzed(),
+            state.entries.len(),
+            totals.files,
+            format_size(totals.total_bytes),
+        );
+        let labels: Vec<(String, usize)> = state
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (entry_label(entry), index))
+            .collect();
+        (status, labels)
+    };
+    s.call_on_name("browser_status", |v: &mut TextView| v.set_content(status));
+    let selected = s
+        .call_on_name("browser_list", |list: &mut SelectView<usize>| {
+            let selected = if reset { None } else { list.selected_id() };
+            list.clear();
+            list.add_item("..", PARENT);
+            list.add_all(labels);
+            list.set_selection(selected.unwrap_or_default().min(list.len() - 1));
+            list.selection().map(|index| *index)
+        })
+        .flatten();
+    if let Some(index) = selected {
+        show_details(s, browser, index);
+    }
+}
+
+fn entry_label(entry: &Entry) -> String {
+    let mut name = entry
+        .path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if entry.is_dir {
+        name.push('/');
+    }
+    let size = entry
+        .analysis
+        .as_ref()
+        .map_or_else(|| "...".to_string(), |a| format_size(a.total_bytes));
+    let decision = entry.decision.as_deref().unwrap_or("");
+    format!("{name:<24.24} {size:>10}  {decision}")
+}
+
+/// Shows the selected entry, or the whole directory for the `..` row.
+fn show_details(s: &mut Cursive, browser: &Arc<Mutex<Browser>>, index: usize) {
+    let text = {
+        let state = browser.lock().unwrap();
+        match state.entries.get(index) {
+            Some(entry) => {
+                let mut text = format!(
+                    "{}\n{}\n\n",
+                    entry.path.display(),
+                    entry.decision.as_deref().unwrap_or("Planning...")
+                );
+                match &entry.analysis {
+                    Some(analysis) => text.push_str(&analysis_text(analysis)),
+                    None => text.push_str("Analyzing..."),
+                }
+                text
+            }
+            None => format!(
+                "{}\n\n{}",
+                state.dir.display(),
+                analysis_text(&state.totals())
+            ),
+        }
+    };
+    s.call_on_name("browser_details", |v: &mut TextView| v.set_content(text));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::organizer::types::{PlannedAction, SkipReason, SkippedEntry};
+
+    fn action(kind: ActionKind, source: &str, destination: Option<&str>) -> PlannedAction {
+        PlannedAction {
+            kind,
+            source: PathBuf::from(source),
+            destination: destination.map(PathBuf::from),
+            category: destination.map(|_| Category::Music),
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn test_entries_are_described_by_the_plan() {
+        let plan = Plan {
+            actions: vec![
+                action(ActionKind::DeleteEmpty, "/d/empty.txt", None),
+                action(
+                    ActionKind::MoveFile,
+                    "/d/song.mp3",
+                    Some("/h/Music/song.mp3"),
+                ),
+                action(
+                    ActionKind::MoveFile,
+                    "/d/mix/a.mp3",
+                    Some("/h/Music/mix/a.mp3"),
+                ),
+                action(
+                    ActionKind::MoveFile,
+                    "/d/mix/b.png",
+                    Some("/h/Pictures/mix/b.png"),
+                ),
+                action(ActionKind::RemoveEmptyDirs, "/d/mix", None),
+            ],
+            skipped: vec![SkippedEntry {
+                path: PathBuf::from("/d/repo"),
+                reason: SkipReason::ProjectFolder {
+                    marker: ".git".to_string(),
+                },
+            }],
+            ..Plan::default()
+        };
+        let describe = |entry: &str| describe_entry(&plan, Path::new(entry));
+        assert_eq!(describe("/d/empty.txt"), "delete (empty file)");
+        assert_eq!(describe("/d/song.mp3"), "move to /h/Music/song.mp3");
+        assert_eq!(describe("/d/mix"), "split: 2 files moved by category");
+        assert_eq!(describe("/d/repo"), "leave: project folder (.git)");
+        assert_eq!(describe("/d/notes.xyz"), "leave: not recognised");
+    }
+
+    #[test]
+    fn test_analysis_text_lists_unrecognized_extensions_by_size() {
+        let config = crate::config::Config::default();
+        let mut analysis = FolderAnalysis::default();
+        analysis.add_file(Path::new("/d/a.mp3"), 2048, &config);
+        analysis.add_file(Path::new("/d/b.xyz"), 10, &config);
+        analysis.add_file(Path::new("/d/c.abc"), 500, &config);
+        let text = analysis_text(&analysis);
+        assert!(text.starts_with("3 files, 2.5 KiB"));
+        assert!(text.contains("Music            1     2.0 KiB"));
+        let abc = text.find(".abc").unwrap();
+        assert!(abc < text.find(".xyz").unwrap());
+    }
+}
@@ -1 +1,6 @@
+pub mod browser;
+pub mod review;
+pub mod settings;
+pub mod summary;
+pub mod theme;
 pub mod views;
@@ -0,0 +1,498 @@
+//! Plan review: approve, drop or recategorize planned moves before executing them
+
+use crate::organizer::engine::Organizer;
+use crate::organizer::types::{ActionKind, Category, PlannedAction, UserPlan};
+use crate::ui::summary::format_size;
+use cursive::traits::*;
+use cursive::views::{Dialog, EditView, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+use std::sync::{Arc, Mutex};
+
+/// Group of the cleanup actions, which have no category
+const CLEANUP: &str = "cleanup";
+
+/// Position of an action: index of the user plan, then of the action in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemRef {
+    pub user: usize,
+    pub action: usize,
+}
+
+/// Counts and sizes of the actions of one category
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupSummary {
+    /// `None` for cleanup actions such as deleting empty files.
+    pub category: Option<Category>,
+    pub items: usize,
+    pub approved: usize,
+    pub bytes: u64,
+    pub approved_bytes: u64,
+}
+
+impl GroupSummary {
+    pub fn label(&self) -> &'static str {
+        self.category.map_or(CLEANUP, |c| c.as_str())
+    }
+}
+
+/// Plans under review together with which of their actions are approved
+#[derive(Debug, Clone, Default)]
+pub struct PlanReview {
+    plans: Vec<UserPlan>,
+    approved: Vec<Vec<bool>>,
+}
+
+impl PlanReview {
+    /// Starts with every action approved.
+    pub fn new(plans: Vec<UserPlan>) -> Self {
+        let approved = plans
+            .iter()
+            .map(|p| vec![true; p.plan.as_ref().map_or(0, |plan| plan.actions.len())])
+            .collect();
+        Self { plans, approved }
+    }
+
+    pub fn plans(&self) -> &[UserPlan] {
+        &self.plans
+    }
+
+    pub fn action(&self, item: ItemRef) -> Option<&PlannedAction> {
+        self.plans
+            .get(item.user)?
+            .plan
+            .as_ref()?
+            .actions
+            .get(item.action)
+    }
+
+    /// Every action with its position, grouped by category in `Category::ALL`
+    /// order with cleanup last, then by source path.
+    pub fn items(&self) -> Vec<(ItemRef, &PlannedAction)> {
+        let mut items: Vec<(ItemRef, &PlannedAction)> = self
+            .plans
+            .iter()
+            .enumerate()
+            .filter_map(|(user, p)| Some((user, p.plan.as_ref()?)))
+            .flat_map(|(user, plan)| {
+                plan.actions
+                    .iter()
+                    .enumerate()
+                    .map(move |(action, a)| (ItemRef { user, action }, a))
+            })
+            .collect();
+        items.sort_by(|(_, a), (_, b)| {
+            (group_order(a.category), &a.source).cmp(&(group_order(b.category), &b.source))
+        });
+        items
+    }
+
+    pub fn is_approved(&self, item: ItemRef) -> bool {
+        self.approved
+            .get(item.user)
+            .and_then(|a| a.get(item.action))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    pub fn toggle(&mut self, item: ItemRef) {
+        if let Some(approved) = self
+            .approved
+            .get_mut(item.user)
+            .and_then(|a| a.get_mut(item.action))
+        {
+            *approved = !*approved;
+        }
+    }
+
+    /// Approves every action of `category` unless all already are, in which
+    /// case they are all dropped.
+    pub fn toggle_category(&mut self, category: Option<Category>) {
+        let items: Vec<ItemRef> = self
+            .items()
+            .into_iter()
+            .filter(|(_, a)| a.category == category)
+            .map(|(item, _)| item)
+            .collect();
+        let approve = !items.iter().all(|item| self.is_approved(*item));
+        for item in items {
+            self.approved[item.user][item.action] = approve;
+        }
+    }
+
+    /// Moves an action to another category, recomputing its destination.
+    pub fn recategorize(&mut self, organizer: &Organizer, item: ItemRef, category: Category) {
+        if let Some(plan) = self.plans.get_mut(item.user).and_then(|p| p.plan.as_mut()) {
+            organizer.recategorize(plan, item.action, category);
+        }
+    }
+
+    /// One summary per category that has actions, in display order.
+    pub fn groups(&self) -> Vec<GroupSummary> {
+        let mut groups: Vec<GroupSummary> = Vec::new();
+        for (item, action) in self.items() {
+            if groups.last().is_none_or(|g| g.category != action.category) {
+                groups.push(GroupSummary {
+                    category: action.category,
+                    ..GroupSummary::default()
+                });
+            }
+            let group = groups.last_mut().expect("group was just pushed");
+            group.items += 1;
+            group.bytes += action.size;
+            if self.is_approved(item) {
+                group.approved += 1;
+                group.approved_bytes += action.size;
+            }
+        }
+        groups
+    }
+
+    /// The plans reduced to their approved actions.
+    pub fn approved_plans(&self) -> Vec<UserPlan> {
+        self.plans
+            .iter()
+            .zip(&self.approved)
+            .map(|(user_plan, approved)| {
+                let mut user_plan = user_plan.clone();
+                if let Some(plan) = &mut user_plan.plan {
+                    let mut flags = approved.iter();
+                    plan.actions.retain(|_| *flags.next().unwrap_or(&false));
+                }
+                user_plan
+            })
+            .collect()
+    }
+}
+
+fn group_order(category: Option<Category>) -> usize {
+    category
+        .and_then(|c| Category::ALL.iter().position(|&all| all == c))
+        .unwrap_or(Category::ALL.len())
+}
+
+/// Row of the review list: a category header or one action
+#[derive(Debug, Clone, Copy)]
+enum Row {
+    Group(Option<Category>),
+    Item(ItemRef),
+}
+
+/// Shows the plans for review; `on_confirm` receives the approved subset.
+pub fn show_review_dialog<F>(
+    s: &mut Cursive,
+    organizer: Arc<Organizer>,
+    plans: Vec<UserPlan>,
+    on_confirm: F,
+) where
+    F: Fn(&mut Cursive, Vec<UserPlan>) + Send + Sync + 'static,
+{
+    let review = Arc::new(Mutex::new(PlanReview::new(plans)));
+    let errors: Vec<String> = review
+        .lock()
+        .unwrap()
+        .plans()
+        .iter()
+        .filter_map(|p| Some(format!("{}: {}", p.username, p.error.as_ref()?)))
+        .collect();
+
+    let mut list = SelectView::<Row>::new();
+    let submitted = review.clone();
+    list.set_on_submit(move |s, row: &Row| {
+        {
+            let mut review = submitted.lock().unwrap();
+            match *row {
+                Row::Group(category) => review.toggle_category(category),
+                Row::Item(item) => review.toggle(item),
+            }
+        }
+        refresh(s, &submitted);
+    });
+
+    let filtered = review.clone();
+    let mut layout = LinearLayout::vertical()
+        .child(TextView::new("").with_name("review_totals"))
+        .child(
+            LinearLayout::horizontal()
+                .child(TextView::new("Filter: "))
+                .child(
+                    EditView::new()
+                        .on_edit(move |s, _, _| refresh(s, &filtered))
+                        .with_name("review_filter")
+                        .full_width(),
+                ),
+        )
+        .child(
+            list.with_name("review_list")
+                .scrollable()
+                .fixed_size((100, 20)),
+        )
+        .child(TextView::new(
+            "Enter toggles an item, or a whole category on its header.",
+        ));
+    if !errors.is_empty() {
+        layout.add_child(TextView::new(format!(
+            "Not planned:\n{}",
+            errors.join("\n")
+        )));
+    }
+
+    let recategorized = review.clone();
+    let confirmed = review.clone();
+    s.add_layer(
+        Dialog::around(layout)
+            .title("Review planned moves")
+            .button("Change category", move |s| {
+                show_category_dialog(s, organizer.clone(), recategorized.clone())
+            })
+            .button("Confirm", move |s| {
+                let plans = confirmed.lock().unwrap().approved_plans();
+                s.pop_layer();
+                on_confirm(s, plans);
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+    refresh(s, &review);
+}
+
+/// Rebuilds the totals and the list, keeping the selection where it was.
+fn refresh(s: &mut Cursive, review: &Arc<Mutex<PlanReview>>) {
+    let review = review.lock().unwrap();
+    let filter = s
+        .call_on_name("review_filter", |v: &mut EditView| v.get_content())
+        .map(|f| f.to_lowercase())
+        .unwrap_or_default();
+    let groups = review.groups();
+
+    let (approved, bytes) = groups
+        .iter()
+        .fold((0, 0), |(n, b), g| (n + g.approved, b + g.approved_bytes));
+    let total: usize = groups.iter().map(|g| g.items).sum();
+    s.call_on_name("review_totals", |v: &mut TextView| {
+        v.set_content(format!(
+            "{approved} of {total} actions approved, {} to move",
+            format_size(bytes)
+        ))
+    });
+
+    s.call_on_name("review_list", |list: &mut SelectView<Row>| {
+        let selected = list.selected_id();
+        list.clear();
+        let items = review.items();
+        for group in &groups {
+            let matching: Vec<_> = items
+                .iter()
+                .filter(|(_, a)| a.category == group.category)
+                .filter(|(_, a)| a.source.to_string_lossy().to_lowercase().contains(&filter))
+                .collect();
+            if matching.is_empty() {
+                continue;
+            }
+            list.add_item(
+                format!(
+                    "{} {}: {}/{} items, {} of {}",
+                    group_mark(group),
+                    group.label(),
+                    group.approved,
+                    group.items,
+                    format_size(group.approved_bytes),
+                    format_size(group.bytes),
+                ),
+                Row::Group(group.category),
+            );
+            for (item, action) in matching {
+                list.add_item(
+                    item_label(action, review.is_approved(*item)),
+                    Row::Item(*item),
+                );
+            }
+        }
+        if let Some(selected) = selected {
+            list.set_selection(selected.min(list.len().saturating_sub(1)));
+        }
+    });
+}
+
+fn mark(approved: bool) -> &'static str {
+    if approved {
+        "[x]"
+    } else {
+        "[ ]"
+    }
+}
+
+fn group_mark(group: &GroupSummary) -> &'static str {
+    match group.approved {
+        0 => "[ ]",
+        n if n == group.items => "[x]",
+        _ => "[-]",
+    }
+}
+
+fn item_label(action: &PlannedAction, approved: bool) -> String {
+    let destination = match &action.destination {
+        Some(destination) => format!(" -> {}", destination.display()),
+        None if action.kind == ActionKind::TrashStale => " -> trash".to_string(),
+        None => String::new(),
+    };
+    format!(
+        "    {} {}{destination}  ({})",
+        mark(approved),
+        action.source.display(),
+        format_size(action.size),
+    )
+}
+
+/// Picks a new category for the selected item.
+fn show_category_dialog(
+    s: &mut Cursive,
+    organizer: Arc<Organizer>,
+    review: Arc<Mutex<PlanReview>>,
+) {
+    let selected = s
+        .call_on_name("review_list", |v: &mut SelectView<Row>| v.selection())
+        .flatten();
+    let item = match selected.as_deref() {
+        Some(Row::Item(item)) => *item,
+        _ => {
+            s.add_layer(Dialog::info("Select a single item to change its category."));
+            return;
+        }
+    };
+    let moves = review
+        .lock()
+        .unwrap()
+        .action(item)
+        .is_some_and(|a| a.category.is_some());
+    if !moves {
+        s.add_layer(Dialog::info("Cleanup actions have no category."));
+        return;
+    }
+
+    let mut categories = SelectView::<Category>::new();
+    for category in Category::ALL {
+        categories.add_item(category.logical_dir(), category);
+    }
+    categories.set_on_submit(move |s, category: &Category| {
+        review
+            .lock()
+            .unwrap()
+            .recategorize(&organizer, item, *category);
+        s.pop_layer();
+        refresh(s, &review);
+    });
+    s.add_layer(
+        Dialog::around(categories)
+            .title("Move to")
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::organizer::types::{ActionKind, Plan};
+    use std::path::PathBuf;
+
+    fn action(
+        kind: ActionKind,
+        source: &str,
+        category: Option<Category>,
+        size: u64,
+    ) -> PlannedAction {
+        PlannedAction {
+            kind,
+            source: PathBuf::from(source),
+            destination: None,
+            category,
+            size,
+        }
+    }
+
+    fn review() -> PlanReview {
+        let plan = Plan {
+            username: "alice".to_string(),
+            actions: vec![
+                action(ActionKind::DeleteEmpty, "/d/empty.txt", None, 0),
+                action(ActionKind::MoveFile, "/d/b.mp3", Some(Category::Music), 10),
+                action(
+                    ActionKind::MoveFile,
+                    "/d/pic.png",
+                    Some(Category::Images),
+                    5,
+                ),
+                action(ActionKind::MoveFolder, "/d/a", Some(Category::Music), 20),
+            ],
+            ..Plan::default()
+        };
+        PlanReview::new(vec![
+            UserPlan {
+                username: "alice".to_string(),
+                plan: Some(plan),
+                error: None,
+            },
+            UserPlan {
+                username: "bob".to_string(),
+                plan: None,
+                error: Some("User not found: bob".to_string()),
+            },
+        ])
+    }
+
+    fn sources(review: &PlanReview) -> Vec<String> {
+        review
+            .items()
+            .iter()
+            .map(|(_, a)| a.source.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_items_are_grouped_by_category_with_totals() {
+        let review = review();
+        assert_eq!(
+            sources(&review),
+            ["/d/a", "/d/b.mp3", "/d/pic.png", "/d/empty.txt"]
+        );
+        let groups: Vec<(&str, usize, u64)> = review
+            .groups()
+            .iter()
+            .map(|g| (g.label(), g.items, g.bytes))
+            .collect();
+        assert_eq!(
+            groups,
+            [("music", 2, 30), ("image", 1, 5), ("cleanup", 1, 0)]
+        );
+    }
+
+    #[test]
+    fn test_only_approved_actions_are_kept() {
+        let mut review = review();
+        let music = ItemRef { user: 0, action: 1 };
+        review.toggle(music);
+        assert!(!review.is_approved(music));
+        let music_group = &review.groups()[0];
+        assert_eq!((music_group.approved, music_group.approved_bytes), (1, 20));
+
+        // Partly approved categories are approved as a whole first
+        review.toggle_category(Some(Category::Music));
+        assert!(review.is_approved(music));
+        review.toggle_category(Some(Category::Music));
+        review.toggle_category(None);
+
+        let plans = review.approved_plans();
+        let kept: Vec<&str> = plans[0]
+            .plan
+            .as_ref()
+            .unwrap()
+            .actions
+            .iter()
+            .map(|a| a.source.to_str().unwrap())
+            .collect();
+        assert_eq!(kept, ["/d/pic.png"]);
+        assert!(plans[1].plan.is_none() && plans[1].error.is_some());
+    }
+}
@@ -0,0 +1,247 @@
+//! Settings dialog editing the persistent configuration
+
+use crate::config::Config;
+use crate::ui::theme::{available_themes, configured_theme, themes_dir};
+use cursive::traits::*;
+use cursive::views::{Checkbox, Dialog, EditView, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+use std::path::PathBuf;
+
+/// Extension lists as named by `Config::get_file_category`, with their labels
+const EXTENSION_LISTS: [(&str, &str); 6] = [
+    ("music", "Music"),
+    ("video", "Videos"),
+    ("image", "Pictures"),
+    ("docs", "Documents"),
+    ("archives", "Archives"),
+    ("code", "Code"),
+];
+
+const PERFORMANCE_FIELDS: [&str; 4] = [
+    "max_concurrent_users",
+    "thread_pool_size",
+    "batch_size",
+    "buffer_size",
+];
+
+const LABEL_WIDTH: usize = 22;
+
+/// Splits a comma or space separated list into lowercase extensions without dots.
+pub fn parse_extension_list(text: &str) -> Vec<String> {
+    let mut extensions: Vec<String> = Vec::new();
+    for item in text.split(|c: char| c == ',' || c.is_whitespace()) {
+        let extension = item.trim().trim_start_matches('.').to_lowercase();
+        if !extension.is_empty() && !extensions.contains(&extension) {
+            extensions.push(extension);
+        }
+    }
+    extensions
+}
+
+/// Shows the settings of the `Config` kept as Cursive user data; saving
+/// validates them, writes them to `path` and makes them current.
+pub fn show_settings_dialog(s: &mut Cursive, path: Option<PathBuf>) {
+    let config = s.user_data::<Config>().cloned().unwrap_or_default();
+
+    let mut form = LinearLayout::vertical().child(TextView::new("Extensions (comma separated)"));
+    for (name, label) in EXTENSION_LISTS {
+        let extensions = config
+            .file_extensions
+            .lists()
+            .into_iter()
+            .find(|(list, _)| *list == name)
+            .map(|(_, extensions)| extensions.join(", "))
+            .unwrap_or_default();
+        form.add_child(labeled(
+            label,
+            EditView::new()
+                .content(extensions)
+                .with_name(format!("ext_{name}"))
+                .full_width(),
+        ));
+    }
+
+    form.add_child(labeled(
+        "Built-in catalog",
+        Checkbox::new()
+            .with_checked(config.file_extensions.catalog)
+            .with_name("catalog"),
+    ));
+
+    form.add_child(labeled(
+        "Inspect archives",
+        Checkbox::new()
+            .with_checked(config.archives.inspect)
+            .with_name("archives_inspect"),
+    ));
+    form.add_child(labeled(
+        "Extract archives",
+        Checkbox::new()
+            .with_checked(config.archives.extract)
+            .with_name("archives_extract"),
+    ));
+
+    let languages = config.languages();
+    let mut language = SelectView::<String>::new().popup();
+    language.add_all_str(languages.iter().cloned());
+    if let Some(index) = languages.iter().position(|l| *l == config.ui.language) {
+        language.set_selection(index);
+    }
+    form.add_child(TextView::new("\nInterface"));
+    form.add_child(labeled("Language", language.with_name("language")));
+    let mut themes = available_themes(themes_dir(path.as_deref()).as_deref());
+    if !themes.contains(&config.ui.theme) {
+        themes.push(config.ui.theme.clone());
+    }
+    let mut theme = SelectView::<String>::new().popup();
+    theme.add_all_str(themes.iter().cloned());
+    if let Some(index) = themes.iter().position(|t| *t == config.ui.theme) {
+        theme.set_selection(index);
+    }
+    form.add_child(labeled("Theme", theme.with_name("theme")));
+    form.add_child(labeled(
+        "Show progress",
+        Checkbox::new()
+            .with_checked(config.ui.show_progress)
+            .with_name("show_progress"),
+    ));
+    form.add_child(labeled(
+        "Auto refresh",
+        Checkbox::new()
+            .with_checked(config.ui.auto_refresh)
+            .with_name("auto_refresh"),
+    ));
+
+    form.add_child(TextView::new("\nPerformance"));
+    let performance = &config.performance;
+    let values = [
+        performance.max_concurrent_users,
+        performance.thread_pool_size,
+        performance.batch_size,
+        performance.buffer_size,
+    ];
+    for (name, value) in PERFORMANCE_FIELDS.into_iter().zip(values) {
+        form.add_child(labeled(
+            name,
+            EditView::new()
+                .content(value.to_string())
+                .with_name(name)
+                .fixed_width(10),
+        ));
+    }
+    form.add_child(labeled(
+        "Parallel processing",
+        Checkbox::new()
+            .with_checked(performance.use_parallel_processing)
+            .with_name("use_parallel_processing"),
+    ));
+
+    s.add_layer(
+        Dialog::around(form.scrollable().max_height(25))
+            .title("Settings")
+            .button("Save", move |s| save(s, path.as_ref()))
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn labeled<V: View>(label: &str, view: V) -> LinearLayout {
+    LinearLayout::horizontal()
+        .child(TextView::new(format!("{label:<LABEL_WIDTH$}")))
+        .child(view)
+}
+
+fn save(s: &mut Cursive, path: Option<&PathBuf>) {
+    let base = s.user_data::<Config>().cloned().unwrap_or_default();
+    let result = read_form(s, base).and_then(|config| {
+        let path = path.ok_or("There is no config directory to save settings in.")?;
+        // A theme file that does not load is reported before anything is saved
+        let theme = configured_theme(&config.ui.theme, themes_dir(Some(path)).as_deref())
+            .map_err(|e| e.to_string())?;
+        config.save_to(path).map_err(|e| e.to_string())?;
+        Ok((config, path, theme))
+    });
+    match result {
+        Ok((config, path, theme)) => {
+            s.set_theme(theme);
+            s.set_user_data(config);
+            s.pop_layer();
+            s.add_layer(Dialog::info(format!(
+                "Settings saved to {}.",
+                path.display()
+            )));
+        }
+        Err(message) => s.add_layer(Dialog::info(message)),
+    }
+}
+
+/// Applies the form to `config`; numbers that do not parse are reported by name.
+fn read_form(s: &mut Cursive, mut config: Config) -> Result<Config, String> {
+    let text = |s: &mut Cursive, name: &str| {
+        s.call_on_name(name, |v: &mut EditView| v.get_content().to_string())
+            .unwrap_or_default()
+    };
+    let checked = |s: &mut Cursive, name: &str| {
+        s.call_on_name(name, |v: &mut Checkbox| v.is_checked())
+            .unwrap_or_default()
+    };
+
+    for (name, _) in EXTENSION_LISTS {
+        let extensions = parse_extension_list(&text(s, &format!("ext_{name}")));
+        if let Some(list) = config.file_extensions.list_mut(name) {
+            *list = extensions;
+        }
+    }
+    config.file_extensions.catalog = checked(s, "catalog");
+    config.archives.inspect = checked(s, "archives_inspect");
+    config.archives.extract = checked(s, "archives_extract");
+
+    if let Some(language) = s
+        .call_on_name("language", |v: &mut SelectView<String>| v.selection())
+        .flatten()
+    {
+        config.ui.language = language.to_string();
+    }
+    if let Some(theme) = s
+        .call_on_name("theme", |v: &mut SelectView<String>| v.selection())
+        .flatten()
+    {
+        config.ui.theme = theme.to_string();
+    }
+    config.ui.show_progress = checked(s, "show_progress");
+    config.ui.auto_refresh = checked(s, "auto_refresh");
+
+    let mut numbers = [0; PERFORMANCE_FIELDS.len()];
+    for (name, number) in PERFORMANCE_FIELDS.into_iter().zip(&mut numbers) {
+        *number = text(s, name)
+            .trim()
+            .parse()
+            .map_err(|_| format!("performance.{name} must be a whole number"))?;
+    }
+    let performance = &mut config.performance;
+    [
+        performance.max_concurrent_users,
+        performance.thread_pool_size,
+        performance.batch_size,
+        performance.buffer_size,
+    ] = numbers;
+    performance.use_parallel_processing = checked(s, "use_parallel_processing");
+
+    config.validate().map_err(|e| e.to_string())?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension_lists_are_normalized() {
+        assert_eq!(
+            parse_extension_list(" .MP3, ogg  flac,,mp3 "),
+            ["mp3", "ogg", "flac"]
+        );
+        assert!(parse_extension_list(" , ").is_empty());
+    }
+}
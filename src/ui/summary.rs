@@ -0,0 +1,200 @@
+//! Text rendering of run results for the TUI dialogs
+
+use crate::history::{HistoryEntry, WeeklyStats};
+use crate::organizer::suggestions::{suggest_category, top_unrecognized};
+use crate::organizer::types::{FileStats, MultiUserReport};
+use crate::schedule::{is_installed, systemd_unit_dir, Backend, Schedule};
+
+const HEADER: [&str; 9] = [
+    "User", "Music", "Videos", "Images", "Docs", "Archives", "Code", "Cleaned", "Errors",
+];
+const WEEK_HEADER: [&str; 9] = [
+    "Week", "Music", "Videos", "Images", "Docs", "Archives", "Code", "Cleaned", "Runs",
+];
+/// Unrecognised extensions listed below the summary
+const TOP_UNRECOGNIZED: usize = 5;
+
+/// Renders a per-user table followed by the combined totals and the error list.
+pub fn summary_table(report: &MultiUserReport) -> String {
+    let user_width = report
+        .users
+        .iter()
+        .map(|u| u.username.chars().count())
+        .chain([HEADER[0].len(), "Total".len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut lines = vec![row(&HEADER, user_width, HEADER.map(String::from))];
+    for user in &report.users {
+        match &user.report {
+            Some(run) => lines.push(stats_row(
+                user_width,
+                &user.username,
+                &run.stats,
+                run.errors.len(),
+            )),
+            None => lines.push(format!("{:<user_width$}  not organized", user.username)),
+        }
+    }
+    let error_count = report.errors().len();
+    lines.push("-".repeat(lines[0].len()));
+    lines.push(stats_row(user_width, "Total", &report.totals, error_count));
+
+    let unrecognized = report.unrecognized();
+    if !unrecognized.is_empty() {
+        lines.push(String::new());
+        lines.push("Unrecognised extensions:".to_string());
+        for (extension, tally) in top_unrecognized(&unrecognized, TOP_UNRECOGNIZED) {
+            let suggestion = suggest_category(extension)
+                .map(|c| format!("  maybe {}", c.logical_dir()))
+                .unwrap_or_default();
+            let extension = match extension {
+                "" => "(none)".to_string(),
+                extension => format!(".{extension}"),
+            };
+            lines.push(format!(
+                "{extension:<10}  {:>5} files  {:>10}{suggestion}",
+                tally.files,
+                format_size(tally.bytes)
+            ));
+        }
+    }
+
+    let errors = report.errors();
+    if !errors.is_empty() {
+        lines.push(String::new());
+        lines.push("Errors:".to_string());
+        lines.extend(errors);
+    }
+    lines.join("\n")
+}
+
+/// Renders files organized per category for each week, with the number of runs.
+pub fn weekly_table(weeks: &[WeeklyStats]) -> String {
+    let width = "YYYY-MM-DD".len();
+    let mut lines = vec![row(&WEEK_HEADER, width, WEEK_HEADER.map(String::from))];
+    for week in weeks {
+        lines.push(row(
+            &WEEK_HEADER,
+            width,
+            stats_cells(&week.week, &week.stats, week.runs),
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Renders one line per run: start, user, files organized, errors and duration.
+pub fn runs_table(entries: &[&HistoryEntry]) -> String {
+    let user_width = entries
+        .iter()
+        .map(|e| e.username.chars().count())
+        .chain(["User".len()])
+        .max()
+        .unwrap_or_default();
+    let mut lines = vec![format!(
+        "{:<20}  {:<user_width$}  {:>5}  {:>6}  {:>8}",
+        "Started", "User", "Files", "Errors", "Duration"
+    )];
+    for entry in entries {
+        let dry_run = if entry.dry_run { "  (dry run)" } else { "" };
+        lines.push(format!(
+            "{:<20}  {:<user_width$}  {:>5}  {:>6}  {:>7.1}s{dry_run}",
+            entry.started(),
+            entry.username,
+            entry.stats.total(),
+            entry.errors,
+            entry.duration_ms as f64 / 1000.0,
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Renders one line per schedule: name, trigger, users, backend and whether
+/// it is installed; `crontab` is the user's crontab.
+pub fn schedules_table(schedules: &[Schedule], crontab: &str) -> String {
+    let unit_dir = systemd_unit_dir();
+    let rows: Vec<[String; 5]> = schedules
+        .iter()
+        .map(|schedule| {
+            let users = match schedule.users.is_empty() {
+                true => "all".to_string(),
+                false => schedule.users.join(","),
+            };
+            let status = match schedule.backend {
+                Backend::Builtin => "schedule run",
+                _ if is_installed(schedule, unit_dir.as_deref(), crontab) => "installed",
+                _ => "not installed",
+            };
+            let dry_run = if schedule.dry_run { " (dry run)" } else { "" };
+            [
+                schedule.name.clone(),
+                schedule.when.to_string(),
+                users,
+                schedule.backend.to_string(),
+                format!("{status}{dry_run}"),
+            ]
+        })
+        .collect();
+    let header = ["Name", "When", "Users", "Backend", "Status"].map(String::from);
+    let mut widths = header.clone().map(|h| h.chars().count());
+    for cells in &rows {
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    std::iter::once(&header)
+        .chain(&rows)
+        .map(|cells| {
+            let line: Vec<String> = cells
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            line.join("  ").trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Renders a byte count with a binary unit, e.g. `1.5 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+fn stats_row(user_width: usize, username: &str, stats: &FileStats, errors: usize) -> String {
+    row(&HEADER, user_width, stats_cells(username, stats, errors))
+}
+
+fn stats_cells(label: &str, stats: &FileStats, last: usize) -> [String; 9] {
+    [
+        label.to_string(),
+        stats.music.to_string(),
+        stats.videos.to_string(),
+        stats.images.to_string(),
+        stats.docs.to_string(),
+        stats.archives.to_string(),
+        stats.code.to_string(),
+        stats.cleaned.to_string(),
+        last.to_string(),
+    ]
+}
+
+fn row(headers: &[&str; 9], user_width: usize, cells: [String; 9]) -> String {
+    let [user, rest @ ..] = cells;
+    let mut line = format!("{user:<user_width$}");
+    for (cell, header) in rest.iter().zip(&headers[1..]) {
+        line.push_str(&format!("  {cell:>width$}", width = header.len()));
+    }
+    line
+}
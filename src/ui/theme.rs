@@ -0,0 +1,208 @@
+//! TUI color themes selected by `UiConfig::theme`
+//!
+//! Besides the built-in themes, `<name>.toml` in the `themes` directory next
+//! to the config file is loaded as a Cursive theme. `NO_COLOR` forces the
+//! monochrome theme whatever is configured.
+
+use crate::error::{Error, Result};
+use cursive::style::{BaseColor, BorderStyle, Color, PaletteColor};
+use cursive::theme::{load_theme_file, Theme};
+use std::path::{Path, PathBuf};
+
+/// Names of the themes that need no file
+pub const BUILTIN_THEMES: [&str; 5] = ["default", "light", "dark", "high-contrast", "monochrome"];
+
+/// Directory holding custom themes, next to the config file at `config_path`.
+pub fn themes_dir(config_path: Option<&Path>) -> Option<PathBuf> {
+    Some(config_path?.parent()?.join("themes"))
+}
+
+/// Whether `NO_COLOR` asks for output without colors.
+pub fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// Built-in themes followed by the custom ones found in `themes_dir`, sorted.
+pub fn available_themes(themes_dir: Option<&Path>) -> Vec<String> {
+    let mut custom: Vec<String> = themes_dir
+        .and_then(|dir| std::fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e == "toml"))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .filter(|name| !BUILTIN_THEMES.contains(&name.as_str()))
+        .collect();
+    custom.sort();
+    BUILTIN_THEMES
+        .iter()
+        .map(|name| name.to_string())
+        .chain(custom)
+        .collect()
+}
+
+/// Theme named `name`: a built-in one or `<name>.toml` in `themes_dir`.
+pub fn load_theme(name: &str, themes_dir: Option<&Path>) -> Result<Theme> {
+    if let Some(theme) = builtin_theme(name) {
+        return Ok(theme);
+    }
+    let path = themes_dir
+        .map(|dir| dir.join(format!("{name}.toml")))
+        .filter(|path| path.is_file())
+        .ok_or_else(|| Error::InvalidConfig {
+            message: format!("unknown theme `{name}`"),
+        })?;
+    load_theme_file(&path).map_err(|e| Error::InvalidConfig {
+        message: format!("theme {}: {e:?}", path.display()),
+    })
+}
+
+/// The theme to use for `name`, honouring `NO_COLOR`.
+pub fn configured_theme(name: &str, themes_dir: Option<&Path>) -> Result<Theme> {
+    if no_color() {
+        return Ok(monochrome());
+    }
+    load_theme(name, themes_dir)
+}
+
+fn builtin_theme(name: &str) -> Option<Theme> {
+    use BaseColor::*;
+    use Color::{Dark, Light};
+    let (shadow, colors) = match name {
+        "default" => return Some(Theme::retro()),
+        "monochrome" => return Some(monochrome()),
+        "light" => (
+            false,
+            [
+                Light(White),
+                Dark(White),
+                Light(White),
+                Dark(Black),
+                Dark(Blue),
+                Light(Black),
+                Dark(Blue),
+                Dark(Magenta),
+                Dark(Blue),
+                Light(Black),
+                Light(White),
+            ],
+        ),
+        "dark" => (
+            false,
+            [
+                Dark(Black),
+                Dark(Black),
+                Dark(Black),
+                Dark(White),
+                Light(Cyan),
+                Light(Black),
+                Light(Cyan),
+                Light(Yellow),
+                Dark(Cyan),
+                Light(Black),
+                Dark(Black),
+            ],
+        ),
+        "high-contrast" => (
+            false,
+            [
+                Dark(Black),
+                Dark(Black),
+                Dark(Black),
+                Light(White),
+                Light(Yellow),
+                Light(White),
+                Light(Yellow),
+                Light(White),
+                Light(Yellow),
+                Light(White),
+                Dark(Black),
+            ],
+        ),
+        _ => return None,
+    };
+    let mut theme = Theme {
+        shadow,
+        ..Theme::retro()
+    };
+    let slots = [
+        PaletteColor::Background,
+        PaletteColor::Shadow,
+        PaletteColor::View,
+        PaletteColor::Primary,
+        PaletteColor::Secondary,
+        PaletteColor::Tertiary,
+        PaletteColor::TitlePrimary,
+        PaletteColor::TitleSecondary,
+        PaletteColor::Highlight,
+        PaletteColor::HighlightInactive,
+        PaletteColor::HighlightText,
+    ];
+    for (slot, color) in slots.into_iter().zip(colors) {
+        theme.palette[slot] = color;
+    }
+    Some(theme)
+}
+
+/// Terminal colors only; selections are shown in reverse video.
+fn monochrome() -> Theme {
+    Theme {
+        borders: BorderStyle::Simple,
+        ..Theme::terminal_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_themes_need_no_directory() {
+        for name in BUILTIN_THEMES {
+            assert!(load_theme(name, None).is_ok(), "{name}");
+        }
+        let light = load_theme("light", None).unwrap();
+        assert_eq!(
+            light.palette[PaletteColor::View],
+            Color::Light(BaseColor::White)
+        );
+        let monochrome = load_theme("monochrome", None).unwrap();
+        assert_eq!(
+            monochrome.palette[PaletteColor::Highlight],
+            Color::TerminalDefault
+        );
+        assert!(load_theme("solarized", None).is_err());
+    }
+
+    #[test]
+    fn test_custom_themes_are_loaded_from_the_themes_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let themes = themes_dir(Some(&dir.path().join("config.toml"))).unwrap();
+        std::fs::create_dir_all(&themes).unwrap();
+        std::fs::write(
+            themes.join("solarized.toml"),
+            "shadow = false\n[colors]\nbackground = \"#002b36\"\n",
+        )
+        .unwrap();
+        std::fs::write(themes.join("notes.txt"), "").unwrap();
+
+        assert_eq!(
+            available_themes(Some(&themes)),
+            [
+                "default",
+                "light",
+                "dark",
+                "high-contrast",
+                "monochrome",
+                "solarized"
+            ]
+        );
+        let theme = load_theme("solarized", Some(&themes)).unwrap();
+        assert!(!theme.shadow);
+        assert_eq!(
+            theme.palette[PaletteColor::Background],
+            Color::Rgb(0x00, 0x2b, 0x36)
+        );
+    }
+}
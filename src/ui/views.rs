@@ -1,25 +1,45 @@
 // UI construction and event handling will go here.
 
-use crate::organizer::mover::organize_files;
-#[cfg(target_os = "macos")]
-use crate::platform::user::MacUserProvider;
-#[cfg(all(unix, not(target_os = "macos")))]
-use crate::platform::user::UnixUserProvider;
-use crate::platform::user::UserProvider;
-#[cfg(target_os = "windows")]
-use crate::platform::user::WindowsUserProvider;
+use crate::config::Config;
+use crate::history::{largest_runs, weekly_totals, HistoryStore};
+use crate::logging::open_audit_log;
+use crate::organizer::engine::Organizer;
+use crate::organizer::suggestions::{suggestions, Suggestion};
+use crate::organizer::types::{CollisionPolicy, ExtensionTally, UserPlan};
+use crate::platform::user::{effective_uid, system_user_provider, UserProvider};
+use crate::report::ExportedReport;
+use crate::schedule::{read_crontab, Backend};
+use crate::ui::browser::show_analysis_browser;
+use crate::ui::review::show_review_dialog;
+use crate::ui::settings::show_settings_dialog;
+use crate::ui::summary::{format_size, runs_table, schedules_table, summary_table, weekly_table};
+use crate::ui::theme::{configured_theme, themes_dir};
 use cursive::traits::*;
-use cursive::views::{Dialog, LinearLayout, SelectView, TextView};
+use cursive::views::{Dialog, EditView, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::warn;
 
-pub fn run_ui() {
-    #[cfg(target_os = "windows")]
-    let user_provider = WindowsUserProvider;
-    #[cfg(target_os = "macos")]
-    let user_provider = MacUserProvider;
-    #[cfg(all(unix, not(target_os = "macos")))]
-    let user_provider = UnixUserProvider;
+/// Runs the interactive UI with `config`; settings are saved to `config_path`
+/// and runs are recorded in `history`.
+pub fn run_ui(config: Config, config_path: Option<PathBuf>, history: Option<HistoryStore>) {
+    let user_provider = system_user_provider();
     let users: Vec<String> = user_provider.list_users();
     let mut siv = cursive::default();
+    match configured_theme(
+        &config.ui.theme,
+        themes_dir(config_path.as_deref()).as_deref(),
+    ) {
+        Ok(theme) => siv.set_theme(theme),
+        Err(e) => warn!("{e}; using the default theme"),
+    }
+    siv.set_user_data(config);
+    let pin_provider = user_provider.clone();
+    let analysis_provider = user_provider.clone();
+    let run_config_path = config_path.clone();
+    let run_history = history.clone();
     let mut select = SelectView::<String>::new().with_all_str(users.clone());
     select.add_item("<All Users>", "<ALL>".to_string());
     select.set_on_submit(move |s, username: &String| {
@@ -28,36 +48,43 @@ pub fn run_ui() {
         } else {
             vec![username.clone()]
         };
-        let processing_dialog = Dialog::new()
-            .title("Organizing Files...")
-            .content(TextView::new("Please wait, organizing your files..."));
-        s.add_layer(processing_dialog);
+        let planning_dialog = Dialog::new()
+            .title("Planning...")
+            .content(TextView::new("Please wait, looking at your files..."));
+        s.add_layer(planning_dialog);
         let cb_sink = s.cb_sink().clone();
-        let usernames_clone = usernames.clone();
-        let config = crate::config::Config::default();
+        let config_path = run_config_path.clone();
+        let history = run_history.clone();
+        let config = s.user_data::<Config>().cloned().unwrap_or_default();
+        let mut builder = Organizer::builder();
+        if let Some(audit) = open_audit_log(&config.logging) {
+            builder = builder.audit(audit);
+        }
+        // As root, organize each home in a worker running as its owner
+        let organizer = Arc::new(
+            builder
+                .config(config)
+                .user_provider(user_provider.clone())
+                // The TUI has always replaced files already at the destination
+                .collision_policy(CollisionPolicy::Overwrite)
+                .privilege_separation(effective_uid() == Some(0))
+                .build(),
+        );
         std::thread::spawn(move || {
-            let mut total_stats = crate::organizer::types::FileStats { music: 0, videos: 0, images: 0, docs: 0 };
-            let mut errors = Vec::new();
-            for user in usernames_clone {
-                match organize_files(&user, "en", &config) {
-                    Ok(stats) => {
-                        total_stats.music += stats.music;
-                        total_stats.videos += stats.videos;
-                        total_stats.images += stats.images;
-                        total_stats.docs += stats.docs;
-                    }
-                    Err(e) => errors.push(format!("{user}: {e}")),
-                }
-            }
-            let info_message = format!(
-                "Organization Complete!\n\nMusic files/folders moved: {}\nVideo files/folders moved: {}\nImage files/folders moved: {}\nDocs files/folders moved: {}{}",
-                total_stats.music, total_stats.videos, total_stats.images, total_stats.docs,
-                if errors.is_empty() { "".to_string() } else { format!("\n\nErrors:\n{}", errors.join("\n")) }
-            );
+            let plans = organizer.plan_users(&usernames);
             cb_sink
                 .send(Box::new(move |s| {
                     s.pop_layer();
-                    s.add_layer(Dialog::info(info_message));
+                    let executor = organizer.clone();
+                    show_review_dialog(s, organizer, plans, move |s, approved| {
+                        execute_approved(
+                            s,
+                            executor.clone(),
+                            approved,
+                            config_path.clone(),
+                            history.clone(),
+                        )
+                    });
                 }))
                 .unwrap();
         });
@@ -72,8 +99,301 @@ pub fn run_ui() {
                     .child(TextView::new("Select the user to organize:"))
                     .child(select.with_name("user_select").fixed_width(50)),
             )
+            .button("Pin items", move |s| {
+                show_pin_dialog(s, pin_provider.clone())
+            })
+            .button("Analyze", move |s| {
+                show_analysis(s, analysis_provider.clone())
+            })
+            .button("History", move |s| show_history_dialog(s, history.as_ref()))
+            .button("Schedules", show_schedules_dialog)
+            .button("Settings", move |s| {
+                show_settings_dialog(s, config_path.clone())
+            })
             .button("Esc", |s| s.quit())
             .with_name("RustGanizer"),
     );
     siv.run();
 }
+
+/// Executes the approved plans in the background, records the run in
+/// `history` and shows the summary; accepted extension mappings are saved to
+/// `config_path`.
+fn execute_approved(
+    s: &mut Cursive,
+    organizer: Arc<Organizer>,
+    plans: Vec<UserPlan>,
+    config_path: Option<PathBuf>,
+    history: Option<HistoryStore>,
+) {
+    let processing_dialog = Dialog::new()
+        .title("Organizing Files...")
+        .content(TextView::new("Please wait, organizing your files..."));
+    s.add_layer(processing_dialog);
+    let cb_sink = s.cb_sink().clone();
+    std::thread::spawn(move || {
+        let report = organizer.execute_plans(&plans);
+        if let Some(history) = &history {
+            if let Err(e) = history.record(&report) {
+                warn!("Could not record the run in the history: {e}");
+            }
+        }
+        let info_message = format!("Organization Complete!\n\n{}", summary_table(&report));
+        let export = Arc::new(ExportedReport::new(report));
+        cb_sink
+            .send(Box::new(move |s| {
+                s.pop_layer();
+                let unrecognized = Arc::new(export.run.unrecognized());
+                let mut summary = Dialog::around(TextView::new(info_message).scrollable())
+                    .title("Summary")
+                    .button("Save report", move |s| {
+                        show_save_report_dialog(s, export.clone())
+                    });
+                if !unrecognized.is_empty() {
+                    summary.add_button("Suggestions", move |s| {
+                        show_suggestions_dialog(s, &unrecognized, config_path.clone())
+                    });
+                }
+                s.add_layer(summary.button("Ok", |s| {
+                    s.pop_layer();
+                }));
+            }))
+            .unwrap();
+    });
+}
+
+/// Lists suggested mappings for unrecognised extensions; submitting one adds
+/// it to the configuration and saves it to `path`.
+fn show_suggestions_dialog(
+    s: &mut Cursive,
+    unrecognized: &BTreeMap<String, ExtensionTally>,
+    path: Option<PathBuf>,
+) {
+    let config = s.user_data::<Config>().cloned().unwrap_or_default();
+    let suggested = suggestions(unrecognized, &config);
+    if suggested.is_empty() {
+        s.add_layer(Dialog::info(
+            "There are no suggestions for the unrecognised extensions.",
+        ));
+        return;
+    }
+    let mut list = SelectView::<Suggestion>::new();
+    for suggestion in suggested {
+        let label = format!(
+            ".{:<8} -> {:<10} {} files, {}",
+            suggestion.extension,
+            suggestion.category.logical_dir(),
+            suggestion.tally.files,
+            format_size(suggestion.tally.bytes)
+        );
+        list.add_item(label, suggestion);
+    }
+    list.set_on_submit(move |s, suggestion: &Suggestion| {
+        let mut config = s.user_data::<Config>().cloned().unwrap_or_default();
+        config.add_extension(suggestion.category, &suggestion.extension);
+        let saved = match &path {
+            Some(path) => config.save_to(path).map_err(|e| e.to_string()),
+            None => Err("There is no config directory to save settings in.".to_string()),
+        };
+        match saved {
+            Ok(()) => {
+                s.set_user_data(config);
+                s.call_on_name("suggestions", |v: &mut SelectView<Suggestion>| {
+                    if let Some(index) = v.selected_id() {
+                        let _ = v.remove_item(index);
+                    }
+                });
+            }
+            Err(message) => s.add_layer(Dialog::info(message)),
+        }
+    });
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(
+                    "Enter adds the mapping to the configuration.",
+                ))
+                .child(list.with_name("suggestions").scrollable().max_height(15)),
+        )
+        .title("Suggested mappings")
+        .button("Close", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+/// Opens the analysis browser in the selected user's home.
+fn show_analysis(s: &mut Cursive, user_provider: Arc<dyn UserProvider>) {
+    let selected = s
+        .call_on_name("user_select", |v: &mut SelectView<String>| v.selection())
+        .flatten();
+    let Some(username) = selected.filter(|u| u.as_str() != "<ALL>") else {
+        s.add_layer(Dialog::info("Select a single user to analyze folders for."));
+        return;
+    };
+    let config = s.user_data::<Config>().cloned().unwrap_or_default();
+    let organizer = Arc::new(
+        Organizer::builder()
+            .config(config)
+            .user_provider(user_provider)
+            .build(),
+    );
+    match organizer.home(&username) {
+        Ok(home) => show_analysis_browser(s, organizer, username.to_string(), home),
+        Err(e) => s.add_layer(Dialog::info(e.to_string())),
+    }
+}
+
+/// Lists the entries of the selected user's source directories; submitting one
+/// adds it to that directory's `.rustganizerignore`.
+fn show_pin_dialog(s: &mut Cursive, user_provider: Arc<dyn UserProvider>) {
+    let selected = s
+        .call_on_name("user_select", |v: &mut SelectView<String>| v.selection())
+        .flatten();
+    let Some(username) = selected.filter(|u| u.as_str() != "<ALL>") else {
+        s.add_layer(Dialog::info("Select a single user to pin items for."));
+        return;
+    };
+    let config = s.user_data::<Config>().cloned().unwrap_or_default();
+    let organizer = Arc::new(
+        Organizer::builder()
+            .config(config)
+            .user_provider(user_provider)
+            .build(),
+    );
+    let sources = match organizer.source_dirs(&username) {
+        Ok(sources) => sources,
+        Err(e) => {
+            s.add_layer(Dialog::info(e.to_string()));
+            return;
+        }
+    };
+
+    let mut entries = SelectView::<(PathBuf, PathBuf)>::new();
+    for source in sources {
+        let Ok(children) = organizer.source_entries(&source.path) else {
+            continue;
+        };
+        for child in children {
+            let label = child
+                .strip_prefix(source.path.parent().unwrap_or(&source.path))
+                .unwrap_or(&child)
+                .display()
+                .to_string();
+            entries.add_item(label, (source.path.clone(), child));
+        }
+    }
+    let pin_user = username.clone();
+    entries.set_on_submit(move |s, (source, entry): &(PathBuf, PathBuf)| {
+        let message = match organizer.pin(&pin_user, source, entry) {
+            Ok(pattern) => format!("Pinned with `{pattern}`."),
+            Err(e) => e.to_string(),
+        };
+        s.add_layer(Dialog::info(message));
+    });
+    s.add_layer(
+        Dialog::around(entries.scrollable().fixed_size((60, 20)))
+            .title(format!("Pin items for {username}"))
+            .button("Done", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+/// Asks for a path and saves the report there; the extension picks the format.
+fn show_save_report_dialog(s: &mut Cursive, export: Arc<ExportedReport>) {
+    let default_path = dirs::home_dir()
+        .unwrap_or_default()
+        .join("rustganizer-report.html");
+    let save = move |s: &mut Cursive, path: &str| {
+        let message = match export.save(&PathBuf::from(path), None) {
+            Ok(format) => format!("Saved {} report to {path}.", format.extension()),
+            Err(e) => e.to_string(),
+        };
+        s.pop_layer();
+        s.add_layer(Dialog::info(message));
+    };
+    let save_button = save.clone();
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new("File (.json, .csv or .html):"))
+                .child(
+                    EditView::new()
+                        .content(default_path.display().to_string())
+                        .on_submit(save)
+                        .with_name("report_path")
+                        .fixed_width(60),
+                ),
+        )
+        .title("Save report")
+        .button("Save", move |s| {
+            let path = s
+                .call_on_name("report_path", |v: &mut EditView| v.get_content())
+                .unwrap_or_default();
+            save_button(s, &path)
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+/// Shows files organized per week, the largest runs and the most recent ones.
+fn show_history_dialog(s: &mut Cursive, history: Option<&HistoryStore>) {
+    let entries = match history.map(|h| h.load()) {
+        Some(Ok(entries)) => entries,
+        Some(Err(e)) => {
+            s.add_layer(Dialog::info(e.to_string()));
+            return;
+        }
+        None => Vec::new(),
+    };
+    let text = if entries.is_empty() {
+        "No runs recorded yet.".to_string()
+    } else {
+        let recent: Vec<_> = entries.iter().rev().take(20).collect();
+        format!(
+            "Per week:\n{}\n\nLargest runs:\n{}\n\nRecent runs:\n{}",
+            weekly_table(&weekly_totals(&entries)),
+            runs_table(&largest_runs(&entries, 5)),
+            runs_table(&recent),
+        )
+    };
+    s.add_layer(
+        Dialog::around(TextView::new(text).scrollable().max_height(30))
+            .title("History")
+            .button("Close", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+/// Lists the configured schedules and whether their timers or crontab lines exist.
+fn show_schedules_dialog(s: &mut Cursive) {
+    let config = s.user_data::<Config>().cloned().unwrap_or_default();
+    let text = if config.schedules.is_empty() {
+        "No schedules configured.".to_string()
+    } else {
+        let uses_cron = config.schedules.iter().any(|s| s.backend == Backend::Cron);
+        let crontab = if uses_cron {
+            read_crontab().unwrap_or_default()
+        } else {
+            String::new()
+        };
+        schedules_table(&config.schedules, &crontab)
+    };
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(text).scrollable().max_height(20))
+                .child(TextView::new(
+                    "\nAdd and remove them with `rustganizer schedule add` and `remove`.",
+                )),
+        )
+        .title("Schedules")
+        .button("Close", |s| {
+            s.pop_layer();
+        }),
+    );
+}
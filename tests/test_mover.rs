@@ -0,0 +1,1004 @@
+#[cfg(test)]
+mod tests {
+    use rustganizer::config::{Config, RetentionRule};
+    use rustganizer::error::Error;
+    use rustganizer::organizer::engine::{Organizer, OrganizerBuilder};
+    use rustganizer::organizer::types::{
+        ActionKind, CancellationToken, Category, CollisionPolicy, FolderStrategy, ItemStatus,
+        ProgressEvent, RetentionAction, SkipReason, SymlinkPolicy,
+    };
+    use rustganizer::platform::filesystem::{FileKind, FileSystem, MemoryFileSystem, Owner};
+    use rustganizer::platform::user::StaticUserProvider;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime};
+
+    fn setup() -> Arc<MemoryFileSystem> {
+        let fs = Arc::new(MemoryFileSystem::new());
+        fs.add_dir("/home/alice/Downloads");
+        fs.add_dir("/home/alice/Desktop");
+        fs
+    }
+
+    fn organizer(fs: &Arc<MemoryFileSystem>, lang: &str, policy: CollisionPolicy) -> Organizer {
+        let users = StaticUserProvider::new()
+            .with_user("alice", "/home/alice")
+            .with_user("bob", "/home/bob");
+        Organizer::builder()
+            .language(lang)
+            .user_provider(Arc::new(users))
+            .filesystem(fs.clone())
+            .collision_policy(policy)
+            .build()
+    }
+
+    /// Builder organizing alice's home with the default config changed by `configure`
+    fn organizer_with(
+        fs: &Arc<MemoryFileSystem>,
+        configure: impl FnOnce(&mut Config),
+    ) -> OrganizerBuilder {
+        let mut config = Config::default();
+        configure(&mut config);
+        Organizer::builder()
+            .config(config)
+            .user_provider(Arc::new(
+                StaticUserProvider::new().with_user("alice", "/home/alice"),
+            ))
+            .filesystem(fs.clone())
+    }
+
+    fn exists(fs: &MemoryFileSystem, path: &str) -> bool {
+        fs.exists(Path::new(path))
+    }
+
+    #[test]
+    fn test_unrecognized_extensions_are_counted_per_run() {
+        let fs = setup();
+        fs.add_file("/home/alice/Downloads/setup.bin", "12345");
+        fs.add_file("/home/alice/Downloads/other.BIN", "123");
+        fs.add_file("/home/alice/Downloads/README", "data");
+        fs.add_file("/home/alice/Downloads/backup.zip", "data");
+        fs.add_file("/home/alice/Downloads/song.mp3", "data");
+
+        let report = organizer(&fs, "en", CollisionPolicy::Rename)
+            .run("alice")
+            .unwrap();
+
+        let unrecognized: Vec<(&str, usize, u64)> = report
+            .unrecognized
+            .iter()
+            .map(|(extension, tally)| (extension.as_str(), tally.files, tally.bytes))
+            .collect();
+        // Archives have a list of their own, so they are not unrecognised
+        assert_eq!(unrecognized, [("", 1, 4), ("bin", 2, 8)]);
+    }
+
+    #[test]
+    fn test_rename_policy_keeps_both_files() {
+        let fs = setup();
+        fs.add_file("/home/alice/Documents/report.pdf", "old");
+        fs.add_file("/home/alice/Documents/report (1).pdf", "older");
+        fs.add_file("/home/alice/Downloads/report.pdf", "new");
+        fs.add_file("/home/alice/Desktop/report.pdf", "desk");
+
+        let report = organizer(&fs, "en", CollisionPolicy::Rename)
+            .run("alice")
+            .unwrap();
+
+        assert_eq!(report.stats.docs, 2);
+        assert_eq!(
+            fs.contents("/home/alice/Documents/report.pdf").unwrap(),
+            b"old"
+        );
+        assert_eq!(
+            fs.contents("/home/alice/Documents/report (2).pdf").unwrap(),
+            b"new"
+        );
+        assert_eq!(
+            fs.contents("/home/alice/Documents/report (3).pdf").unwrap(),
+            b"desk"
+        );
+    }
+
+    #[test]
+    fn test_skip_policy_leaves_source_in_place() {
+        let fs = setup();
+        fs.add_file("/home/alice/Pictures/trip/old.jpg", "old");
+        fs.add_file("/home/alice/Downloads/trip/new.jpg", "new");
+
+        let report = organizer(&fs, "en", CollisionPolicy::Skip)
+            .run("alice")
+            .unwrap();
+
+        assert_eq!(report.stats.images, 0);
+        assert!(matches!(report.items[0].status, ItemStatus::Skipped { .. }));
+        assert!(exists(&fs, "/home/alice/Downloads/trip/new.jpg"));
+        assert!(!exists(&fs, "/home/alice/Pictures/trip/new.jpg"));
+    }
+
+    #[test]
+    fn test_plan_then_dry_run_does_not_touch_filesystem() {
+        let fs = setup();
+        fs.add_file("/home/alice/Downloads/empty.txt", "");
+        fs.add_file("/home/alice/Downloads/album/01.mp3", "data");
+        fs.add_file("/home/alice/Downloads/song.mp3", "data");
+        let before = fs.paths();
+
+        let organizer = Organizer::builder()
+            .user_provider(Arc::new(
+                StaticUserProvider::new().with_user("alice", "/home/alice"),
+            ))
+            .filesystem(fs.clone())
+            .dry_run(true)
+            .build();
+        let plan = organizer.plan("alice").unwrap();
+        let kinds: Vec<ActionKind> = plan.actions.iter().map(|a| a.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ActionKind::DeleteEmpty,
+                ActionKind::MoveFolder,
+                ActionKind::MoveFile
+            ]
+        );
+
+        let report = organizer.execute(&plan).unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.stats.music, 2);
+        assert!(report.items.iter().all(|i| i.status == ItemStatus::DryRun));
+        assert_eq!(fs.paths(), before);
+    }
+
+    #[test]
+    fn test_progress_and_cancellation() {
+        let fs = setup();
+        fs.add_file("/home/alice/Downloads/a.mp3", "data");
+        fs.add_file("/home/alice/Downloads/b.mp3", "data");
+
+        let token = CancellationToken::new();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink_events = events.clone();
+        let sink_token = token.clone();
+        let mut config = Config::default();
+        config.performance.use_parallel_processing = false;
+        let organizer = Organizer::builder()
+            .config(config)
+            .user_provider(Arc::new(
+                StaticUserProvider::new().with_user("alice", "/home/alice"),
+            ))
+            .filesystem(fs.clone())
+            .cancellation(token.clone())
+            .progress(Arc::new(move |event: &ProgressEvent| {
+                if let ProgressEvent::Item { completed, .. } = event {
+                    sink_events.lock().unwrap().push(*completed);
+                    sink_token.cancel();
+                }
+            }))
+            .build();
+
+        let report = organizer.run("alice").unwrap();
+
+        assert!(report.cancelled);
+        assert_eq!(report.stats.music, 1);
+        assert_eq!(*events.lock().unwrap(), vec![1, 2]);
+        assert!(exists(&fs, "/home/alice/Downloads/b.mp3"));
+        assert!(matches!(
+            organizer.plan("alice").unwrap_err(),
+            Error::Cancelled
+        ));
+    }
+
+    fn populate_many(fs: &MemoryFileSystem) {
+        for i in 0..200 {
+            let ext = ["mp3", "mkv", "png", "pdf", "bin"][i % 5];
+            fs.add_file(format!("/home/alice/Downloads/file{}.{ext}", i % 40), "d");
+            fs.add_file(format!("/home/alice/Desktop/file{}.{ext}", i % 40), "d");
+        }
+        for i in 0..20 {
+            fs.add_file(format!("/home/alice/Downloads/album{i}/track1.mp3"), "d");
+            fs.add_file(format!("/home/alice/Downloads/album{i}/track2.mp3"), "d");
+            fs.add_file(format!("/home/alice/Downloads/album{i}/cover.jpg"), "d");
+            fs.add_file(format!("/home/alice/Downloads/album{i}/extra.ogg"), "d");
+        }
+    }
+
+    #[test]
+    fn test_parallel_execution_matches_serial() {
+        let mut reports = Vec::new();
+        let mut trees = Vec::new();
+        for parallel in [false, true] {
+            let fs = setup();
+            populate_many(&fs);
+            let mut config = Config::default();
+            config.performance.use_parallel_processing = parallel;
+            config.performance.thread_pool_size = 8;
+            let organizer = Organizer::builder()
+                .config(config)
+                .user_provider(Arc::new(
+                    StaticUserProvider::new().with_user("alice", "/home/alice"),
+                ))
+                .filesystem(fs.clone())
+                .build();
+            reports.push(organizer.run("alice").unwrap());
+            trees.push(fs.paths());
+        }
+
+        assert_eq!(reports[0].stats, reports[1].stats);
+        assert_eq!(reports[0].stats.music, 20 + 16);
+        assert!(reports[1].errors.is_empty());
+        let destinations = |i: usize| -> Vec<_> {
+            reports[i]
+                .items
+                .iter()
+                .map(|item| (item.action.source.clone(), item.status.clone()))
+                .collect()
+        };
+        assert_eq!(destinations(0), destinations(1));
+        assert_eq!(trees[0], trees[1]);
+        assert!(trees[1].contains(&PathBuf::from("/home/alice/Music/album7/cover.jpg")));
+    }
+
+    #[test]
+    fn test_run_users_isolates_failures() {
+        let fs = setup();
+        fs.add_file("/home/alice/Downloads/song.mp3", "data");
+        fs.add_file("/home/bob/Downloads/movie.mkv", "data");
+        let mut config = Config::default();
+        config.performance.max_concurrent_users = 2;
+        let organizer = Organizer::builder()
+            .config(config)
+            .user_provider(Arc::new(
+                StaticUserProvider::new()
+                    .with_user("alice", "/home/alice")
+                    .with_user("bob", "/home/bob"),
+            ))
+            .filesystem(fs.clone())
+            .build();
+
+        let users = ["alice", "mallory", "bob"].map(String::from);
+        let report = organizer.run_users(&users);
+
+        let names: Vec<&str> = report.users.iter().map(|u| u.username.as_str()).collect();
+        assert_eq!(names, vec!["alice", "mallory", "bob"]);
+        assert_eq!(report.users[0].report.as_ref().unwrap().stats.music, 1);
+        assert!(report.users[1].report.is_none());
+        assert!(report.users[1]
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("not found"));
+        assert_eq!(report.users[2].report.as_ref().unwrap().stats.videos, 1);
+        assert_eq!(report.totals.music + report.totals.videos, 2);
+        assert_eq!(report.errors().len(), 1);
+    }
+
+    #[test]
+    fn test_only_approved_actions_of_reviewed_plans_run() {
+        let fs = setup();
+        fs.add_file("/home/alice/Downloads/song.mp3", "data");
+        fs.add_file("/home/alice/Downloads/clip.mp4", "video");
+        fs.add_file("/home/alice/Downloads/cover.mp3", "art");
+        let organizer = organizer(&fs, "en", CollisionPolicy::Rename);
+
+        let users = ["alice", "mallory"].map(String::from);
+        let mut plans = organizer.plan_users(&users);
+        assert!(plans[1].plan.is_none() && plans[1].error.is_some());
+        let plan = plans[0].plan.as_mut().unwrap();
+        assert_eq!(plan.actions.len(), 3);
+        let size_of = |plan: &rustganizer::organizer::types::Plan, name: &str| {
+            plan.actions
+                .iter()
+                .find(|a| a.source.ends_with(name))
+                .map(|a| a.size)
+        };
+        assert_eq!(size_of(plan, "clip.mp4"), Some(5));
+
+        let cover = plan
+            .actions
+            .iter()
+            .position(|a| a.source.ends_with("cover.mp3"))
+            .unwrap();
+        organizer.recategorize(plan, cover, Category::Images);
+        assert_eq!(
+            plan.actions[cover].destination.as_deref(),
+            Some(Path::new("/home/alice/Pictures/cover.mp3"))
+        );
+        plan.actions.retain(|a| !a.source.ends_with("clip.mp4"));
+
+        let report = organizer.execute_plans(&plans);
+        let stats = &report.users[0].report.as_ref().unwrap().stats;
+        assert_eq!((stats.music, stats.videos, stats.images), (1, 0, 1));
+        assert!(exists(&fs, "/home/alice/Music/song.mp3"));
+        assert!(exists(&fs, "/home/alice/Pictures/cover.mp3"));
+        assert!(exists(&fs, "/home/alice/Downloads/clip.mp4"));
+        assert!(report.users[1].error.is_some());
+    }
+
+    #[test]
+    fn test_folders_left_alone_are_reported() {
+        let fs = setup();
+        // A checkout deeper inside keeps its enclosing folder in place
+        fs.add_file("/home/alice/Downloads/stuff/repo/.git/HEAD", "ref");
+        fs.add_file("/home/alice/Downloads/stuff/notes.txt", "data");
+        fs.add_file("/home/alice/Downloads/mixed/a.mp3", "data");
+        fs.add_file("/home/alice/Downloads/mixed/b.pdf", "data");
+        fs.add_file("/home/alice/Downloads/junk/one.mp3", "data");
+        for i in 0..5 {
+            fs.add_file(format!("/home/alice/Downloads/junk/blob{i}.bin"), "data");
+        }
+
+        let report = organizer(&fs, "en", CollisionPolicy::Rename)
+            .run("alice")
+            .unwrap();
+
+        assert_eq!(report.stats.total(), 0);
+        let reasons: Vec<(String, SkipReason)> = report
+            .skipped
+            .iter()
+            .map(|s| {
+                (
+                    s.path.file_name().unwrap().to_string_lossy().into_owned(),
+                    s.reason.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                ("junk".to_string(), SkipReason::UnrecognizedFolder),
+                ("mixed".to_string(), SkipReason::MixedFolder),
+                (
+                    "stuff".to_string(),
+                    SkipReason::ProjectFolder {
+                        marker: ".git".to_string()
+                    }
+                ),
+            ]
+        );
+        assert!(exists(&fs, "/home/alice/Downloads/stuff/notes.txt"));
+    }
+
+    #[test]
+    fn test_split_strategy_moves_files_by_category() {
+        let fs = setup();
+        fs.add_file("/home/alice/Downloads/mixed/a.mp3", "data");
+        fs.add_file("/home/alice/Downloads/mixed/docs/b.pdf", "data");
+        fs.add_file("/home/alice/Downloads/mixed/deep/er/c.png", "data");
+        fs.add_file("/home/alice/Downloads/leftover/keep.bin", "data");
+        fs.add_file("/home/alice/Downloads/leftover/d.ogg", "data");
+
+        let report = organizer_with(&fs, |c| {
+            c.sources[0].folder_strategy = FolderStrategy::Split
+        })
+        .build()
+        .run("alice")
+        .unwrap();
+
+        assert_eq!(
+            (report.stats.music, report.stats.images, report.stats.docs),
+            (2, 1, 1)
+        );
+        assert!(exists(&fs, "/home/alice/Music/mixed/a.mp3"));
+        assert!(exists(&fs, "/home/alice/Documents/mixed/docs/b.pdf"));
+        assert!(exists(&fs, "/home/alice/Pictures/mixed/deep/er/c.png"));
+        assert!(exists(&fs, "/home/alice/Music/leftover/d.ogg"));
+        // Emptied folders are cleaned up, folders with leftovers are kept
+        assert!(!exists(&fs, "/home/alice/Downloads/mixed"));
+        assert!(exists(&fs, "/home/alice/Downloads/leftover/keep.bin"));
+        assert!(report.errors.is_empty());
+    }
+
+    #[test]
+    fn test_split_strategy_keeps_projects_together() {
+        let fs = setup();
+        fs.add_file("/home/alice/Downloads/app/package.json", "{}");
+        fs.add_file("/home/alice/Downloads/app/logo.png", "data");
+
+        let report = organizer_with(&fs, |c| {
+            c.sources[0].folder_strategy = FolderStrategy::Split
+        })
+        .build()
+        .run("alice")
+        .unwrap();
+
+        assert_eq!(report.stats.code, 1);
+        assert!(exists(&fs, "/home/alice/Projects/app/package.json"));
+        assert!(exists(&fs, "/home/alice/Projects/app/logo.png"));
+    }
+
+    #[test]
+    fn test_skip_strategy_ignores_folders() {
+        let fs = setup();
+        fs.add_file("/home/alice/Downloads/album/a.mp3", "data");
+        fs.add_file("/home/alice/Downloads/b.mp3", "data");
+
+        let report = organizer_with(&fs, |c| c.sources[0].folder_strategy = FolderStrategy::Skip)
+            .build()
+            .run("alice")
+            .unwrap();
+
+        assert_eq!(report.stats.music, 1);
+        assert!(exists(&fs, "/home/alice/Downloads/album/a.mp3"));
+    }
+
+    #[test]
+    fn test_ignored_entries_are_reported_with_pattern() {
+        let fs = setup();
+        fs.add_file(
+            "/home/alice/Downloads/.rustganizerignore",
+            "*.iso\n/keep.mp3\n",
+        );
+        fs.add_file("/home/alice/Downloads/keep.mp3", "data");
+        fs.add_file("/home/alice/Downloads/song.mp3", "data");
+        fs.add_file("/home/alice/Downloads/disk.iso", "data");
+        fs.add_file("/home/alice/Desktop/shortcut.png", "data");
+        // The ignored cover must not turn the album into a mixed folder
+        fs.add_file(
+            "/home/alice/Downloads/album/.rustganizerignore",
+            "cover.png\n",
+        );
+        fs.add_file("/home/alice/Downloads/album/a.mp3", "data");
+        fs.add_file("/home/alice/Downloads/album/cover.png", "data");
+        let config = Config {
+            ignore_patterns: vec!["shortcut.*".to_string()],
+            ..Config::default()
+        };
+
+        let report = Organizer::builder()
+            .config(config)
+            .user_provider(Arc::new(
+                StaticUserProvider::new().with_user("alice", "/home/alice"),
+            ))
+            .filesystem(fs.clone())
+            .build()
+            .run("alice")
+            .unwrap();
+
+        let ignored: Vec<(String, String)> = report
+            .skipped
+            .iter()
+            .filter_map(|s| match &s.reason {
+                SkipReason::Ignored { pattern } => {
+                    Some((s.path.display().to_string(), pattern.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            ignored,
+            vec![
+                (
+                    "/home/alice/Downloads/disk.iso".to_string(),
+                    "*.iso".to_string()
+                ),
+                (
+                    "/home/alice/Downloads/keep.mp3".to_string(),
+                    "/keep.mp3".to_string()
+                ),
+                (
+                    "/home/alice/Desktop/shortcut.png".to_string(),
+                    "shortcut.*".to_string()
+                ),
+            ]
+        );
+        assert!(exists(&fs, "/home/alice/Downloads/keep.mp3"));
+        assert!(exists(&fs, "/home/alice/Desktop/shortcut.png"));
+        assert!(exists(&fs, "/home/alice/Music/song.mp3"));
+        assert!(exists(&fs, "/home/alice/Music/album/cover.png"));
+        assert_eq!(report.stats.images, 0);
+    }
+
+    #[test]
+    fn test_pin_keeps_entry_in_place() {
+        let fs = setup();
+        fs.add_file("/home/alice/Downloads/song.mp3", "data");
+        let organizer = organizer(&fs, "en", CollisionPolicy::Rename);
+
+        let sources = organizer.source_dirs("alice").unwrap();
+        assert_eq!(
+            organizer.source_entries(&sources[0].path).unwrap(),
+            [Path::new("/home/alice/Downloads/song.mp3")]
+        );
+        let pattern = organizer
+            .pin(
+                "alice",
+                &sources[0].path,
+                Path::new("/home/alice/Downloads/song.mp3"),
+            )
+            .unwrap();
+        let report = organizer.run("alice").unwrap();
+
+        assert_eq!(pattern, "/song.mp3");
+        assert_eq!(report.stats.total(), 0);
+        assert!(exists(&fs, "/home/alice/Downloads/song.mp3"));
+    }
+
+    #[test]
+    fn test_symlinks_and_special_files_are_skipped_by_default() {
+        let fs = setup();
+        fs.add_file("/data/song.mp3", "data");
+        fs.add_symlink("/home/alice/Downloads/root", "/");
+        fs.add_symlink("/home/alice/Downloads/song.mp3", "/data/song.mp3");
+        fs.add_special("/home/alice/Downloads/pipe.mp3", FileKind::Fifo);
+
+        let report = organizer_with(&fs, |c| c.traversal.symlinks = SymlinkPolicy::Skip)
+            .build()
+            .run("alice")
+            .unwrap();
+
+        let reasons: Vec<String> = report
+            .skipped
+            .iter()
+            .map(|s| s.reason.to_string())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec!["fifo", "symlink to /", "symlink to /data/song.mp3"]
+        );
+        assert_eq!(report.stats.total(), 0);
+        assert!(report.items.is_empty());
+    }
+
+    #[test]
+    fn test_move_link_policy_moves_only_the_link() {
+        let fs = setup();
+        fs.add_file("/data/song.mp3", "data");
+        fs.add_symlink("/home/alice/Downloads/song.mp3", "/data/song.mp3");
+
+        let report = organizer_with(&fs, |c| c.traversal.symlinks = SymlinkPolicy::MoveLink)
+            .build()
+            .run("alice")
+            .unwrap();
+
+        assert_eq!(report.items[0].action.kind, ActionKind::MoveLink);
+        assert_eq!(report.stats.music, 1);
+        assert_eq!(
+            fs.read_link(Path::new("/home/alice/Music/song.mp3"))
+                .unwrap(),
+            Path::new("/data/song.mp3")
+        );
+        assert!(exists(&fs, "/data/song.mp3"));
+    }
+
+    #[test]
+    fn test_follow_policy_classifies_linked_folder_and_moves_link() {
+        let fs = setup();
+        fs.add_file("/mnt/nas/album/a.mp3", "data");
+        fs.add_file("/mnt/nas/album/b.ogg", "data");
+        fs.add_symlink("/mnt/nas/album/loop", "/mnt/nas/album");
+        fs.add_symlink("/home/alice/Downloads/album", "/mnt/nas/album");
+
+        let report = organizer_with(&fs, |c| c.traversal.symlinks = SymlinkPolicy::Follow)
+            .build()
+            .run("alice")
+            .unwrap();
+
+        assert_eq!(report.items.len(), 1);
+        assert_eq!(report.items[0].action.kind, ActionKind::MoveLink);
+        assert_eq!(report.items[0].status, ItemStatus::Moved);
+        assert!(fs
+            .symlink_metadata(Path::new("/home/alice/Music/album"))
+            .unwrap()
+            .is_symlink());
+        assert!(exists(&fs, "/mnt/nas/album/a.mp3"));
+    }
+
+    const ALICE: Owner = Owner {
+        uid: 1000,
+        gid: 1000,
+    };
+
+    fn owned_home() -> Arc<MemoryFileSystem> {
+        let fs = setup();
+        for path in [
+            "/home/alice",
+            "/home/alice/Downloads",
+            "/home/alice/Desktop",
+        ] {
+            fs.set_owner(Path::new(path), ALICE).unwrap();
+        }
+        fs.add_file("/home/alice/Downloads/song.mp3", "data");
+        fs.set_owner(Path::new("/home/alice/Downloads/song.mp3"), ALICE)
+            .unwrap();
+        fs
+    }
+
+    #[test]
+    fn test_root_gives_created_folders_to_home_owner() {
+        let fs = owned_home();
+        fs.set_current_owner(Some(Owner { uid: 0, gid: 0 }));
+
+        let report = organizer_with(&fs, |_| {})
+            .acting_uid(Some(0))
+            .build()
+            .run("alice")
+            .unwrap();
+
+        assert_eq!(report.stats.music, 1);
+        let music = fs.metadata(Path::new("/home/alice/Music")).unwrap();
+        assert_eq!((music.owner, music.mode), (Some(ALICE), Some(0o755)));
+        let song = fs
+            .metadata(Path::new("/home/alice/Music/song.mp3"))
+            .unwrap();
+        assert_eq!(song.owner, Some(ALICE));
+    }
+
+    #[test]
+    fn test_other_users_home_is_refused_without_root() {
+        let fs = owned_home();
+        fs.set_current_owner(Some(Owner {
+            uid: 1001,
+            gid: 1001,
+        }));
+        let organizer = organizer_with(&fs, |_| {}).acting_uid(Some(1001)).build();
+
+        assert!(matches!(
+            organizer.run("alice"),
+            Err(Error::PermissionDenied { .. })
+        ));
+        let report = organizer.run_users(&["alice".to_string()]);
+        assert_eq!(
+            report.errors(),
+            vec!["alice: Permission denied: \"/home/alice\"".to_string()]
+        );
+        assert!(exists(&fs, "/home/alice/Downloads/song.mp3"));
+        assert!(!exists(&fs, "/home/alice/Music"));
+
+        // The owner organizes their own home as usual
+        assert_eq!(
+            organizer_with(&fs, |_| {})
+                .acting_uid(Some(1000))
+                .build()
+                .run("alice")
+                .unwrap()
+                .stats
+                .music,
+            1
+        );
+    }
+
+    #[test]
+    fn test_pins_are_refused_or_handed_to_home_owner() {
+        let fs = owned_home();
+        let downloads = Path::new("/home/alice/Downloads");
+        let song = downloads.join("song.mp3");
+
+        assert!(matches!(
+            organizer_with(&fs, |_| {})
+                .acting_uid(Some(1001))
+                .build()
+                .pin("alice", downloads, &song),
+            Err(Error::PermissionDenied { .. })
+        ));
+        assert!(!exists(&fs, "/home/alice/Downloads/.rustganizerignore"));
+
+        fs.set_current_owner(Some(Owner { uid: 0, gid: 0 }));
+        organizer_with(&fs, |_| {})
+            .acting_uid(Some(0))
+            .build()
+            .pin("alice", downloads, &song)
+            .unwrap();
+        let ignore = fs
+            .metadata(Path::new("/home/alice/Downloads/.rustganizerignore"))
+            .unwrap();
+        assert_eq!(ignore.owner, Some(ALICE));
+    }
+
+    fn zip_bytes(files: &[(&str, &str)]) -> Vec<u8> {
+        use std::io::Write;
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn tar_gz_bytes(files: &[(&str, &str)]) -> Vec<u8> {
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        let mut builder = tar::Builder::new(encoder);
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_archives_go_to_the_category_of_their_contents() {
+        let fs = setup();
+        let album = [
+            ("a.mp3", "1"),
+            ("b.mp3", "2"),
+            ("c.mp3", "3"),
+            ("cover.jpg", "4"),
+        ];
+        fs.add_file("/home/alice/Downloads/album.zip", zip_bytes(&album));
+        let papers = [("papers/a.pdf", "1"), ("papers/b.pdf", "2")];
+        fs.add_file("/home/alice/Downloads/papers.tar.gz", tar_gz_bytes(&papers));
+        fs.add_file("/home/alice/Downloads/setup.rar", "not listable");
+        fs.add_file("/home/alice/Downloads/broken.zip", "not a zip");
+
+        let report = organizer_with(&fs, |c| c.archives.extract = false)
+            .build()
+            .run("alice")
+            .unwrap();
+
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(
+            (report.stats.music, report.stats.docs, report.stats.archives),
+            (1, 1, 2)
+        );
+        assert!(exists(&fs, "/home/alice/Music/album.zip"));
+        assert!(exists(&fs, "/home/alice/Documents/papers.tar.gz"));
+        assert!(exists(&fs, "/home/alice/Archives/setup.rar"));
+        assert!(exists(&fs, "/home/alice/Archives/broken.zip"));
+    }
+
+    #[test]
+    fn test_extracted_archives_are_unpacked_and_trashed() {
+        let fs = setup();
+        let album = [("a.mp3", "1"), ("disc 2/b.mp3", "2")];
+        fs.add_file("/home/alice/Downloads/album.zip", zip_bytes(&album));
+        let evil = [("../../.bashrc", "x"), ("c.mp3", "3")];
+        fs.add_file("/home/alice/Downloads/evil.zip", zip_bytes(&evil));
+
+        let organizer = organizer_with(&fs, |c| c.archives.extract = true).build();
+        let plan = organizer.plan("alice").unwrap();
+        let kinds: Vec<ActionKind> = plan.actions.iter().map(|a| a.kind).collect();
+        assert_eq!(kinds, [ActionKind::ExtractArchive, ActionKind::MoveFile]);
+
+        let report = organizer.execute(&plan).unwrap();
+
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(fs.contents("/home/alice/Music/album/a.mp3").unwrap(), b"1");
+        assert!(exists(&fs, "/home/alice/Music/album/disc 2/b.mp3"));
+        assert!(!exists(&fs, "/home/alice/Downloads/album.zip"));
+        assert!(exists(
+            &fs,
+            "/home/alice/.local/share/Trash/files/album.zip"
+        ));
+        assert!(exists(
+            &fs,
+            "/home/alice/.local/share/Trash/info/album.zip.trashinfo"
+        ));
+        // An archive reaching outside its folder is moved, never unpacked
+        assert!(exists(&fs, "/home/alice/Archives/evil.zip"));
+        assert!(!exists(&fs, "/home/.bashrc"));
+    }
+
+    #[test]
+    fn test_repositories_and_scripts_go_to_projects() {
+        let fs = setup();
+        // Text files would make this checkout look like documents
+        fs.add_file("/home/alice/Downloads/tool/.git/HEAD", "ref");
+        fs.add_file("/home/alice/Downloads/tool/README.md", "data");
+        fs.add_file("/home/alice/Downloads/tool/notes.txt", "data");
+        fs.add_file("/home/alice/Downloads/lib/pyproject.toml", "data");
+        fs.add_file("/home/alice/Downloads/lib/src/lib/__init__.py", "data");
+        fs.add_file("/home/alice/Downloads/backup.sh", "data");
+        fs.add_file("/home/alice/Downloads/main.rs", "data");
+
+        let report = organizer(&fs, "en", CollisionPolicy::Rename)
+            .run("alice")
+            .unwrap();
+
+        assert_eq!(report.stats.code, 4);
+        assert_eq!(report.stats.docs, 0);
+        assert!(exists(&fs, "/home/alice/Projects/tool/.git/HEAD"));
+        assert!(exists(&fs, "/home/alice/Projects/tool/notes.txt"));
+        assert!(exists(&fs, "/home/alice/Projects/lib/src/lib/__init__.py"));
+        assert!(exists(&fs, "/home/alice/Projects/backup.sh"));
+        assert!(exists(&fs, "/home/alice/Projects/main.rs"));
+        assert!(!exists(&fs, "/home/alice/Downloads/tool"));
+    }
+
+    #[test]
+    fn test_retention_rules_clean_up_stale_files() {
+        let fs = setup();
+        let days_ago = |days: u64| SystemTime::now() - Duration::from_secs(days * 24 * 60 * 60);
+        fs.add_file_modified("/home/alice/Desktop/setup.deb", "deb", days_ago(40));
+        fs.add_file_modified("/home/alice/Downloads/tool.AppImage", "app", days_ago(10));
+        fs.add_file_modified("/home/alice/Downloads/old.mp3", "old", days_ago(100));
+        fs.add_file("/home/alice/Downloads/new.mp3", "new");
+        let retention = vec![
+            RetentionRule {
+                older_than_days: 30,
+                extensions: vec!["deb".to_string(), "appimage".to_string()],
+                categories: Vec::new(),
+                sources: Vec::new(),
+                action: RetentionAction::Trash,
+                move_to: None,
+            },
+            RetentionRule {
+                older_than_days: 90,
+                extensions: Vec::new(),
+                categories: Vec::new(),
+                sources: vec!["Downloads".to_string()],
+                action: RetentionAction::Move,
+                move_to: Some("Old downloads".into()),
+            },
+        ];
+        let organizer = |dry_run| {
+            organizer_with(&fs, |c| c.retention = retention.clone())
+                .dry_run(dry_run)
+                .build()
+        };
+
+        let dry_run = organizer(true).run("alice").unwrap();
+        assert_eq!((dry_run.stats.cleaned, dry_run.stats.music), (2, 1));
+        assert!(exists(&fs, "/home/alice/Desktop/setup.deb"));
+        assert!(exists(&fs, "/home/alice/Downloads/old.mp3"));
+
+        let report = organizer(false).run("alice").unwrap();
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!((report.stats.cleaned, report.stats.music), (2, 1));
+        assert_eq!(report.stats.total(), 1);
+        assert!(exists(
+            &fs,
+            "/home/alice/.local/share/Trash/files/setup.deb"
+        ));
+        assert!(exists(&fs, "/home/alice/Old downloads/old.mp3"));
+        assert!(exists(&fs, "/home/alice/Music/new.mp3"));
+        // Too recent for the rule, and installers have no folder of their own
+        assert!(exists(&fs, "/home/alice/Downloads/tool.AppImage"));
+    }
+
+    /// The wrapper kept for callers of the function-style API
+    mod organize_files_with {
+        use rustganizer::config::Config;
+        use rustganizer::organizer::mover::organize_files_with;
+        use rustganizer::platform::filesystem::{FileSystem, MemoryFileSystem};
+        use rustganizer::platform::user::StaticUserProvider;
+        use std::path::Path;
+
+        fn setup() -> (MemoryFileSystem, StaticUserProvider) {
+            let fs = MemoryFileSystem::new();
+            fs.add_dir("/home/alice/Downloads");
+            fs.add_dir("/home/alice/Desktop");
+            let users = StaticUserProvider::new().with_user("alice", "/home/alice");
+            (fs, users)
+        }
+
+        fn exists(fs: &MemoryFileSystem, path: &str) -> bool {
+            fs.exists(Path::new(path))
+        }
+
+        #[test]
+        fn test_files_are_moved_by_extension() {
+            let (fs, users) = setup();
+            fs.add_file("/home/alice/Downloads/song.mp3", "data");
+            fs.add_file("/home/alice/Downloads/clip.MP4", "data");
+            fs.add_file("/home/alice/Desktop/photo.png", "data");
+            fs.add_file("/home/alice/Desktop/paper.pdf", "data");
+
+            let stats =
+                organize_files_with("alice", "en", &Config::default(), &users, &fs).unwrap();
+
+            assert_eq!(
+                (stats.music, stats.videos, stats.images, stats.docs),
+                (1, 1, 1, 1)
+            );
+            assert!(exists(&fs, "/home/alice/Music/song.mp3"));
+            assert!(exists(&fs, "/home/alice/Videos/clip.MP4"));
+            assert!(exists(&fs, "/home/alice/Pictures/photo.png"));
+            assert!(exists(&fs, "/home/alice/Documents/paper.pdf"));
+            assert!(!exists(&fs, "/home/alice/Downloads/song.mp3"));
+        }
+
+        #[test]
+        fn test_unknown_extensions_are_left_alone() {
+            let (fs, users) = setup();
+            fs.add_file("/home/alice/Downloads/setup.bin", "data");
+            fs.add_file("/home/alice/Downloads/README", "data");
+
+            let stats =
+                organize_files_with("alice", "en", &Config::default(), &users, &fs).unwrap();
+
+            assert_eq!(stats.music + stats.videos + stats.images + stats.docs, 0);
+            assert!(exists(&fs, "/home/alice/Downloads/setup.bin"));
+            assert!(exists(&fs, "/home/alice/Downloads/README"));
+        }
+
+        #[test]
+        fn test_folder_moved_by_majority() {
+            let (fs, users) = setup();
+            fs.add_file("/home/alice/Downloads/album/01.mp3", "data");
+            fs.add_file("/home/alice/Downloads/album/02.mp3", "data");
+            fs.add_file("/home/alice/Downloads/album/cd2/03.flac", "data");
+            fs.add_file("/home/alice/Downloads/album/booklet.pdf", "data");
+
+            let stats =
+                organize_files_with("alice", "en", &Config::default(), &users, &fs).unwrap();
+
+            assert_eq!(stats.music, 1);
+            assert_eq!(stats.docs, 0);
+            assert!(exists(&fs, "/home/alice/Music/album/cd2/03.flac"));
+            assert!(exists(&fs, "/home/alice/Music/album/booklet.pdf"));
+            assert!(!exists(&fs, "/home/alice/Downloads/album"));
+        }
+
+        #[test]
+        fn test_desktop_folders_are_not_moved() {
+            let (fs, users) = setup();
+            fs.add_file("/home/alice/Desktop/project/notes.txt", "data");
+
+            let stats =
+                organize_files_with("alice", "en", &Config::default(), &users, &fs).unwrap();
+
+            assert_eq!(stats.docs, 0);
+            assert!(exists(&fs, "/home/alice/Desktop/project/notes.txt"));
+        }
+
+        #[test]
+        fn test_empty_files_are_deleted() {
+            let (fs, users) = setup();
+            fs.add_file("/home/alice/Downloads/empty.mp3", "");
+            fs.add_file("/home/alice/Desktop/empty.txt", "");
+
+            let stats =
+                organize_files_with("alice", "en", &Config::default(), &users, &fs).unwrap();
+
+            assert_eq!(stats.music + stats.docs, 0);
+            assert!(!exists(&fs, "/home/alice/Downloads/empty.mp3"));
+            assert!(!exists(&fs, "/home/alice/Desktop/empty.txt"));
+            assert!(!exists(&fs, "/home/alice/Music/empty.mp3"));
+        }
+
+        #[test]
+        fn test_folder_collision_merges_into_existing_destination() {
+            let (fs, users) = setup();
+            fs.add_file("/home/alice/Pictures/trip/old.jpg", "old");
+            fs.add_file("/home/alice/Downloads/trip/new.jpg", "new");
+
+            let stats =
+                organize_files_with("alice", "en", &Config::default(), &users, &fs).unwrap();
+
+            assert_eq!(stats.images, 1);
+            assert!(exists(&fs, "/home/alice/Pictures/trip/old.jpg"));
+            assert!(exists(&fs, "/home/alice/Pictures/trip/new.jpg"));
+            assert!(!exists(&fs, "/home/alice/Downloads/trip"));
+        }
+
+        #[test]
+        fn test_file_collision_replaces_existing_destination() {
+            let (fs, users) = setup();
+            fs.add_file("/home/alice/Documents/report.pdf", "old");
+            fs.add_file("/home/alice/Downloads/report.pdf", "new");
+
+            organize_files_with("alice", "en", &Config::default(), &users, &fs).unwrap();
+
+            assert_eq!(
+                fs.contents("/home/alice/Documents/report.pdf").unwrap(),
+                b"new"
+            );
+        }
+
+        #[test]
+        fn test_spanish_directories() {
+            let fs = MemoryFileSystem::new();
+            fs.add_file("/home/bob/Descargas/cancion.ogg", "data");
+            let users = StaticUserProvider::new().with_user("bob", "/home/bob");
+
+            let stats = organize_files_with("bob", "es", &Config::default(), &users, &fs).unwrap();
+
+            assert_eq!(stats.music, 1);
+            assert!(exists(&fs, "/home/bob/Música/cancion.ogg"));
+        }
+
+        #[test]
+        fn test_injected_provider_unknown_user() {
+            let (fs, users) = setup();
+            let err =
+                organize_files_with("mallory", "en", &Config::default(), &users, &fs).unwrap_err();
+            assert!(err.contains("not found"));
+        }
+    }
+}
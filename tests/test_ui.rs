@@ -12,3 +12,142 @@ mod tests {
         assert!(result.is_err());
     }
 }
+
+#[cfg(test)]
+mod summary_tests {
+    use rustganizer::organizer::types::{
+        ExtensionTally, FileStats, MultiUserReport, RunReport, UserRunResult,
+    };
+    use rustganizer::schedule::{Backend, Schedule};
+    use rustganizer::ui::summary::{format_size, schedules_table, summary_table};
+
+    #[test]
+    fn test_sizes_use_binary_units() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[test]
+    fn test_summary_table_lists_each_user() {
+        let alice = RunReport {
+            username: "alice".to_string(),
+            stats: FileStats {
+                music: 3,
+                videos: 0,
+                images: 12,
+                docs: 1,
+                archives: 2,
+                code: 4,
+                cleaned: 5,
+            },
+            errors: vec!["move file failed".to_string()],
+            ..RunReport::default()
+        };
+        let report = MultiUserReport {
+            totals: alice.stats.clone(),
+            users: vec![
+                UserRunResult {
+                    username: "alice".to_string(),
+                    report: Some(alice),
+                    error: None,
+                    ..UserRunResult::default()
+                },
+                UserRunResult {
+                    username: "bob".to_string(),
+                    report: None,
+                    error: Some("User bob not found".to_string()),
+                    ..UserRunResult::default()
+                },
+            ],
+        };
+
+        let table = summary_table(&report);
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "User   Music  Videos  Images  Docs  Archives  Code  Cleaned  Errors"
+        );
+        assert_eq!(
+            lines[1],
+            "alice      3       0      12     1         2     4        5       1"
+        );
+        assert_eq!(lines[2], "bob    not organized");
+        assert_eq!(
+            lines[4],
+            "Total      3       0      12     1         2     4        5       2"
+        );
+        assert!(table.contains("bob: User bob not found"));
+        assert!(table.contains("alice: move file failed"));
+    }
+
+    #[test]
+    fn test_summary_lists_unrecognized_extensions_with_suggestions() {
+        let alice = RunReport {
+            username: "alice".to_string(),
+            unrecognized: [
+                (
+                    "heic".to_string(),
+                    ExtensionTally {
+                        files: 3,
+                        bytes: 3072,
+                    },
+                ),
+                (
+                    "xyz".to_string(),
+                    ExtensionTally {
+                        files: 1,
+                        bytes: 10,
+                    },
+                ),
+            ]
+            .into(),
+            ..RunReport::default()
+        };
+        let report = MultiUserReport {
+            totals: FileStats::default(),
+            users: vec![UserRunResult {
+                username: "alice".to_string(),
+                report: Some(alice),
+                ..UserRunResult::default()
+            }],
+        };
+
+        let table = summary_table(&report);
+        assert!(table.contains(".heic           3 files     3.0 KiB  maybe Pictures"));
+        assert!(table
+            .lines()
+            .any(|l| l.starts_with(".xyz") && !l.contains("maybe")));
+    }
+
+    #[test]
+    fn test_schedules_table_shows_whether_each_is_installed() {
+        let schedule = |name: &str, when: &str, backend| Schedule {
+            name: name.to_string(),
+            when: when.parse().unwrap(),
+            users: Vec::new(),
+            dry_run: false,
+            backend,
+        };
+        let mut loop_run = schedule("loop", "hourly", Backend::Builtin);
+        loop_run.users = vec!["alice".to_string(), "bob".to_string()];
+        let schedules = [
+            schedule("nightly", "daily 02:00", Backend::Cron),
+            schedule("weekly", "weekly sun 22:00", Backend::Cron),
+            loop_run,
+        ];
+
+        let table = schedules_table(&schedules, "0 2 * * * rustganizer # rustganizer:nightly\n");
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "Name     When              Users      Backend  Status",
+                "nightly  daily 02:00       all        cron     installed",
+                "weekly   weekly sun 22:00  all        cron     not installed",
+                "loop     hourly            alice,bob  builtin  schedule run",
+            ]
+        );
+    }
+}
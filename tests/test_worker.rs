@@ -0,0 +1,134 @@
+#[cfg(all(test, unix))]
+mod tests {
+    use rustganizer::config::{Config, PerformanceConfig};
+    use rustganizer::organizer::engine::Organizer;
+    use rustganizer::organizer::types::ProgressEvent;
+    use rustganizer::organizer::worker::WorkerRequest;
+    use rustganizer::platform::audit::{AuditLog, AuditOperation, AuditRecord};
+    use rustganizer::platform::user::StaticUserProvider;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_users_are_organized_in_worker_processes() {
+        let home = tempfile::tempdir().unwrap();
+        let downloads = home.path().join("Downloads");
+        fs::create_dir_all(downloads.join("album")).unwrap();
+        fs::write(downloads.join("song.mp3"), "data").unwrap();
+        fs::write(downloads.join("album/a.mp3"), "data").unwrap();
+        fs::write(downloads.join("album/b.ogg"), "data").unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let audit_path = home.path().join("audit.jsonl");
+
+        let organizer = Organizer::builder()
+            .config(Config::default())
+            .user_provider(Arc::new(
+                StaticUserProvider::new().with_user("alice", home.path()),
+            ))
+            .privilege_separation(true)
+            .worker_command(env!("CARGO_BIN_EXE_rustganizer"))
+            .audit(Arc::new(AuditLog::open(&audit_path).unwrap()))
+            .progress(Arc::new(move |event: &ProgressEvent| {
+                sink.lock().unwrap().push(event.clone())
+            }))
+            .build();
+        let report = organizer.run_users(&["alice".to_string()]);
+
+        assert!(report.errors().is_empty(), "{:?}", report.errors());
+        assert_eq!(report.totals.music, 2);
+        assert!(home.path().join("Music/song.mp3").exists());
+        assert!(home.path().join("Music/album/b.ogg").exists());
+        let events = events.lock().unwrap();
+        assert!(matches!(
+            events.first(),
+            Some(ProgressEvent::Started { .. })
+        ));
+        assert!(matches!(
+            events.last(),
+            Some(ProgressEvent::Finished { .. })
+        ));
+
+        // The worker's mutations end up in the parent's audit log
+        let audit: Vec<AuditRecord> = fs::read_to_string(&audit_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let renamed = |path: &str| {
+            audit.iter().any(|r| {
+                r.operation == AuditOperation::Rename
+                    && r.path == downloads.join(path)
+                    && r.error.is_none()
+            })
+        };
+        assert!(renamed("song.mp3") && renamed("album"));
+        assert!(audit.iter().any(
+            |r| r.operation == AuditOperation::CreateDir && r.path == home.path().join("Music")
+        ));
+    }
+
+    #[test]
+    fn test_reviewed_plans_are_executed_in_worker_processes() {
+        let home = tempfile::tempdir().unwrap();
+        let downloads = home.path().join("Downloads");
+        fs::create_dir_all(&downloads).unwrap();
+        fs::write(downloads.join("song.mp3"), "data").unwrap();
+        fs::write(downloads.join("clip.mp4"), "data").unwrap();
+
+        let organizer = Organizer::builder()
+            .config(Config::default())
+            .user_provider(Arc::new(
+                StaticUserProvider::new().with_user("alice", home.path()),
+            ))
+            .privilege_separation(true)
+            .worker_command(env!("CARGO_BIN_EXE_rustganizer"))
+            .build();
+        let mut plans = organizer.plan_users(&["alice".to_string()]);
+        let plan = plans[0].plan.as_mut().expect("alice was planned");
+        assert_eq!(plan.actions.len(), 2);
+        assert!(plan.actions.iter().all(|a| a.size == 4));
+        plan.actions.retain(|a| a.source.ends_with("song.mp3"));
+
+        let report = organizer.execute_plans(&plans);
+        assert!(report.errors().is_empty(), "{:?}", report.errors());
+        assert!(home.path().join("Music/song.mp3").exists());
+        assert!(downloads.join("clip.mp4").exists());
+    }
+
+    #[test]
+    fn test_performance_config_reaches_the_worker() {
+        let home = tempfile::tempdir().unwrap();
+        fs::create_dir_all(home.path().join("Downloads")).unwrap();
+        // Stands in for the worker and keeps the request it was sent
+        let worker = home.path().join("worker.sh");
+        fs::write(&worker, "#!/bin/sh\nexec /bin/cat > request.json\n").unwrap();
+        fs::set_permissions(&worker, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = Config {
+            performance: PerformanceConfig {
+                use_parallel_processing: false,
+                thread_pool_size: 1,
+                ..PerformanceConfig::default()
+            },
+            ..Config::default()
+        };
+        let organizer = Organizer::builder()
+            .config(config)
+            .user_provider(Arc::new(
+                StaticUserProvider::new().with_user("alice", home.path()),
+            ))
+            .privilege_separation(true)
+            .worker_command(&worker)
+            .build();
+        organizer.run_users(&["alice".to_string()]);
+
+        let request: WorkerRequest =
+            serde_json::from_str(&fs::read_to_string(home.path().join("request.json")).unwrap())
+                .unwrap();
+        let performance = request.config().performance;
+        assert!(!performance.use_parallel_processing);
+        assert_eq!(performance.thread_pool_size, 1);
+    }
+}